@@ -0,0 +1,46 @@
+use rocket::{get, serde::json::Json};
+use crate::database::activity::{get_activity, ActivityTarget};
+use crate::database::share::ShareTarget;
+use crate::model::activity::{ActivityPage, ActivityQuery};
+use crate::model::share::ShareRole;
+use super::auth::AuthenticatedUser;
+use super::{require, Permission, Resource, Response};
+
+#[get("/tasks/<task_id>/history?<query..>")]
+/// Get the history of a task
+/// Every change to the task with who made it and the old and new values of the changed fields.
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `query` - The `cursor` from the previous page and the `limit` of activities to return.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to see the task.
+///
+/// # Returns
+/// * `Response<Json<ActivityPage>>` - A response indicating the result of the retrieval. If successful, it returns a page of activity, newest first, in JSON format.
+pub async fn get_task_history_handler(task_id: &str, query: ActivityQuery, user: AuthenticatedUser) -> Response<Json<ActivityPage>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_activity(ActivityTarget::Task(task_id), &query).await {
+        Ok(page) => Response::Ok(Json(page)),
+        Err(err) => Response::from(err).about(Resource::Task),
+    }
+}
+
+#[get("/users/me/activity?<query..>")]
+/// Get the activity feed of the user
+/// The changes the user has made and the changes to their account.
+///
+/// # Arguments
+/// * `query` - The `cursor` from the previous page and the `limit` of activities to return.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<ActivityPage>>` - A response indicating the result of the retrieval. If successful, it returns a page of activity, newest first, in JSON format.
+pub async fn get_my_activity_handler(query: ActivityQuery, user: AuthenticatedUser) -> Response<Json<ActivityPage>> {
+    match get_activity(ActivityTarget::User(&user.id), &query).await {
+        Ok(page) => Response::Ok(Json(page)),
+        Err(err) => Response::from(err).about(Resource::User),
+    }
+}
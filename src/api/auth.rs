@@ -2,9 +2,20 @@ use std::sync::LazyLock;
 
 use chrono::Duration;
 use jsonwebtoken::{encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use rocket::request::FromRequest;
+use rocket::{catch, post, request::FromRequest, serde::json::Json};
 use serde::{Deserialize, Serialize};
 
+use crate::{database::{tokens::{create_refresh_token, generate_random_token, get_token_generation, is_token_revoked, revoke_all_tokens, revoke_refresh_token, revoke_token, rotate_refresh_token}, users::get_user_by_id, DBCreateError, DBReadError}, model::{tokens::{RefreshRequest, TokenPair}, users::User}};
+
+use super::problem::Problem;
+use super::Response;
+
+/// How long access tokens are valid for
+pub const ACCESS_TOKEN_DURATION: Duration = Duration::minutes(15);
+
+/// How long refresh tokens are valid for
+pub const REFRESH_TOKEN_DURATION: Duration = Duration::days(30);
+
 /// Contains the public key used to verify the JWT token
 static PUBLIC_KEY: LazyLock<DecodingKey> = LazyLock::new(|| {
     // Load the public key from the file
@@ -19,15 +30,19 @@ static PRIVATE_KEY: LazyLock<EncodingKey> = LazyLock::new(|| {
     EncodingKey::from_rsa_pem(key.as_bytes()).unwrap()
 });
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// The claims that will be included in the JWT token
 /// 
 /// # Fields
 /// * `sub` - The subject of the token, usually the user ID
 /// * `exp` - The expiration time of the token, in seconds since the epoch
+/// * `jti` - The unique ID of the token, used to revoke it
+/// * `generation` - The token generation of the user when the token was made, used to revoke every older token
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub jti: String,
+    pub generation: i64,
 }
 
 /// Generate a JWT token for a user
@@ -37,8 +52,11 @@ pub struct Claims {
 /// * `duration` - The duration for which the token is valid.
 /// 
 /// # Returns
-/// * `String` - The generated JWT token.
-pub async fn generate_token(user_id: &str, duration: Duration) -> String {
+/// * `Result<String, DBReadError>` - The generated JWT token, or an error if the user's token generation could not be read.
+pub async fn generate_token(user_id: &str, duration: Duration) -> Result<String, DBReadError> {
+    // The token must carry the current generation or it will be treated as revoked
+    let generation = get_token_generation(user_id).await?;
+
     // Generate a JWT token with the claims
     let claims = Claims {
         sub: user_id.to_string(),
@@ -46,16 +64,19 @@ pub async fn generate_token(user_id: &str, duration: Duration) -> String {
             .checked_add_signed(duration)
             .expect("valid timestamp")
             .timestamp() as usize,
+        jti: generate_random_token(),
+        generation,
     };
 
     // Encode the token using the secret key
     let token = encode(&Header::new(Algorithm::RS512), &claims, &*PRIVATE_KEY)
         .expect("Failed to encode token");
 
-    token
+    Ok(token)
 }
 
 /// Verify a JWT token and extract the claims
+/// This also checks the token has not been revoked
 /// 
 /// # Arguments
 /// * `token` - The JWT token to be verified.
@@ -82,13 +103,135 @@ pub async fn verify_token(token: &str) -> Result<Claims, VerifyJWTError> {
             _ => VerifyJWTError::Other(error.to_string()),
         }
     })?;
+    let claims = token_data.claims;
+
+    // Check the token has not been revoked by logging out, changing password or deleting the account
+    let revoked = is_token_revoked(&claims.sub, &claims.jti, claims.generation)
+        .await
+        .map_err(|e| VerifyJWTError::Other(e.to_string()))?;
+    if revoked {
+        return Err(VerifyJWTError::Revoked);
+    }
 
     // Return the claims
-    Ok(token_data.claims)
+    Ok(claims)
+}
+
+/// Generate an access token and a refresh token for a user
+/// 
+/// # Arguments
+/// * `user_id` - The ID of the user for whom the tokens are generated.
+/// * `family` - The refresh token family to add to, if None then a new family is started.
+/// 
+/// # Returns
+/// * `Result<TokenPair, DBCreateError>` - The generated tokens, or an error if the refresh token could not be stored.
+pub async fn generate_token_pair(user_id: &str, family: Option<&str>) -> Result<TokenPair, DBCreateError> {
+    let refresh_token = create_refresh_token(user_id, family, REFRESH_TOKEN_DURATION).await?;
+    let access_token = generate_token(user_id, ACCESS_TOKEN_DURATION)
+        .await
+        .map_err(|e| DBCreateError::Other(e.to_string()))?;
+
+    Ok(TokenPair {
+        access_token,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_DURATION.num_seconds(),
+    })
+}
+
+#[post("/auth/refresh", data = "<input_refresh>")]
+/// Refresh a token pair
+/// This function handles swapping a refresh token for a new access token and refresh token.
+/// Each refresh token can only be used once, using one again revokes every token from the same log in.
+/// 
+/// # Arguments
+/// * `input_refresh` - A JSON payload containing the refresh token.
+/// 
+/// # Returns
+/// * `Response<Json<TokenPair>>` - A response indicating the result of the refresh. If successful, it returns the new tokens.
+pub async fn refresh_token_handler(
+    input_refresh: Json<RefreshRequest>
+) -> Response<Json<TokenPair>> {
+    let input_refresh = input_refresh.into_inner(); // Deserialise the input from JSON
+
+    // Rotate the refresh token, this also gives the new refresh token
+    let rotated = rotate_refresh_token(&input_refresh.refresh_token, REFRESH_TOKEN_DURATION).await;
+    let (user_id, refresh_token) = match rotated {
+        Ok(r) => r,
+        Err(err) => return Response::from(err),
+    };
+
+    // Generate a new access token for the user
+    let access_token = match generate_token(&user_id, ACCESS_TOKEN_DURATION).await {
+        Ok(t) => t,
+        // The user has been deleted since the refresh token was made
+        Err(DBReadError::NotFound(_)) => return Response::Unauthorized(Problem::new("invalid_refresh_token", "Invalid refresh token")),
+        Err(err) => return Response::from(err),
+    };
+
+    // Return the response
+    Response::Ok(Json(TokenPair {
+        access_token,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_DURATION.num_seconds(),
+    }))
+}
+
+#[post("/auth/logout", data = "<input_refresh>")]
+/// Log out
+/// This function handles revoking the access token used for the request.
+/// If a refresh token is given then every refresh token from the same log in is revoked too.
+/// 
+/// # Arguments
+/// * `input_refresh` - An optional JSON payload containing the refresh token to revoke.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<()>` - A response indicating the result of logging out. If successful, it returns no content.
+pub async fn logout_handler(
+    input_refresh: Option<Json<RefreshRequest>>,
+    user: AuthenticatedUser,
+) -> Response<()> {
+    let claims = user.claims;
+
+    // Revoke the access token until it would have expired
+    let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(chrono::Utc::now);
+    if let Err(err) = revoke_token(&claims.sub, &claims.jti, expires_at).await {
+        return Response::from(err);
+    }
+
+    // Revoke the refresh token family if one was given, an unknown refresh token is not an error as there is nothing to revoke
+    if let Some(input_refresh) = input_refresh
+        && let Err(err @ crate::database::DBTokenError::Other(_)) = revoke_refresh_token(&input_refresh.refresh_token).await
+    {
+        return Response::from(err);
+    }
+
+    Response::NoContent(())
+}
+
+#[post("/auth/logout-all")]
+/// Log out everywhere
+/// This function handles revoking every access token and refresh token of the user.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<()>` - A response indicating the result of logging out. If successful, it returns no content.
+pub async fn logout_all_handler(user: AuthenticatedUser) -> Response<()> {
+    let user_id = user.id;
+
+    // Revoke everything for the user
+    if let Err(err) = revoke_all_tokens(&user_id).await {
+        return Response::from(err);
+    }
+
+    Response::NoContent(())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 /// The JWT struct which will be used to extract the token from the request
+/// This only extracts the token, use `AuthenticatedUser` to also verify it
 /// 
 /// # Fields
 /// * `token` - The JWT token extracted from the request
@@ -96,35 +239,164 @@ pub struct JWT {
     pub token: String,
 }
 
+/// Extract the token from an `Authorization` header value
+/// The header must use the `Bearer` scheme, which is matched case insensitively
+/// 
+/// # Arguments
+/// * `header` - The value of the `Authorization` header
+/// 
+/// # Returns
+/// * `Option<&str>` - The token, or None if the header is not a bearer token
+pub fn parse_bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    let token = token.trim();
+
+    if !scheme.eq_ignore_ascii_case("bearer") || token.is_empty() {
+        return None;
+    }
+
+    Some(token)
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for JWT {
-    type Error = std::io::Error;
+    type Error = AuthError;
     
     async fn from_request(request: &'r rocket::request::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
-        // Check if the Authorization header is present
-        match request.headers().get_one("Authorization") {
-            Some(token) => {
-                let token = token[7..].to_string(); 
-                rocket::request::Outcome::Success(JWT { token })
+        // Check if the Authorization header is present and is a bearer token
+        let result = match request.headers().get_one("Authorization") {
+            Some(header) => match parse_bearer_token(header) {
+                Some(token) => Ok(JWT { token: token.to_string() }),
+                None => Err(AuthError::InvalidHeader),
+            },
+            None => Err(AuthError::MissingHeader),
+        };
+
+        match result {
+            Ok(jwt) => rocket::request::Outcome::Success(jwt),
+            Err(err) => {
+                // Store the error so the 401 catcher can explain what went wrong
+                request.local_cache(|| Some(err.clone()));
+                rocket::request::Outcome::Error((rocket::http::Status::Unauthorized, err))
             }
-            _ => {
-                rocket::request::Outcome::Error(
-                    (rocket::http::Status::Unauthorized, std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Missing or invalid Authorization header")),
-                )
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A request guard for a user with a verified, unrevoked access token
+/// Handlers taking this can only run for authenticated requests, anything else is forwarded to the 401 catcher
+/// 
+/// # Fields
+/// * `id` - The ID of the authenticated user
+/// * `claims` - The claims of the access token
+pub struct AuthenticatedUser {
+    pub id: String,
+    pub claims: Claims,
+}
+
+impl AuthenticatedUser {
+    /// Load the `User` record of the authenticated user
+    /// 
+    /// # Returns
+    /// * `Result<User, DBReadError>` - The user or an error
+    pub async fn user(&self) -> Result<User, DBReadError> {
+        get_user_by_id(&self.id).await
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r rocket::request::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
+        // Extract the token from the header first
+        let jwt = match request.guard::<JWT>().await {
+            rocket::request::Outcome::Success(jwt) => jwt,
+            rocket::request::Outcome::Error(e) => return rocket::request::Outcome::Error(e),
+            rocket::request::Outcome::Forward(s) => return rocket::request::Outcome::Forward(s),
+        };
+
+        // Verify the token & extract the claims from it
+        match verify_token(&jwt.token).await {
+            Ok(claims) => rocket::request::Outcome::Success(AuthenticatedUser {
+                id: claims.sub.clone(),
+                claims,
+            }),
+            Err(err) => {
+                let err = AuthError::InvalidToken(err);
+                request.local_cache(|| Some(err.clone()));
+                rocket::request::Outcome::Error((rocket::http::Status::Unauthorized, err))
             }
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+/// The error type which will be returned when authenticating a request
+/// 
+/// # Variants
+/// * `MissingHeader` - There is no `Authorization` header
+/// * `InvalidHeader` - The `Authorization` header is not a bearer token
+/// * `InvalidToken` - The token could not be verified
+pub enum AuthError {
+    MissingHeader,
+    InvalidHeader,
+    InvalidToken(VerifyJWTError),
+}
+
+impl AuthError {
+    /// A stable machine readable code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::MissingHeader => "missing_authorization",
+            AuthError::InvalidHeader => "invalid_authorization",
+            AuthError::InvalidToken(VerifyJWTError::Expired) => "token_expired",
+            AuthError::InvalidToken(VerifyJWTError::Revoked) => "token_revoked",
+            AuthError::InvalidToken(_) => "invalid_token",
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingHeader => write!(f, "Missing Authorization header"),
+            AuthError::InvalidHeader => write!(f, "Authorization header must be a Bearer token"),
+            AuthError::InvalidToken(VerifyJWTError::Expired) => write!(f, "Token has expired"),
+            AuthError::InvalidToken(VerifyJWTError::Revoked) => write!(f, "Token has been revoked"),
+            AuthError::InvalidToken(_) => write!(f, "Invalid token"),
+        }
+    }
+}
+
+#[catch(401)]
+/// Catch requests rejected by the authentication guards
+/// 
+/// # Arguments
+/// * `request` - The request that was rejected
+/// 
+/// # Returns
+/// * `Problem` - The reason the request was rejected
+pub fn unauthorized_catcher(request: &rocket::Request) -> Problem {
+    let problem = match request.local_cache(|| None::<AuthError>) {
+        Some(err) => Problem::new(err.code(), err.to_string()),
+        None => Problem::new("unauthorized", "Unauthorized"),
+    };
+    problem.with_status(rocket::http::Status::Unauthorized)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 /// The error type which will be returned when verifying a JWT token
 /// 
 /// # Variants
 /// * `Malformed` - The token is malformed and cannot be decoded
 /// * `Expired` - The token has expired and is no longer valid
+/// * `Revoked` - The token has been revoked by logging out, changing password or deleting the account
 /// * `Other` - Any other error that may occur during verification
 pub enum VerifyJWTError {
     Malformed,
     Expired,
+    Revoked,
     Other(String),
 }
\ No newline at end of file
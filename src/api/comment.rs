@@ -0,0 +1,170 @@
+use rocket::{get, post, patch, delete, serde::json::Json};
+use crate::database::comment::{create_comment, delete_comment_by_id, edit_comment, get_comment_by_id, get_comment_history, get_comments};
+use crate::database::notification::notify_task_users;
+use crate::database::share::ShareTarget;
+use crate::database::todotask::get_task_by_id;
+use crate::model::comment::{Comment, CommentEdit, CommentPage, CommentQuery, CommentRequest};
+use crate::model::notification::NotificationKind;
+use crate::model::share::ShareRole;
+use super::auth::AuthenticatedUser;
+use super::problem::{Problem, RequestId};
+use super::{require, Permission, Resource, Response};
+
+/// Get a comment on a task, giving the response to return if there isn't one
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `comment_id` - The id of the comment
+///
+/// # Returns
+/// * `Result<Comment, Response<T>>` - The comment, or the error response if it doesn't exist or is on another task
+async fn get_task_comment<T>(task_id: &str, comment_id: &str) -> Result<Comment, Response<T>> {
+    match get_comment_by_id(comment_id).await {
+        Ok(comment) if comment.task.as_ref().is_some_and(|t| t.id.to_raw() == task_id) => Ok(comment),
+        Ok(_) => Err(Response::NotFound(Resource::Comment.not_found())),
+        Err(err) => Err(Response::from(err).about(Resource::Comment)),
+    }
+}
+
+#[get("/tasks/<task_id>/comments?<query..>")]
+/// Get the comments on a task
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `query` - The `cursor` from the previous page and the `limit` of comments to return.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to see the task.
+///
+/// # Returns
+/// * `Response<Json<CommentPage>>` - A response indicating the result of the retrieval. If successful, it returns a page of comments, oldest first, in JSON format.
+pub async fn get_comments_handler(task_id: &str, query: CommentQuery, user: AuthenticatedUser) -> Response<Json<CommentPage>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_comments(task_id, &query).await {
+        Ok(page) => Response::Ok(Json(page)),
+        Err(err) => Response::from(err).about(Resource::Task),
+    }
+}
+
+#[post("/tasks/<task_id>/comments", data = "<input_comment>")]
+/// Comment on a task
+/// The owner and assignee of the task are sent a notification.
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `input_comment` - A JSON payload containing the `body` of the comment.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to see the task.
+/// * `request_id` - The id of the request, for logging errors sending the notifications.
+///
+/// # Returns
+/// * `Response<Json<Comment>>` - A response indicating the result. If successful, it returns the created comment in JSON format.
+pub async fn create_comment_handler(task_id: &str, input_comment: Json<CommentRequest>, user: AuthenticatedUser, request_id: RequestId) -> Response<Json<Comment>> {
+    let input_comment = input_comment.into_inner(); // Deserialise the input from JSON
+
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    let comment = match create_comment(task_id, &user.id, input_comment.body.as_deref().unwrap_or_default()).await {
+        Ok(comment) => comment,
+        Err(err) => return Response::from(err),
+    };
+
+    // The comment has still been made if the notifications fail
+    match get_task_by_id(task_id).await {
+        Ok(task) => {
+            if let Err(err) = notify_task_users(&task, NotificationKind::Commented, &user.id).await {
+                request_id.log("creating notifications", err);
+            }
+        },
+        Err(err) => request_id.log("retrieving task", err),
+    }
+
+    Response::Created(Json(comment))
+}
+
+#[patch("/tasks/<task_id>/comments/<comment_id>", data = "<update_comment>")]
+/// Edit a comment
+/// The previous body is kept in the history of the comment.
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `comment_id` - The ID of the comment.
+/// * `update_comment` - A JSON payload containing the new `body` of the comment.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the author and still be able to see the task.
+///
+/// # Returns
+/// * `Response<Json<Comment>>` - A response indicating the result. If successful, it returns the edited comment in JSON format.
+pub async fn update_comment_handler(task_id: &str, comment_id: &str, update_comment: Json<CommentRequest>, user: AuthenticatedUser) -> Response<Json<Comment>> {
+    let update_comment = update_comment.into_inner(); // Deserialise the input from JSON
+
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+    let comment = match get_task_comment(task_id, comment_id).await {
+        Ok(comment) => comment,
+        Err(response) => return response,
+    };
+    if comment.author.is_none_or(|a| a.id.to_raw() != user.id) {
+        return Response::Forbidden(Problem::new("not_comment_author", "Only the author can edit a comment"));
+    }
+
+    match edit_comment(comment_id, update_comment.body.as_deref().unwrap_or_default()).await {
+        Ok(comment) => Response::Ok(Json(comment)),
+        Err(err) => Response::from(err).about(Resource::Comment),
+    }
+}
+
+#[get("/tasks/<task_id>/comments/<comment_id>/history")]
+/// Get the edit history of a comment
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `comment_id` - The ID of the comment.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to see the task.
+///
+/// # Returns
+/// * `Response<Json<Vec<CommentEdit>>>` - A response indicating the result of the retrieval. If successful, it returns the previous versions of the comment, oldest first, in JSON format.
+pub async fn get_comment_history_handler(task_id: &str, comment_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<CommentEdit>>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+    if let Err(response) = get_task_comment(task_id, comment_id).await {
+        return response;
+    }
+
+    match get_comment_history(comment_id).await {
+        Ok(history) => Response::Ok(Json(history)),
+        Err(err) => Response::from(err).about(Resource::Comment),
+    }
+}
+
+#[delete("/tasks/<task_id>/comments/<comment_id>")]
+/// Delete a comment and its history
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `comment_id` - The ID of the comment.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the author, or the owner or a co-owner of the task.
+///
+/// # Returns
+/// * `Response<Json<Comment>>` - A response indicating the result of the deletion. If successful, it returns the deleted comment in JSON format.
+pub async fn delete_comment_handler(task_id: &str, comment_id: &str, user: AuthenticatedUser) -> Response<Json<Comment>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+    let comment = match get_task_comment(task_id, comment_id).await {
+        Ok(comment) => comment,
+        Err(response) => return response,
+    };
+    if comment.author.is_none_or(|a| a.id.to_raw() != user.id)
+        && let Err(response) = require(&user.id, Permission::Role(ShareTarget::Task(task_id), ShareRole::CoOwner)).await {
+        return response;
+    }
+
+    match delete_comment_by_id(comment_id).await {
+        Ok(comment) => Response::Ok(Json(comment)),
+        Err(err) => Response::from(err).about(Resource::Comment),
+    }
+}
@@ -0,0 +1,105 @@
+use rocket::{http::Header, request::FromRequest, Responder};
+
+#[derive(Debug, Responder)]
+/// A response with an `ETag` header
+/// 
+/// # Fields
+/// * `inner` - The response body
+/// * `etag` - The `ETag` header
+pub struct Tagged<T> {
+    pub inner: T,
+    pub etag: Header<'static>,
+}
+
+impl<T> Tagged<T> {
+    /// Tag a response with the version of the record in it
+    /// 
+    /// # Arguments
+    /// * `inner` - The response body
+    /// * `version` - The version of the record
+    /// 
+    /// # Returns
+    /// * `Tagged<T>` - The tagged response
+    pub fn new(inner: T, version: Option<i64>) -> Self {
+        Tagged {
+            inner,
+            etag: Header::new("ETag", etag(version)),
+        }
+    }
+}
+
+/// The entity tag of a version of a record
+/// 
+/// # Arguments
+/// * `version` - The version of the record
+/// 
+/// # Returns
+/// * `String` - The quoted entity tag, e.g. `"3"`
+pub fn etag(version: Option<i64>) -> String {
+    format!("\"{}\"", version.unwrap_or_default())
+}
+
+/// Check if an `If-Match` or `If-None-Match` header matches an entity tag
+/// The header can list several tags or be `*` to match any
+/// A strong comparison never matches a weak tag, as `If-Match` requires. A weak comparison, for `If-None-Match`, compares weak tags by their value
+/// 
+/// # Arguments
+/// * `header` - The value of the header
+/// * `etag` - The entity tag of the record, which is always strong
+/// * `strong` - True to use the strong comparison
+/// 
+/// # Returns
+/// * `bool` - True if one of the tags matches
+fn header_matches(header: &str, etag: &str, strong: bool) -> bool {
+    header
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || if strong { tag == etag } else { tag.trim_start_matches("W/") == etag })
+}
+
+#[derive(Debug, Clone, Default)]
+/// A request guard for the conditional request headers
+/// 
+/// # Fields
+/// * `if_match` - The `If-Match` header, the change is only made if the record still has one of these tags
+/// * `if_none_match` - The `If-None-Match` header, the record is only sent if it doesn't have one of these tags
+pub struct Preconditions {
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+}
+
+impl Preconditions {
+    /// Check the `If-Match` header against the current version of a record, a weak tag never matches
+    /// 
+    /// # Arguments
+    /// * `version` - The current version of the record
+    /// 
+    /// # Returns
+    /// * `bool` - False if there is a header which doesn't match, this should be answered with 412 Precondition Failed
+    pub fn if_match(&self, version: Option<i64>) -> bool {
+        self.if_match.as_deref().is_none_or(|header| header_matches(header, &etag(version), true))
+    }
+
+    /// Check the `If-None-Match` header against the current version of a record, a weak tag matches by its value
+    /// 
+    /// # Arguments
+    /// * `version` - The current version of the record
+    /// 
+    /// # Returns
+    /// * `bool` - True if the client already has this version, this should be answered with 304 Not Modified
+    pub fn not_modified(&self, version: Option<i64>) -> bool {
+        self.if_none_match.as_deref().is_some_and(|header| header_matches(header, &etag(version), false))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preconditions {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r rocket::request::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
+        rocket::request::Outcome::Success(Preconditions {
+            if_match: request.headers().get_one("If-Match").map(str::to_string),
+            if_none_match: request.headers().get_one("If-None-Match").map(str::to_string),
+        })
+    }
+}
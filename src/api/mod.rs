@@ -1,33 +1,249 @@
-use rocket::Responder;
+use rocket::{http::{ContentType, Status}, response::{self, Responder}, Request};
 
+use conditional::Tagged;
+use problem::Problem;
+
+use crate::database::share::{check_permission, ShareTarget};
+use crate::database::{tag::check_is_tag_owner, todotask::check_is_owner};
+use crate::model::share::ShareRole;
+
+pub mod activity;
 pub mod auth;
+pub mod comment;
+pub mod conditional;
+pub mod notification;
+pub mod problem;
+pub mod project;
+pub mod rate_limit;
+pub mod share;
+pub mod sync;
+pub mod tag;
 pub mod todotask;
 pub mod user;
+pub mod validation;
 
-#[derive(Debug, Responder)]
+#[derive(Debug)]
 /// Response types for the API
+/// Errors are sent as an RFC 7807 `application/problem+json` body, see `Problem`
 /// 
 /// # Variants
 /// * `Ok` - Indicates a successful request with a 200 status code and JSON content type.
 /// * `Created` - Indicates a successful request that resulted in a resource being created, with a 201 status code and JSON content type.
-/// * `BadRequest` - Indicates a client error with a 400 status code and text content type, along with an error message.
-/// * `Unauthorized` - Indicates an authentication error with a 401 status code and text content type, along with an error message.
-/// * `Forbidden` - Indicates a permission error with a 403 status code and text content type, along with an error message.
-/// * `NotFound` - Indicates a resource not found error with a 404 status code and text content type, along with an error message.
-/// * `InternalServerError` - Indicates a server error with a 500 status code and text content type, along with an error message.
+/// * `NoContent` - Indicates a successful request with nothing to return, with a 204 status code and no body.
+/// * `NotModified` - Indicates the client already has the current version, with a 304 status code, the `ETag` header and no body.
+/// * `BadRequest` - Indicates a client error with a 400 status code, along with the problem.
+/// * `Unauthorized` - Indicates an authentication error with a 401 status code, along with the problem.
+/// * `Forbidden` - Indicates a permission error with a 403 status code, along with the problem.
+/// * `NotFound` - Indicates a resource not found error with a 404 status code, along with the problem.
+/// * `Conflict` - Indicates the request clashes with another record, e.g. a taken username, with a 409 status code, along with the problem.
+/// * `PreconditionFailed` - Indicates the record has changed since the version in `If-Match`, with a 412 status code, along with the problem.
+/// * `TooManyRequests` - Indicates the client has been rate limited, with a 429 status code, the `Retry-After` header, along with the problem.
+/// * `InternalServerError` - Indicates a server error with a 500 status code, along with the problem.
 pub enum Response<T> {
-    #[response(status = 200, content_type = "json")]
     Ok(T),
-    #[response(status = 201, content_type = "json")]
     Created(T),
-    #[response(status = 400, content_type = "text")]
-    BadRequest(String),
-    #[response(status = 401, content_type = "text")]
-    Unauthorized(String),
-    #[response(status = 403, content_type = "text")]
-    Forbidden(String),
-    #[response(status = 404, content_type = "text")]
-    NotFound(String),
-    #[response(status = 500, content_type = "text")]
-    InternalServerError(String),
-}
\ No newline at end of file
+    NoContent(()),
+    NotModified(Tagged<()>),
+    BadRequest(Problem),
+    Unauthorized(Problem),
+    Forbidden(Problem),
+    NotFound(Problem),
+    Conflict(Problem),
+    PreconditionFailed(Problem),
+    TooManyRequests(Problem),
+    InternalServerError(Problem),
+}
+
+impl<'r, 'o: 'r, T: Responder<'r, 'o>> Responder<'r, 'o> for Response<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'o> {
+        let (status, problem) = match self {
+            Response::Ok(body) => return rocket::Response::build_from(body.respond_to(request)?).status(Status::Ok).header(ContentType::JSON).ok(),
+            Response::Created(body) => return rocket::Response::build_from(body.respond_to(request)?).status(Status::Created).header(ContentType::JSON).ok(),
+            Response::NoContent(()) => return rocket::Response::build().status(Status::NoContent).ok(),
+            Response::NotModified(tagged) => return rocket::Response::build_from(tagged.respond_to(request)?).status(Status::NotModified).ok(),
+            Response::BadRequest(problem) => (Status::BadRequest, problem),
+            Response::Unauthorized(problem) => (Status::Unauthorized, problem),
+            Response::Forbidden(problem) => (Status::Forbidden, problem),
+            Response::NotFound(problem) => (Status::NotFound, problem),
+            Response::Conflict(problem) => (Status::Conflict, problem),
+            Response::PreconditionFailed(problem) => (Status::PreconditionFailed, problem),
+            Response::TooManyRequests(problem) => (Status::TooManyRequests, problem),
+            Response::InternalServerError(problem) => (Status::InternalServerError, problem),
+        };
+        problem.with_status(status).respond_to(request)
+    }
+}
+
+impl<T> Response<T> {
+    /// The response for an error from the database, the status depends on the code of the problem
+    /// 
+    /// # Arguments
+    /// * `problem` - The problem made from the error
+    /// 
+    /// # Returns
+    /// * `Response<T>` - 404 for `not_found`, 409 for `already_exists`, 500 for `internal_error` and 400 for anything else
+    fn from_db_problem(problem: Problem) -> Self {
+        match problem.code.as_str() {
+            "not_found" => Response::NotFound(problem),
+            "already_exists" => Response::Conflict(problem),
+            "internal_error" => Response::InternalServerError(problem),
+            _ => Response::BadRequest(problem),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The kinds of record the API works with, each has its own code for when it doesn't exist
+///
+/// # Variants
+/// * `Task` - `task_not_found`
+/// * `TrashedTask` - `task_not_in_trash`, a task in the trash
+/// * `Project` - `project_not_found`
+/// * `Tag` - `tag_not_found`
+/// * `TaskTag` - `tag_not_on_task`, the tag of a task
+/// * `Comment` - `comment_not_found`
+/// * `Notification` - `notification_not_found`
+/// * `Share` - `share_not_found`
+/// * `User` - `user_not_found`
+pub enum Resource {
+    Task,
+    TrashedTask,
+    Project,
+    Tag,
+    TaskTag,
+    Comment,
+    Notification,
+    Share,
+    User,
+}
+
+impl Resource {
+    /// The problem for a record of this kind which doesn't exist
+    ///
+    /// # Returns
+    /// * `Problem` - The problem, e.g. with the code `task_not_found`
+    pub fn not_found(self) -> Problem {
+        let (code, detail) = match self {
+            Resource::Task => ("task_not_found", "Task not found"),
+            Resource::TrashedTask => ("task_not_in_trash", "Task not found in the trash"),
+            Resource::Project => ("project_not_found", "Project not found"),
+            Resource::Tag => ("tag_not_found", "Tag not found"),
+            Resource::TaskTag => ("tag_not_on_task", "The task doesn't have this tag"),
+            Resource::Comment => ("comment_not_found", "Comment not found"),
+            Resource::Notification => ("notification_not_found", "Notification not found"),
+            Resource::Share => ("share_not_found", "It isn't shared with this user"),
+            Resource::User => ("user_not_found", "User not found"),
+        };
+        Problem::new(code, detail)
+    }
+}
+
+impl<T> From<crate::database::DBCreateError> for Response<T> {
+    fn from(err: crate::database::DBCreateError) -> Self {
+        Response::from_db_problem(Problem::from(err))
+    }
+}
+
+impl<T> From<crate::database::DBEditError> for Response<T> {
+    fn from(err: crate::database::DBEditError) -> Self {
+        Response::from_db_problem(Problem::from(err))
+    }
+}
+
+impl<T> From<crate::database::DBReadError> for Response<T> {
+    fn from(err: crate::database::DBReadError) -> Self {
+        Response::from_db_problem(Problem::from(err))
+    }
+}
+
+// A refresh token which can't be used is 401 whatever the reason, so the client logs in again
+impl<T> From<crate::database::DBTokenError> for Response<T> {
+    fn from(err: crate::database::DBTokenError) -> Self {
+        match err {
+            crate::database::DBTokenError::Other(_) => Response::InternalServerError(Problem::from(err)),
+            _ => Response::Unauthorized(Problem::from(err)),
+        }
+    }
+}
+
+impl<T> Response<T> {
+    /// Say which kind of record a response made from a database error was about
+    /// The `From` conversions only know a record wasn't found, this gives it the code of the resource, e.g. `task_not_found`
+    ///
+    /// # Arguments
+    /// * `resource` - The kind of record the request was for
+    ///
+    /// # Returns
+    /// * `Response<T>` - The response, with any `not_found` problem replaced by the one for the resource
+    pub fn about(self, resource: Resource) -> Self {
+        match self {
+            Response::NotFound(problem) if problem.code == "not_found" => Response::NotFound(resource.not_found()),
+            response => response,
+        }
+    }
+
+    /// Change the body of a successful response, errors are kept as they are
+    /// 
+    /// # Arguments
+    /// * `f` - The function to change the body with
+    /// 
+    /// # Returns
+    /// * `Response<U>` - The response with the new body
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Response<U> {
+        match self {
+            Response::Ok(body) => Response::Ok(f(body)),
+            Response::Created(body) => Response::Created(f(body)),
+            Response::NoContent(()) => Response::NoContent(()),
+            Response::NotModified(tagged) => Response::NotModified(tagged),
+            Response::BadRequest(problem) => Response::BadRequest(problem),
+            Response::Unauthorized(problem) => Response::Unauthorized(problem),
+            Response::Forbidden(problem) => Response::Forbidden(problem),
+            Response::NotFound(problem) => Response::NotFound(problem),
+            Response::Conflict(problem) => Response::Conflict(problem),
+            Response::PreconditionFailed(problem) => Response::PreconditionFailed(problem),
+            Response::TooManyRequests(problem) => Response::TooManyRequests(problem),
+            Response::InternalServerError(problem) => Response::InternalServerError(problem),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Something a user has to be allowed to do before a request is handled, checked with `require`
+///
+/// # Variants
+/// * `Role` - Having at least a role on a task or project, the owner has every role
+/// * `TaskOwner` - Owning a task, by id
+/// * `TagOwner` - Owning a tag, by id
+pub enum Permission<'a> {
+    Role(ShareTarget<'a>, ShareRole),
+    TaskOwner(&'a str),
+    TagOwner(&'a str),
+}
+
+/// Check a user is allowed to do something, giving the response to return if they aren't
+/// Every handler checks permissions with this so they all answer the same way, 403 if the user isn't allowed and 404 if the record doesn't exist.
+/// Any other error from the database is turned into a response by `From<DBReadError>`
+///
+/// # Arguments
+/// * `user_id` - The id of the user making the request
+/// * `permission` - What they need to be allowed to do
+///
+/// # Returns
+/// * `Result<(), Response<T>>` - Nothing if the user is allowed, or the error response
+pub async fn require<T>(user_id: &str, permission: Permission<'_>) -> Result<(), Response<T>> {
+    let allowed = match permission {
+        Permission::Role(target, role) => check_permission(user_id, target, role).await,
+        Permission::TaskOwner(task_id) => check_is_owner(user_id, task_id).await,
+        Permission::TagOwner(tag_id) => check_is_tag_owner(user_id, tag_id).await,
+    };
+
+    match allowed {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Response::Forbidden(Problem::new("forbidden", "You do not have permissions"))),
+        Err(err) => Err(Response::from(err).about(match permission {
+            Permission::Role(ShareTarget::Task(_), _) | Permission::TaskOwner(_) => Resource::Task,
+            Permission::Role(ShareTarget::Project(_), _) => Resource::Project,
+            Permission::TagOwner(_) => Resource::Tag,
+        })),
+    }
+}
@@ -0,0 +1,53 @@
+use rocket::{get, post, serde::json::Json};
+use crate::database::notification::{get_notifications, mark_all_notifications_read, mark_notification_read};
+use crate::model::notification::Notification;
+use super::auth::AuthenticatedUser;
+use super::{Resource, Response};
+
+#[get("/notifications?<unread>")]
+/// Get the notifications of the user
+///
+/// # Arguments
+/// * `unread` - Only get the notifications which have not been read, defaults to false.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Notification>>>` - A response indicating the result of the retrieval. If successful, it returns the most recent notifications, newest first, in JSON format.
+pub async fn get_notifications_handler(unread: Option<bool>, user: AuthenticatedUser) -> Response<Json<Vec<Notification>>> {
+    match get_notifications(&user.id, unread.unwrap_or(false)).await {
+        Ok(notifications) => Response::Ok(Json(notifications)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[post("/notifications/<notification_id>/read")]
+/// Mark a notification as read
+///
+/// # Arguments
+/// * `notification_id` - The ID of the notification.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. Users can only read their own notifications.
+///
+/// # Returns
+/// * `Response<Json<Notification>>` - A response indicating the result. If successful, it returns the notification in JSON format.
+pub async fn read_notification_handler(notification_id: &str, user: AuthenticatedUser) -> Response<Json<Notification>> {
+    match mark_notification_read(notification_id, &user.id).await {
+        Ok(notification) => Response::Ok(Json(notification)),
+        Err(err) => Response::from(err).about(Resource::Notification),
+    }
+}
+
+#[post("/notifications/read-all")]
+/// Mark all the notifications of the user as read
+///
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<()>` - A response indicating the result. If successful, it returns no content.
+pub async fn read_all_notifications_handler(user: AuthenticatedUser) -> Response<()> {
+    if let Err(err) = mark_all_notifications_read(&user.id).await {
+        return Response::from(err);
+    }
+
+    Response::NoContent(())
+}
@@ -0,0 +1,324 @@
+use std::io::Cursor;
+
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::{catch, http::{ContentType, Header, Status}, response::{self, Responder}, Request};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{DBCreateError, DBEditError, DBReadError, DBTokenError};
+
+/// The header the request id is read from and sent back in
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A problem with one field of a request
+///
+/// # Fields
+/// * `field` - The name of the field, e.g. `title`
+/// * `code` - What is wrong with the field, e.g. `required`
+/// * `message` - A human readable explanation
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An RFC 7807 `application/problem+json` error body
+/// The status, title, instance and request id are filled in when the response is sent
+///
+/// # Fields
+/// * `problem_type` - The `type` of the problem, always `about:blank` so the title is the HTTP status
+/// * `title` - The HTTP status, e.g. `Not Found`
+/// * `status` - The HTTP status code
+/// * `code` - A stable machine readable code, e.g. `task_not_found`
+/// * `detail` - A human readable explanation
+/// * `errors` - The problems with each field of the request, left out when empty
+/// * `instance` - The path of the request
+/// * `request_id` - The id of the request, also sent in the `X-Request-Id` header
+/// * `retry_after` - How many seconds to wait before trying again, also sent in the `Retry-After` header, left out when there is nothing to wait for
+/// * `cause` - The error behind an internal error, it is logged with the request id but never sent to the client
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub code: String,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    pub instance: Option<String>,
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    #[serde(skip)]
+    pub cause: Option<String>,
+}
+
+impl Problem {
+    /// Create a problem
+    ///
+    /// # Arguments
+    /// * `code` - A stable machine readable code
+    /// * `detail` - A human readable explanation
+    ///
+    /// # Returns
+    /// * `Problem` - The problem
+    pub fn new(code: &str, detail: impl Into<String>) -> Self {
+        Problem {
+            problem_type: "about:blank".to_string(),
+            title: String::new(),
+            status: 0,
+            code: code.to_string(),
+            detail: detail.into(),
+            errors: Vec::new(),
+            instance: None,
+            request_id: None,
+            retry_after: None,
+            cause: None,
+        }
+    }
+
+    /// Create a problem with one field of the request
+    ///
+    /// # Arguments
+    /// * `field` - The name of the field
+    /// * `code` - What is wrong with the field
+    /// * `message` - A human readable explanation, also used as the detail
+    ///
+    /// # Returns
+    /// * `Problem` - The problem, with the code `validation_failed`
+    pub fn field(field: &str, code: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Problem::fields(vec![FieldError { field: field.to_string(), code: code.to_string(), message }])
+    }
+
+    /// Create a problem with some of the fields of the request
+    ///
+    /// # Arguments
+    /// * `errors` - The problems with each field
+    ///
+    /// # Returns
+    /// * `Problem` - The problem, with the code `validation_failed`
+    pub fn fields(errors: Vec<FieldError>) -> Self {
+        let detail = match errors.as_slice() {
+            [error] => error.message.clone(),
+            _ => "Some fields are invalid".to_string(),
+        };
+        Problem { errors, ..Problem::new("validation_failed", detail) }
+    }
+
+    /// The problem for an error which isn't the clients fault, the details are not sent to the client
+    ///
+    /// # Returns
+    /// * `Problem` - The problem, with the code `internal_error`
+    pub fn internal() -> Self {
+        Problem::new("internal_error", "There was an unknown error")
+    }
+
+    /// The problem for an unexpected error, the error is logged with the request id when the response is sent
+    ///
+    /// # Arguments
+    /// * `context` - What was being done, e.g. `deleting a task`
+    /// * `err` - The error
+    ///
+    /// # Returns
+    /// * `Problem` - The problem, with the code `internal_error`
+    pub fn internal_error(context: &str, err: impl std::fmt::Debug) -> Self {
+        Problem {
+            cause: Some(format!("{}: {:?}", context, err)),
+            ..Problem::internal()
+        }
+    }
+
+    /// Set the status and title of the problem
+    ///
+    /// # Arguments
+    /// * `status` - The HTTP status
+    ///
+    /// # Returns
+    /// * `Problem` - The problem with the status
+    pub fn with_status(self, status: Status) -> Self {
+        Problem {
+            status: status.code,
+            title: status.reason_lossy().to_string(),
+            ..self
+        }
+    }
+
+    /// Tell the client how long to wait before trying again
+    ///
+    /// # Arguments
+    /// * `wait` - How long to wait, rounded up to whole seconds
+    ///
+    /// # Returns
+    /// * `Problem` - The problem with the wait
+    pub fn with_retry_after(self, wait: std::time::Duration) -> Self {
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        Problem {
+            retry_after: Some(seconds.max(1)),
+            ..self
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Problem {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::from_code(self.status).unwrap_or(Status::InternalServerError);
+        if let Some(cause) = &self.cause {
+            log_error(request_id(request), cause);
+        }
+        let problem = Problem {
+            instance: Some(request.uri().path().to_string()),
+            request_id: Some(request_id(request).to_string()),
+            ..self.with_status(status)
+        };
+
+        let body = serde_json::to_string(&problem).map_err(|_| Status::InternalServerError)?;
+        let mut response = rocket::Response::build();
+        response
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .sized_body(body.len(), Cursor::new(body));
+        if let Some(seconds) = problem.retry_after {
+            response.header(Header::new("Retry-After", seconds.to_string()));
+        }
+        response.ok()
+    }
+}
+
+/// Get the id of a request, the client can send one in the `X-Request-Id` header otherwise one is made
+///
+/// # Arguments
+/// * `request` - The request
+///
+/// # Returns
+/// * `&str` - The id of the request
+pub fn request_id<'r>(request: &'r Request<'_>) -> &'r str {
+    request.local_cache(|| {
+        request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+            .map(str::to_string)
+            .unwrap_or_else(|| rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect())
+    })
+}
+
+/// Log an unexpected error with the id of the request it happened in, so it can be found from the response the client got
+///
+/// # Arguments
+/// * `request_id` - The id of the request
+/// * `message` - What went wrong
+pub fn log_error(request_id: &str, message: &str) {
+    rocket::error!("Unhandled error in request {}, {}", request_id, message);
+}
+
+#[derive(Debug, Clone)]
+/// A request guard for the id of the request, for logging errors which don't stop the request from succeeding
+///
+/// # Fields
+/// * `0` - The id of the request
+pub struct RequestId(pub String);
+
+#[rocket::async_trait]
+impl<'r> rocket::request::FromRequest<'r> for RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
+        rocket::request::Outcome::Success(RequestId(request_id(request).to_string()))
+    }
+}
+
+impl RequestId {
+    /// Log an error which didn't stop the request from succeeding
+    ///
+    /// # Arguments
+    /// * `context` - What was being done, e.g. `creating notifications`
+    /// * `err` - The error
+    pub fn log(&self, context: &str, err: impl std::fmt::Debug) {
+        log_error(&self.0, &format!("{}: {:?}", context, err));
+    }
+}
+
+/// Send the request id back on every response so it can be matched with the logs
+///
+/// # Returns
+/// * `AdHoc` - The fairing to attach
+pub fn request_id_fairing() -> rocket::fairing::AdHoc {
+    rocket::fairing::AdHoc::on_response("Request id", |request, response| Box::pin(async move {
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id(request).to_string()));
+    }))
+}
+
+impl From<DBCreateError> for Problem {
+    fn from(err: DBCreateError) -> Self {
+        match err {
+            DBCreateError::AlreadyExists(msg) => Problem::new("already_exists", msg),
+            DBCreateError::BadData(msg) => Problem::new("invalid_data", msg),
+            DBCreateError::Other(msg) => Problem::internal_error("creating a record", msg),
+        }
+    }
+}
+
+impl From<DBEditError> for Problem {
+    fn from(err: DBEditError) -> Self {
+        match err {
+            DBEditError::NotFound(msg) => Problem::new("not_found", msg),
+            DBEditError::AlreadyExists(msg) => Problem::new("already_exists", msg),
+            DBEditError::BadData(msg) => Problem::new("invalid_data", msg),
+            DBEditError::Other(msg) => Problem::internal_error("editing a record", msg),
+        }
+    }
+}
+
+impl From<DBReadError> for Problem {
+    fn from(err: DBReadError) -> Self {
+        match err {
+            DBReadError::NotFound(msg) => Problem::new("not_found", msg),
+            DBReadError::BadData(msg) => Problem::new("invalid_query", msg),
+            DBReadError::Other(msg) => Problem::internal_error("reading a record", msg),
+        }
+    }
+}
+
+impl From<DBTokenError> for Problem {
+    fn from(err: DBTokenError) -> Self {
+        match err {
+            DBTokenError::NotFound(_) => Problem::new("invalid_refresh_token", "Invalid refresh token"),
+            DBTokenError::Expired(_) => Problem::new("refresh_token_expired", "Refresh token has expired"),
+            DBTokenError::Reused(_) => Problem::new("refresh_token_reused", "Refresh token has already been used"),
+            DBTokenError::Other(msg) => Problem::internal_error("using a refresh token", msg),
+        }
+    }
+}
+
+#[catch(404)]
+/// Catch requests which don't match any route
+///
+/// # Returns
+/// * `Problem` - The problem
+pub fn not_found_catcher() -> Problem {
+    Problem::new("route_not_found", "There is nothing at this path").with_status(Status::NotFound)
+}
+
+#[catch(422)]
+/// Catch requests with a body which couldn't be read, e.g. JSON with a missing or mistyped field
+///
+/// # Returns
+/// * `Problem` - The problem
+pub fn unprocessable_catcher() -> Problem {
+    Problem::new("invalid_body", "The request body could not be read").with_status(Status::UnprocessableEntity)
+}
+
+#[catch(default)]
+/// Catch every other error which wasn't answered by a route, so they have the same shape
+///
+/// # Arguments
+/// * `status` - The status of the error
+///
+/// # Returns
+/// * `Problem` - The problem, the code is the status e.g. `bad_request`
+pub fn default_catcher(status: Status, _request: &Request) -> Problem {
+    let code = status.reason_lossy().to_lowercase().replace([' ', '-'], "_");
+    Problem::new(&code, status.reason_lossy()).with_status(status)
+}
@@ -0,0 +1,136 @@
+use rocket::{get, post, patch, delete, serde::json::Json};
+use crate::database::project::{create_project, delete_project_by_id, edit_project_by_id, get_project_by_id, get_projects_by_user, INBOX};
+use crate::database::share::ShareTarget;
+use crate::database::todotask::get_tasks_by_user;
+use crate::model::project::{Project, ProjectRequest};
+use crate::model::share::ShareRole;
+use crate::model::todotask::{TaskPage, TaskQuery};
+use super::auth::AuthenticatedUser;
+use super::{require, Permission, Resource, Response};
+
+#[post("/projects", data = "<input_project>")]
+/// Create a new project
+/// This function handles the creation of a new project by accepting a JSON payload containing the project's details.
+///
+/// # Arguments
+/// * `input_project` - A JSON payload containing the project's details, including name and description.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Project>>` - A response indicating the result of the project creation process. If successful, it returns the created project in JSON format.
+pub async fn create_project_handler(
+    input_project: Json<ProjectRequest>,
+    user: AuthenticatedUser,
+) -> Response<Json<Project>> {
+    let input_project = input_project.into_inner(); // Deserialise the input from JSON
+
+    match create_project(&user.id, &input_project).await {
+        Ok(project) => Response::Created(Json(project)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[get("/projects?<archived>")]
+/// Get the projects of the user
+/// This function handles the retrieval of the projects associated with the authenticated user.
+///
+/// # Arguments
+/// * `archived` - Get the archived projects instead of the active ones, defaults to false.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Project>>>` - A response indicating the result of the retrieval. If successful, it returns the projects, oldest first, in JSON format.
+pub async fn get_projects_handler(archived: Option<bool>, user: AuthenticatedUser) -> Response<Json<Vec<Project>>> {
+    match get_projects_by_user(&user.id, archived.unwrap_or(false)).await {
+        Ok(projects) => Response::Ok(Json(projects)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[get("/projects/<project_id>")]
+/// Get a project by its ID
+///
+/// # Arguments
+/// * `project_id` - The ID of the project to be retrieved.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Project>>` - A response indicating the result of the retrieval. If successful, it returns the project in JSON format.
+pub async fn get_project_handler(project_id: &str, user: AuthenticatedUser) -> Response<Json<Project>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Project(project_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_project_by_id(project_id).await {
+        Ok(project) => Response::Ok(Json(project)),
+        Err(err) => Response::from(err).about(Resource::Project),
+    }
+}
+
+#[get("/projects/<project_id>/tasks?<query..>")]
+/// Get the tasks in a project
+/// This function handles the retrieval of the tasks in a project, one page at a time. The project `inbox` has the tasks which are not in a project.
+///
+/// # Arguments
+/// * `project_id` - The ID of the project, or `inbox`.
+/// * `query` - The same query parameters as `GET /tasks`, `project` is ignored.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<TaskPage>>` - A response indicating the result of the retrieval. If successful, it returns a page of tasks and the cursor of the next page in JSON format.
+pub async fn get_project_tasks_handler(project_id: &str, query: TaskQuery, user: AuthenticatedUser) -> Response<Json<TaskPage>> {
+    if project_id != INBOX && let Err(response) = require(&user.id, Permission::Role(ShareTarget::Project(project_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    let query = TaskQuery { project: Some(project_id.to_string()), ..query };
+    match get_tasks_by_user(&user.id, &query).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err).about(Resource::Task),
+    }
+}
+
+#[patch("/projects/<project_id>", data = "<update_project>")]
+/// Update an existing project
+/// This function handles the update of a project, including archiving or restoring it with `archived`.
+///
+/// # Arguments
+/// * `project_id` - The ID of the project to be updated.
+/// * `update_project` - A JSON payload containing the updated project's details, including name, description and archived.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Project>>` - A response indicating the result of the project update process. If successful, it returns the updated project in JSON format.
+pub async fn update_project_handler(project_id: &str, update_project: Json<ProjectRequest>, user: AuthenticatedUser) -> Response<Json<Project>> {
+    let update_project = update_project.into_inner(); // Deserialise the input from JSON
+
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Project(project_id), ShareRole::Editor)).await {
+        return response;
+    }
+
+    match edit_project_by_id(project_id, &update_project).await {
+        Ok(project) => Response::Ok(Json(project)),
+        Err(err) => Response::from(err).about(Resource::Project),
+    }
+}
+
+#[delete("/projects/<project_id>")]
+/// Delete a project
+/// This function handles the deletion of a project by its ID, the tasks in it are moved to the inbox.
+///
+/// # Arguments
+/// * `project_id` - The ID of the project to be deleted.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Project>>` - A response indicating the result of the project deletion process. If successful, it returns the deleted project in JSON format.
+pub async fn delete_project_handler(project_id: &str, user: AuthenticatedUser) -> Response<Json<Project>> {
+    if let Err(response) = require(&user.id, Permission::Role(ShareTarget::Project(project_id), ShareRole::CoOwner)).await {
+        return response;
+    }
+
+    match delete_project_by_id(project_id, &user.id).await {
+        Ok(project) => Response::Ok(Json(project)),
+        Err(err) => Response::from(err).about(Resource::Project),
+    }
+}
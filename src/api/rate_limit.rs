@@ -0,0 +1,580 @@
+use std::{collections::{BTreeSet, HashMap}, marker::PhantomData, sync::{LazyLock, Mutex}, time::{Duration, Instant}};
+
+use chrono::{DateTime, Utc};
+use rocket::{catch, figment::providers::Env, http::Status, request::FromRequest};
+use serde::Deserialize;
+
+use crate::database::rate_limit::{clear_login_attempts, get_login_attempt, record_login_failure, set_login_retry_at, take_token};
+
+use super::problem::{request_id, Problem, RequestId};
+
+/// The rate limits, read once when they are first used
+static CONFIG: LazyLock<RateLimitConfig> = LazyLock::new(RateLimitConfig::load);
+
+/// The most buckets or accounts the memory store keeps, past this the ones which expire soonest are forgotten first
+const MEMORY_STORE_SIZE: usize = 10_000;
+
+/// The token buckets in the memory store, with the tokens in each and when they were counted
+static MEMORY_BUCKETS: LazyLock<Mutex<MemoryStore<(f64, Instant)>>> = LazyLock::new(|| Mutex::new(MemoryStore::new(MEMORY_STORE_SIZE)));
+
+/// The failed log ins for each account in the memory store
+static MEMORY_ATTEMPTS: LazyLock<Mutex<MemoryStore<Attempt>>> = LazyLock::new(|| Mutex::new(MemoryStore::new(MEMORY_STORE_SIZE)));
+
+/// Entries kept in memory until they expire, with at most `size` of them
+/// The entries are also ordered by when they expire, so the expired ones are found without looking at the rest
+///
+/// # Fields
+/// * `size` - The most entries kept, past this the one which expires soonest is forgotten
+/// * `entries` - Each entry and when it expires
+/// * `expiries` - When each entry expires and its key, soonest first
+pub struct MemoryStore<V> {
+    size: usize,
+    entries: HashMap<String, (V, Instant)>,
+    expiries: BTreeSet<(Instant, String)>,
+}
+
+impl<V> MemoryStore<V> {
+    /// Create an empty store
+    ///
+    /// # Arguments
+    /// * `size` - The most entries to keep
+    ///
+    /// # Returns
+    /// * `MemoryStore<V>` - The store
+    pub fn new(size: usize) -> Self {
+        MemoryStore { size: size.max(1), entries: HashMap::new(), expiries: BTreeSet::new() }
+    }
+
+    /// Get an entry which hasn't expired
+    ///
+    /// # Arguments
+    /// * `key` - The key of the entry
+    /// * `now` - The time now
+    ///
+    /// # Returns
+    /// * `Option<&V>` - The entry, None if there isn't one or it has expired
+    pub fn get(&self, key: &str, now: Instant) -> Option<&V> {
+        self.entries.get(key).filter(|(_, expires)| *expires > now).map(|(value, _)| value)
+    }
+
+    /// Add or replace an entry, forgetting the expired entries and making room if the store is full
+    ///
+    /// # Arguments
+    /// * `key` - The key of the entry
+    /// * `value` - The entry
+    /// * `expires` - When it can be forgotten
+    /// * `now` - The time now
+    pub fn insert(&mut self, key: &str, value: V, expires: Instant, now: Instant) {
+        self.remove(key);
+
+        // Only the entries at the front can have expired
+        while let Some((first, _)) = self.expiries.first() && *first <= now {
+            self.pop_first();
+        }
+        while self.entries.len() >= self.size {
+            self.pop_first();
+        }
+
+        self.expiries.insert((expires, key.to_string()));
+        self.entries.insert(key.to_string(), (value, expires));
+    }
+
+    /// Forget an entry
+    ///
+    /// # Arguments
+    /// * `key` - The key of the entry
+    pub fn remove(&mut self, key: &str) {
+        if let Some((_, expires)) = self.entries.remove(key) {
+            self.expiries.remove(&(expires, key.to_string()));
+        }
+    }
+
+    /// Forget the entry which expires soonest
+    fn pop_first(&mut self) {
+        if let Some((_, key)) = self.expiries.pop_first() {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Where the rate limits are kept
+///
+/// # Variants
+/// * `Memory` - In this instance of the backend, so each instance has its own limits and they are forgotten on restart
+/// * `Database` - In the database, so every instance shares the same limits
+pub enum RateLimitStore {
+    Memory,
+    Database,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+/// A token bucket, each request takes a token and tokens are added back at a steady rate
+///
+/// # Fields
+/// * `capacity` - How many requests can be made at once
+/// * `per_minute` - How many requests can be made each minute once the bucket is empty
+pub struct Bucket {
+    pub capacity: u32,
+    pub per_minute: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+/// The configuration of the rate limits
+/// This is read from the `rate_limit` section of `Rocket.toml`
+/// Each field can be overridden with a `ROCKET_RATE_LIMIT_<FIELD>` environment variable, e.g. `ROCKET_RATE_LIMIT_STORE=database`
+///
+/// # Fields
+/// * `enabled` - Whether requests are rate limited at all
+/// * `store` - Where the rate limits are kept
+/// * `log_in` - The bucket for logging in from each IP
+/// * `log_in_account` - The bucket for logging in to each account, from any IP
+/// * `sign_up` - The bucket for signing up from each IP
+/// * `availability` - The bucket for checking if a username or email is free from each IP
+/// * `delay_after` - How many log ins to an account can fail in a row before it has to wait between attempts
+/// * `delay_seconds` - The first wait, it doubles with each failure after that
+/// * `max_delay_seconds` - The longest wait before the account is locked
+/// * `lockout_after` - How many log ins to an account can fail in a row before it is locked
+/// * `lockout_minutes` - How long an account is locked for, also how long failures are remembered
+/// * `purge_interval_minutes` - How often the database store deletes the limits which have expired
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub store: RateLimitStore,
+    pub log_in: Bucket,
+    pub log_in_account: Bucket,
+    pub sign_up: Bucket,
+    pub availability: Bucket,
+    pub delay_after: u32,
+    pub delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    pub lockout_after: u32,
+    pub lockout_minutes: u64,
+    pub purge_interval_minutes: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            store: RateLimitStore::Memory,
+            log_in: Bucket { capacity: 20, per_minute: 10 },
+            log_in_account: Bucket { capacity: 10, per_minute: 5 },
+            sign_up: Bucket { capacity: 5, per_minute: 1 },
+            availability: Bucket { capacity: 30, per_minute: 30 },
+            delay_after: 3,
+            delay_seconds: 1,
+            max_delay_seconds: 30,
+            lockout_after: 10,
+            lockout_minutes: 15,
+            purge_interval_minutes: 10,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Read the rate limit configuration
+    /// Its okay for this to panic because the backend shouldn't run with rate limits it can't read
+    ///
+    /// # Returns
+    /// `RateLimitConfig` - The configuration, using the defaults for anything not set
+    pub fn load() -> RateLimitConfig {
+        let figment = rocket::Config::figment()
+            .merge(Env::prefixed("ROCKET_RATE_LIMIT_").map(|key| format!("rate_limit.{}", key).into()).global());
+
+        // If there is no rate_limit section at all use the defaults
+        if !figment.contains("rate_limit") {
+            return RateLimitConfig::default();
+        }
+
+        figment
+            .extract_inner("rate_limit")
+            .expect("Invalid rate limit configuration")
+    }
+
+    /// Get the rate limit configuration the backend is using
+    ///
+    /// # Returns
+    /// `&RateLimitConfig` - The configuration
+    pub fn get() -> &'static RateLimitConfig {
+        &CONFIG
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Why a request was rate limited, with how long to wait before trying again
+///
+/// # Variants
+/// * `TooManyRequests` - The routes have been used too often from this IP or for this account
+/// * `TooManyAttempts` - Log ins to the account have failed several times in a row, so it has to wait between attempts
+/// * `AccountLocked` - Log ins to the account have failed too many times in a row, so it is locked for a while
+pub enum Limited {
+    TooManyRequests(Duration),
+    TooManyAttempts(Duration),
+    AccountLocked(Duration),
+}
+
+impl From<Limited> for Problem {
+    fn from(limited: Limited) -> Self {
+        let (problem, wait) = match limited {
+            Limited::TooManyRequests(wait) => (Problem::new("too_many_requests", "Too many requests, try again later"), wait),
+            Limited::TooManyAttempts(wait) => (Problem::new("too_many_attempts", "Too many failed log ins, try again later"), wait),
+            Limited::AccountLocked(wait) => (Problem::new("account_locked", "Too many failed log ins, the account is locked for a while"), wait),
+        };
+        problem.with_retry_after(wait)
+    }
+}
+
+/// A group of routes which share a rate limit
+pub trait RouteGroup: Send + Sync + 'static {
+    /// The name of the group, used in the keys of its buckets
+    const NAME: &'static str;
+
+    /// Get the bucket for each IP from the configuration
+    ///
+    /// # Arguments
+    /// * `config` - The rate limit configuration
+    ///
+    /// # Returns
+    /// * `Bucket` - The bucket
+    fn bucket(config: &RateLimitConfig) -> Bucket;
+}
+
+/// Logging in
+pub struct LogIn;
+
+impl RouteGroup for LogIn {
+    const NAME: &'static str = "log_in";
+
+    fn bucket(config: &RateLimitConfig) -> Bucket {
+        config.log_in
+    }
+}
+
+/// Signing up
+pub struct SignUp;
+
+impl RouteGroup for SignUp {
+    const NAME: &'static str = "sign_up";
+
+    fn bucket(config: &RateLimitConfig) -> Bucket {
+        config.sign_up
+    }
+}
+
+/// Checking if a username or email is free
+pub struct Availability;
+
+impl RouteGroup for Availability {
+    const NAME: &'static str = "availability";
+
+    fn bucket(config: &RateLimitConfig) -> Bucket {
+        config.availability
+    }
+}
+
+/// Request guard which limits how often a group of routes can be used from each IP
+/// Handlers taking this are answered with 429 Too Many Requests by the catcher once the bucket for the IP is empty
+/// The IP is the address the connection came from, headers like `X-Real-IP` are ignored because any client can set them
+/// Requests without an IP, e.g. from the local test client, are not limited by IP
+pub struct RateLimit<G: RouteGroup>(PhantomData<G>);
+
+#[rocket::async_trait]
+impl<'r, G: RouteGroup> FromRequest<'r> for RateLimit<G> {
+    type Error = Limited;
+
+    async fn from_request(request: &'r rocket::request::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
+        let config = RateLimitConfig::get();
+        let ip = match request.remote() {
+            Some(remote) if config.enabled => remote.ip(),
+            _ => return rocket::request::Outcome::Success(RateLimit(PhantomData)),
+        };
+
+        let request_id = RequestId(request_id(request).to_string());
+        match take(&format!("{}:ip:{}", G::NAME, ip), G::bucket(config), &request_id).await {
+            Ok(()) => rocket::request::Outcome::Success(RateLimit(PhantomData)),
+            Err(wait) => {
+                // Store why so the 429 catcher can say how long to wait
+                let limited = Limited::TooManyRequests(wait);
+                request.local_cache(|| Some(limited));
+                rocket::request::Outcome::Error((Status::TooManyRequests, limited))
+            }
+        }
+    }
+}
+
+#[catch(429)]
+/// Catch requests rejected by the rate limit guards
+///
+/// # Arguments
+/// * `request` - The request that was rejected
+///
+/// # Returns
+/// * `Problem` - The reason the request was rejected and how long to wait
+pub fn too_many_requests_catcher(request: &rocket::Request) -> Problem {
+    let problem = match request.local_cache(|| None::<Limited>) {
+        Some(limited) => Problem::from(*limited),
+        None => Problem::new("too_many_requests", "Too many requests, try again later"),
+    };
+    problem.with_status(Status::TooManyRequests)
+}
+
+/// The key used for the failed log ins of an account
+/// It is made from the id of the user, so logging in with the username and the email count together
+///
+/// # Arguments
+/// * `user_id` - The id of the user, or `None` if no user has the username or email
+///
+/// # Returns
+/// * `String` - The key, e.g. `user:abc123`, or `unknown` for every username and email which isn't a user
+pub fn account_key(user_id: Option<&str>) -> String {
+    match user_id {
+        Some(id) => format!("user:{}", id),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Check an account can be logged in to, before its password is checked
+/// The account has to wait if its last log ins failed, and each log in takes a token from the bucket for the account
+///
+/// # Arguments
+/// * `account` - The account, from `account_key`
+/// * `request_id` - The id of the request, for logging errors from the store
+///
+/// # Returns
+/// * `Result<(), Limited>` - Nothing if the password can be checked, or why not
+pub async fn check_account(account: &str, request_id: &RequestId) -> Result<(), Limited> {
+    let config = RateLimitConfig::get();
+    if !config.enabled {
+        return Ok(());
+    }
+
+    // Wait for the delay or lockout from the last failure
+    if let Some(attempt) = get_attempt(account, request_id).await
+        && let Some(retry_at) = attempt.retry_at
+        && let Ok(wait) = (retry_at - Utc::now()).to_std() {
+        return Err(if attempt.failures >= i64::from(config.lockout_after) {
+            Limited::AccountLocked(wait)
+        } else {
+            Limited::TooManyAttempts(wait)
+        });
+    }
+
+    take(&format!("{}:account:{}", LogIn::NAME, account), config.log_in_account, request_id).await.map_err(Limited::TooManyRequests)
+}
+
+/// Count a failed log in to an account, making it wait or locking it if too many have failed
+///
+/// # Arguments
+/// * `account` - The account, from `account_key`
+/// * `request_id` - The id of the request, for logging errors from the store
+pub async fn record_failure(account: &str, request_id: &RequestId) {
+    let config = RateLimitConfig::get();
+    if !config.enabled {
+        return;
+    }
+
+    let lockout = chrono::Duration::minutes(config.lockout_minutes as i64);
+    let failures = match config.store {
+        RateLimitStore::Memory => record_memory_failure(account, lockout),
+        RateLimitStore::Database => match record_login_failure(account, lockout).await {
+            Ok(failures) => failures,
+            Err(err) => {
+                request_id.log("recording a failed log in", err);
+                return;
+            }
+        },
+    };
+
+    if let Some(wait) = failure_delay(config, failures) {
+        let retry_at = Utc::now() + chrono::Duration::seconds(wait.as_secs() as i64);
+        match config.store {
+            RateLimitStore::Memory => set_memory_retry_at(account, retry_at, lockout),
+            RateLimitStore::Database => {
+                if let Err(err) = set_login_retry_at(account, retry_at).await {
+                    request_id.log("delaying an account", err);
+                }
+            },
+        }
+    }
+}
+
+/// Forget the failed log ins to an account after it has been logged in to
+///
+/// # Arguments
+/// * `account` - The account, from `account_key`
+/// * `request_id` - The id of the request, for logging errors from the store
+pub async fn record_success(account: &str, request_id: &RequestId) {
+    match RateLimitConfig::get().store {
+        RateLimitStore::Memory => {
+            MEMORY_ATTEMPTS.lock().unwrap().remove(account);
+        },
+        RateLimitStore::Database => {
+            if let Err(err) = clear_login_attempts(account).await {
+                request_id.log("clearing failed log ins", err);
+            }
+        },
+    }
+}
+
+/// How long an account has to wait after some failed log ins in a row
+/// Once `delay_after` have failed the wait starts at `delay_seconds` and doubles each time, until `lockout_after` locks the account
+///
+/// # Arguments
+/// * `config` - The rate limit configuration
+/// * `failures` - How many log ins have failed in a row
+///
+/// # Returns
+/// * `Option<Duration>` - How long to wait, None if the account can be tried again straight away
+pub fn failure_delay(config: &RateLimitConfig, failures: i64) -> Option<Duration> {
+    if failures >= i64::from(config.lockout_after) {
+        return Some(Duration::from_secs(config.lockout_minutes * 60));
+    }
+    if failures < i64::from(config.delay_after) {
+        return None;
+    }
+
+    let doublings = u32::try_from(failures - i64::from(config.delay_after)).unwrap_or(u32::MAX).min(32);
+    let seconds = config.delay_seconds.saturating_mul(1 << doublings).min(config.max_delay_seconds);
+    Some(Duration::from_secs(seconds))
+}
+
+#[derive(Debug, Clone)]
+/// The failed log ins for an account
+///
+/// # Fields
+/// * `failures` - How many log ins have failed in a row
+/// * `retry_at` - When the account can be tried again
+/// * `updated_at` - When the last log in failed
+struct Attempt {
+    failures: i64,
+    retry_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+/// Take a token from a bucket in the configured store
+///
+/// # Arguments
+/// * `key` - The bucket, e.g. `log_in:ip:127.0.0.1`
+/// * `bucket` - The size and refill rate of the bucket
+/// * `request_id` - The id of the request, for logging errors from the store
+///
+/// # Returns
+/// * `Result<(), Duration>` - Nothing if a token was taken, or how long until there is one
+async fn take(key: &str, bucket: Bucket, request_id: &RequestId) -> Result<(), Duration> {
+    let per_minute = bucket.per_minute.max(1);
+    let tokens = match RateLimitConfig::get().store {
+        RateLimitStore::Memory => take_memory_token(key, bucket.capacity, per_minute),
+        RateLimitStore::Database => match take_token(key, bucket.capacity, per_minute).await {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                // Let the request through rather than locking everyone out when the database is having problems
+                request_id.log("taking a rate limit token", err);
+                return Ok(());
+            }
+        },
+    };
+
+    if tokens >= 1.0 {
+        return Ok(());
+    }
+    let per_milli = f64::from(per_minute) / 60_000.0;
+    Err(Duration::from_millis(((1.0 - tokens) / per_milli).ceil() as u64))
+}
+
+/// Take a token from a bucket in the memory store
+/// The bucket is kept until it has filled up again at its own rate, after that it is the same as a new one
+///
+/// # Arguments
+/// * `key` - The bucket
+/// * `capacity` - The most tokens the bucket can hold, a new bucket starts full
+/// * `per_minute` - How many tokens are added back each minute
+///
+/// # Returns
+/// * `f64` - The tokens in the bucket before this request, one was taken if there was at least one
+fn take_memory_token(key: &str, capacity: u32, per_minute: u32) -> f64 {
+    let capacity = f64::from(capacity);
+    let per_milli = f64::from(per_minute) / 60_000.0;
+    let now = Instant::now();
+    let mut buckets = MEMORY_BUCKETS.lock().unwrap();
+
+    let (tokens, at) = buckets.get(key, now).copied().unwrap_or((capacity, now));
+    let available = (tokens + now.duration_since(at).as_millis() as f64 * per_milli).min(capacity);
+    let tokens = if available >= 1.0 { available - 1.0 } else { available };
+
+    let full_in = Duration::from_millis(((capacity - tokens) / per_milli).ceil() as u64);
+    buckets.insert(key, (tokens, now), now + full_in, now);
+    available
+}
+
+/// When the failed log ins to an account can be forgotten by the memory store
+///
+/// # Arguments
+/// * `attempt` - The failed log ins
+/// * `forget_after` - How long after the last failure the count starts again
+///
+/// # Returns
+/// * `Instant` - When the count starts again and the account doesn't have to wait any more
+fn attempt_expiry(attempt: &Attempt, forget_after: chrono::Duration) -> Instant {
+    let until = (attempt.updated_at + forget_after).max(attempt.retry_at.unwrap_or(attempt.updated_at));
+    Instant::now() + (until - Utc::now()).to_std().unwrap_or_default()
+}
+
+/// Count a failed log in to an account in the memory store
+///
+/// # Arguments
+/// * `account` - The account
+/// * `forget_after` - How long after the last failure the count starts again
+///
+/// # Returns
+/// * `i64` - How many log ins have failed in a row, including this one
+fn record_memory_failure(account: &str, forget_after: chrono::Duration) -> i64 {
+    let now = Utc::now();
+    let mut attempts = MEMORY_ATTEMPTS.lock().unwrap();
+
+    // An attempt which has expired isn't returned, so the count starts again
+    let failures = attempts.get(account, Instant::now()).map_or(0, |attempt| attempt.failures) + 1;
+    let attempt = Attempt { failures, retry_at: None, updated_at: now };
+    attempts.insert(account, attempt.clone(), attempt_expiry(&attempt, forget_after), Instant::now());
+    failures
+}
+
+/// Make an account in the memory store wait before it can be logged in to again
+///
+/// # Arguments
+/// * `account` - The account
+/// * `retry_at` - When it can be tried again
+/// * `forget_after` - How long after the last failure the count starts again
+fn set_memory_retry_at(account: &str, retry_at: DateTime<Utc>, forget_after: chrono::Duration) {
+    let mut attempts = MEMORY_ATTEMPTS.lock().unwrap();
+    if let Some(attempt) = attempts.get(account, Instant::now()) {
+        let attempt = Attempt { retry_at: Some(retry_at), ..attempt.clone() };
+        attempts.insert(account, attempt.clone(), attempt_expiry(&attempt, forget_after), Instant::now());
+    }
+}
+
+/// Get the failed log ins for an account from the configured store
+///
+/// # Arguments
+/// * `account` - The account
+/// * `request_id` - The id of the request, for logging errors from the store
+///
+/// # Returns
+/// * `Option<Attempt>` - The failed log ins, None if there haven't been any
+async fn get_attempt(account: &str, request_id: &RequestId) -> Option<Attempt> {
+    match RateLimitConfig::get().store {
+        RateLimitStore::Memory => MEMORY_ATTEMPTS.lock().unwrap().get(account, Instant::now()).cloned(),
+        RateLimitStore::Database => match get_login_attempt(account).await {
+            Ok(attempt) => attempt.map(|a| Attempt {
+                failures: a.failures,
+                retry_at: a.retry_at.as_deref().and_then(|r| DateTime::parse_from_rfc3339(r).ok()).map(|r| r.with_timezone(&Utc)),
+                updated_at: a.updated_at.as_deref().and_then(|u| DateTime::parse_from_rfc3339(u).ok()).map(|u| u.with_timezone(&Utc)).unwrap_or_else(Utc::now),
+            }),
+            Err(err) => {
+                request_id.log("getting failed log ins", err);
+                None
+            }
+        },
+    }
+}
@@ -0,0 +1,136 @@
+use rocket::{get, post, delete, serde::json::Json};
+use crate::database::share::{get_shares, share, unshare, ShareTarget};
+use crate::database::users::get_user_by_username;
+use crate::model::share::{Share, ShareRequest, ShareRole};
+use super::auth::AuthenticatedUser;
+use super::problem::Problem;
+use super::{require, Permission, Resource, Response};
+
+/// List who a task or project is shared with, anyone who can see it can see who it is shared with
+async fn list_shares(user_id: &str, target: ShareTarget<'_>) -> Response<Json<Vec<Share>>> {
+    if let Err(response) = require(user_id, Permission::Role(target, ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_shares(target).await {
+        Ok(shares) => Response::Ok(Json(shares)),
+        Err(err) => Response::from(err),
+    }
+}
+
+/// Share a task or project with a user by their username, only the owner and co-owners can share
+async fn add_share(user_id: &str, target: ShareTarget<'_>, request: ShareRequest) -> Response<Json<Share>> {
+    if let Err(response) = require(user_id, Permission::Role(target, ShareRole::CoOwner)).await {
+        return response;
+    }
+
+    let username = match request.username.as_deref() {
+        Some(u) if !u.is_empty() => u,
+        _ => return Response::BadRequest(Problem::field("username", "required", "Username is required")),
+    };
+    let shared_with = match get_user_by_username(username).await {
+        Ok(u) => u,
+        Err(err) => return Response::from(err).about(Resource::User),
+    };
+    let shared_with_id = shared_with.id.map(|id| id.id.to_raw()).unwrap_or_default();
+
+    match share(target, &shared_with_id, request.role.unwrap_or_default()).await {
+        Ok(share) => Response::Created(Json(share)),
+        Err(err) => Response::from(err),
+    }
+}
+
+/// Stop sharing a task or project with a user, the owner and co-owners can remove anyone and users can remove themselves
+async fn remove_share(user_id: &str, target: ShareTarget<'_>, shared_user_id: &str) -> Response<Json<Share>> {
+    if user_id != shared_user_id && let Err(response) = require(user_id, Permission::Role(target, ShareRole::CoOwner)).await {
+        return response;
+    }
+
+    match unshare(target, shared_user_id).await {
+        Ok(share) => Response::Ok(Json(share)),
+        Err(err) => Response::from(err).about(Resource::Share),
+    }
+}
+
+#[get("/tasks/<task_id>/shares")]
+/// Get who a task is shared with
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Share>>>` - A response indicating the result of the retrieval. If successful, it returns the shares, oldest first, in JSON format.
+pub async fn get_task_shares_handler(task_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<Share>>> {
+    list_shares(&user.id, ShareTarget::Task(task_id)).await
+}
+
+#[post("/tasks/<task_id>/shares", data = "<request>")]
+/// Share a task with another user
+/// Sharing again with the same user changes their role.
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `request` - A JSON payload containing the `username` to share with and their `role`: `viewer` (default), `editor` or `co-owner`.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the owner or a co-owner.
+///
+/// # Returns
+/// * `Response<Json<Share>>` - A response indicating the result of sharing. If successful, it returns the share in JSON format.
+pub async fn share_task_handler(task_id: &str, request: Json<ShareRequest>, user: AuthenticatedUser) -> Response<Json<Share>> {
+    add_share(&user.id, ShareTarget::Task(task_id), request.into_inner()).await
+}
+
+#[delete("/tasks/<task_id>/shares/<user_id>")]
+/// Stop sharing a task with a user
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `user_id` - The ID of the user it is shared with.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the owner, a co-owner or the user it is shared with.
+///
+/// # Returns
+/// * `Response<Json<Share>>` - A response indicating the result. If successful, it returns the removed share in JSON format.
+pub async fn unshare_task_handler(task_id: &str, user_id: &str, user: AuthenticatedUser) -> Response<Json<Share>> {
+    remove_share(&user.id, ShareTarget::Task(task_id), user_id).await
+}
+
+#[get("/projects/<project_id>/shares")]
+/// Get who a project is shared with
+///
+/// # Arguments
+/// * `project_id` - The ID of the project.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Share>>>` - A response indicating the result of the retrieval. If successful, it returns the shares, oldest first, in JSON format.
+pub async fn get_project_shares_handler(project_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<Share>>> {
+    list_shares(&user.id, ShareTarget::Project(project_id)).await
+}
+
+#[post("/projects/<project_id>/shares", data = "<request>")]
+/// Share a project with another user, the role applies to every task in the project
+///
+/// # Arguments
+/// * `project_id` - The ID of the project.
+/// * `request` - A JSON payload containing the `username` to share with and their `role`: `viewer` (default), `editor` or `co-owner`.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the owner or a co-owner.
+///
+/// # Returns
+/// * `Response<Json<Share>>` - A response indicating the result of sharing. If successful, it returns the share in JSON format.
+pub async fn share_project_handler(project_id: &str, request: Json<ShareRequest>, user: AuthenticatedUser) -> Response<Json<Share>> {
+    add_share(&user.id, ShareTarget::Project(project_id), request.into_inner()).await
+}
+
+#[delete("/projects/<project_id>/shares/<user_id>")]
+/// Stop sharing a project with a user
+///
+/// # Arguments
+/// * `project_id` - The ID of the project.
+/// * `user_id` - The ID of the user it is shared with.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be the owner, a co-owner or the user it is shared with.
+///
+/// # Returns
+/// * `Response<Json<Share>>` - A response indicating the result. If successful, it returns the removed share in JSON format.
+pub async fn unshare_project_handler(project_id: &str, user_id: &str, user: AuthenticatedUser) -> Response<Json<Share>> {
+    remove_share(&user.id, ShareTarget::Project(project_id), user_id).await
+}
@@ -0,0 +1,93 @@
+use rocket::{get, http::Status, post, serde::json::Json};
+use crate::database::sync::get_changes;
+use crate::model::sync::{SyncMutation, SyncPage, SyncQuery, SyncRequest, SyncResult};
+use crate::model::todotask::ToDoTask;
+use super::auth::AuthenticatedUser;
+use super::conditional::{etag, Preconditions};
+use super::todotask::{create_task_handler, delete_task_handler, update_task_handler};
+use super::problem::{log_error, Problem, RequestId};
+use super::Response;
+
+/// The most changes a client can upload at once
+const MAX_SYNC_MUTATIONS: usize = 100;
+
+#[get("/sync?<query..>")]
+/// Get the changes to the tasks the user can see since the last sync
+/// 
+/// # Arguments
+/// * `query` - The `since` cursor returned by the last page, leave it out to get every task, and the `limit` of changes in a page.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<SyncPage>>` - The changed tasks, oldest change first, with deleted tasks marked as `deleted`, the cursor for the next page or sync and if there are more changes.
+pub async fn get_sync_handler(query: SyncQuery, user: AuthenticatedUser) -> Response<Json<SyncPage>> {
+    match get_changes(&user.id, &query).await {
+        Ok(page) => Response::Ok(Json(page)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[post("/sync", data = "<input>")]
+/// Upload changes made on a client while it was offline
+/// Each change is applied in order as if it was sent to its own route, one failing doesn't stop the rest
+/// 
+/// # Arguments
+/// * `input` - A JSON payload with the changes to apply.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// * `request_id` - The id of the request, the errors of the changes are logged with it because they are sent in the results instead of as a response.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<SyncResult>>>` - The result of each change, in the same order as the changes.
+pub async fn post_sync_handler(input: Json<SyncRequest>, user: AuthenticatedUser, request_id: RequestId) -> Response<Json<Vec<SyncResult>>> {
+    let input = input.into_inner();
+
+    if input.mutations.len() > MAX_SYNC_MUTATIONS {
+        return Response::BadRequest(Problem::field("mutations", "too_many", format!("At most {} changes can be uploaded at once", MAX_SYNC_MUTATIONS)));
+    }
+
+    let mut results = Vec::with_capacity(input.mutations.len());
+    for mutation in input.mutations {
+        let result = match mutation {
+            SyncMutation::Create { client_id, task } => to_result(create_task_handler(Json(task), user.clone()).await, client_id, &request_id),
+            SyncMutation::Update { id, task, version } => {
+                let conditions = Preconditions { if_match: version.map(|v| etag(Some(v))), ..Default::default() };
+                to_result(update_task_handler(&id, Json(task), user.clone(), conditions, request_id.clone()).await.map(|tagged| tagged.inner), None, &request_id)
+            },
+            SyncMutation::Delete { id } => to_result(delete_task_handler(&id, user.clone()).await, None, &request_id),
+        };
+        results.push(result);
+    }
+
+    Response::Ok(Json(results))
+}
+
+/// Turn the response of a task route into the result of an uploaded change
+/// 
+/// # Arguments
+/// * `response` - The response of the route
+/// * `client_id` - The id the client gave a created task
+/// * `request_id` - The id of the request, to log the cause of an internal error
+/// 
+/// # Returns
+/// * `SyncResult` - The status and task, or the error
+fn to_result(response: Response<Json<ToDoTask>>, client_id: Option<String>, request_id: &RequestId) -> SyncResult {
+    let (status, task, error) = match response {
+        Response::Ok(task) => (Status::Ok, Some(task.into_inner()), None),
+        Response::Created(task) => (Status::Created, Some(task.into_inner()), None),
+        Response::NoContent(()) => (Status::NoContent, None, None),
+        Response::NotModified(_) => (Status::NotModified, None, None),
+        Response::BadRequest(problem) => (Status::BadRequest, None, Some(problem)),
+        Response::Unauthorized(problem) => (Status::Unauthorized, None, Some(problem)),
+        Response::Forbidden(problem) => (Status::Forbidden, None, Some(problem)),
+        Response::NotFound(problem) => (Status::NotFound, None, Some(problem)),
+        Response::Conflict(problem) => (Status::Conflict, None, Some(problem)),
+        Response::PreconditionFailed(problem) => (Status::PreconditionFailed, None, Some(problem)),
+        Response::TooManyRequests(problem) => (Status::TooManyRequests, None, Some(problem)),
+        Response::InternalServerError(problem) => (Status::InternalServerError, None, Some(problem)),
+    };
+    if let Some(cause) = error.as_ref().and_then(|problem| problem.cause.as_deref()) {
+        log_error(&request_id.0, cause);
+    }
+    let error = error.map(|problem| problem.with_status(status));
+    SyncResult { status: status.code, client_id, task, error }
+}
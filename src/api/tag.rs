@@ -0,0 +1,177 @@
+use rocket::{get, post, put, patch, delete, serde::json::Json};
+use crate::database::tag::{create_tag, delete_tag_by_id, get_tags_by_user, get_task_tags, merge_tags, rename_tag, tag_task, untag_task};
+use crate::model::tag::{Tag, TagMergeRequest, TagRequest};
+use super::auth::AuthenticatedUser;
+use super::{require, Permission, Resource, Response};
+
+#[post("/tags", data = "<input_tag>")]
+/// Create a new tag
+///
+/// # Arguments
+/// * `input_tag` - A JSON payload containing the name of the tag.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Tag>>` - A response indicating the result of the tag creation process. If successful, it returns the created tag in JSON format, 409 Conflict if the user already has a tag with the name.
+pub async fn create_tag_handler(input_tag: Json<TagRequest>, user: AuthenticatedUser) -> Response<Json<Tag>> {
+    let input_tag = input_tag.into_inner(); // Deserialise the input from JSON
+
+    match create_tag(&user.id, input_tag.name.as_deref().unwrap_or_default()).await {
+        Ok(tag) => Response::Created(Json(tag)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[get("/tags")]
+/// Get the tags of the user
+///
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Tag>>>` - A response indicating the result of the retrieval. If successful, it returns the tags in order of name in JSON format.
+pub async fn get_tags_handler(user: AuthenticatedUser) -> Response<Json<Vec<Tag>>> {
+    match get_tags_by_user(&user.id).await {
+        Ok(tags) => Response::Ok(Json(tags)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[patch("/tags/<tag_id>", data = "<update_tag>")]
+/// Rename a tag
+///
+/// # Arguments
+/// * `tag_id` - The ID of the tag to be renamed.
+/// * `update_tag` - A JSON payload containing the new name of the tag.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Tag>>` - A response indicating the result of the rename. If successful, it returns the renamed tag in JSON format, 409 Conflict if the user already has a tag with the name.
+pub async fn rename_tag_handler(tag_id: &str, update_tag: Json<TagRequest>, user: AuthenticatedUser) -> Response<Json<Tag>> {
+    let update_tag = update_tag.into_inner(); // Deserialise the input from JSON
+
+    if let Err(response) = require(&user.id, Permission::TagOwner(tag_id)).await {
+        return response;
+    }
+
+    match rename_tag(tag_id, update_tag.name.as_deref().unwrap_or_default()).await {
+        Ok(tag) => Response::Ok(Json(tag)),
+        Err(err) => Response::from(err).about(Resource::Tag),
+    }
+}
+
+#[post("/tags/<tag_id>/merge", data = "<merge>")]
+/// Merge a tag into another
+/// The tasks with the tag get the tag it is merged into, then the tag is deleted.
+///
+/// # Arguments
+/// * `tag_id` - The ID of the tag to be merged and deleted.
+/// * `merge` - A JSON payload containing `into`, the ID of the tag to keep.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Tag>>` - A response indicating the result of the merge. If successful, it returns the kept tag in JSON format.
+pub async fn merge_tags_handler(tag_id: &str, merge: Json<TagMergeRequest>, user: AuthenticatedUser) -> Response<Json<Tag>> {
+    let merge = merge.into_inner(); // Deserialise the input from JSON
+
+    // The user must own both tags
+    if let Err(response) = require(&user.id, Permission::TagOwner(tag_id)).await {
+        return response;
+    }
+    if let Err(response) = require(&user.id, Permission::TagOwner(&merge.into)).await {
+        return response;
+    }
+
+    match merge_tags(tag_id, &merge.into).await {
+        Ok(tag) => Response::Ok(Json(tag)),
+        Err(err) => Response::from(err).about(Resource::Tag),
+    }
+}
+
+#[delete("/tags/<tag_id>")]
+/// Delete a tag
+/// The tag is removed from all of its tasks.
+///
+/// # Arguments
+/// * `tag_id` - The ID of the tag to be deleted.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Tag>>` - A response indicating the result of the deletion. If successful, it returns the deleted tag in JSON format.
+pub async fn delete_tag_handler(tag_id: &str, user: AuthenticatedUser) -> Response<Json<Tag>> {
+    if let Err(response) = require(&user.id, Permission::TagOwner(tag_id)).await {
+        return response;
+    }
+
+    match delete_tag_by_id(tag_id).await {
+        Ok(tag) => Response::Ok(Json(tag)),
+        Err(err) => Response::from(err).about(Resource::Tag),
+    }
+}
+
+#[get("/tasks/<task_id>/tags")]
+/// Get the tags of a task
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Tag>>>` - A response indicating the result of the retrieval. If successful, it returns the tags in order of name in JSON format.
+pub async fn get_task_tags_handler(task_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<Tag>>> {
+    if let Err(response) = require(&user.id, Permission::TaskOwner(task_id)).await {
+        return response;
+    }
+
+    match get_task_tags(task_id).await {
+        Ok(tags) => Response::Ok(Json(tags)),
+        Err(err) => Response::from(err).about(Resource::Task),
+    }
+}
+
+#[put("/tasks/<task_id>/tags/<tag_id>")]
+/// Put a tag on a task
+/// Putting a tag on a task which already has it changes nothing.
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `tag_id` - The ID of the tag.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Tag>>>` - A response indicating the result. If successful, it returns the tags of the task in JSON format.
+pub async fn tag_task_handler(task_id: &str, tag_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<Tag>>> {
+    // The user must own the task and the tag
+    if let Err(response) = require(&user.id, Permission::TaskOwner(task_id)).await {
+        return response;
+    }
+    if let Err(response) = require(&user.id, Permission::TagOwner(tag_id)).await {
+        return response;
+    }
+
+    match tag_task(task_id, tag_id).await {
+        Ok(tags) => Response::Ok(Json(tags)),
+        Err(err) => Response::from(err).about(Resource::Task),
+    }
+}
+
+#[delete("/tasks/<task_id>/tags/<tag_id>")]
+/// Take a tag off a task
+///
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `tag_id` - The ID of the tag.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+///
+/// # Returns
+/// * `Response<Json<Vec<Tag>>>` - A response indicating the result. If successful, it returns the remaining tags of the task in JSON format.
+pub async fn untag_task_handler(task_id: &str, tag_id: &str, user: AuthenticatedUser) -> Response<Json<Vec<Tag>>> {
+    if let Err(response) = require(&user.id, Permission::TaskOwner(task_id)).await {
+        return response;
+    }
+
+    match untag_task(task_id, tag_id).await {
+        Ok(tags) => Response::Ok(Json(tags)),
+        Err(err) => Response::from(err).about(Resource::TaskTag),
+    }
+}
@@ -1,59 +1,48 @@
 use rocket::get;
-use rocket::{post, patch, delete, serde::json::Json};
-use crate::database::todotask::{check_is_owner, create_task, delete_task_by_id, edit_task_by_id, get_task_by_id, get_all_tasks_by_user};
-use crate::model::todotask::ToDoTask;
-use super::auth::{verify_token, JWT};
-use super::Response;
+use rocket::{post, put, patch, delete, serde::json::Json};
+use crate::database::notification::{create_notification, notify_task_users};
+use crate::database::share::{get_tasks_shared_with, ShareTarget};
+use crate::database::todotask::{create_task, delete_task_by_id, edit_task_by_id, get_overdue_tasks, get_subtasks, get_task_by_id, get_task_completions, get_task_tree, get_tasks_assigned_to, get_tasks_by_user, get_trash, get_trashed_task_by_id, get_upcoming_tasks, purge_task_by_id, restore_task_by_id, set_assignee};
+use crate::database::users::get_user_by_username;
+use crate::database::config::DatabaseConfig;
+use crate::model::notification::NotificationKind;
+use crate::model::share::{ShareRole, SharedTask};
+use crate::model::todotask::{AssignRequest, TaskCompletion, TaskPage, TaskQuery, TaskRequest, TaskTree, ToDoTask};
+use super::auth::AuthenticatedUser;
+use super::conditional::{Preconditions, Tagged};
+use super::problem::{Problem, RequestId};
+use super::validation;
+use super::{require, Permission, Resource, Response};
 
 #[post("/tasks", data = "<input_task>")]
 /// Create a new task
 /// This function handles the creation of a new task by accepting a JSON payload containing the task's details.
 /// 
 /// # Arguments
-/// * `input_task` - A JSON payload containing the task's details, including title, description, and completed_at.
-/// * `jwt` - A JWT token for authentication, which is passed in the request `Authorization` header.
+/// * `input_task` - A JSON payload containing the task's details, including title, description, completed_at, due_at, remind_at and recurrence.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
 /// 
 /// # Returns
 /// * `Response<Json<ToDoTask>>` - A response indicating the result of the task creation process. If successful, it returns the created task in JSON format.
 pub async fn create_task_handler(
-    input_task: Json<ToDoTask>,
-    jwt: JWT,
+    input_task: Json<TaskRequest>,
+    user: AuthenticatedUser,
 ) -> Response<Json<ToDoTask>> {
     let input_task = input_task.into_inner(); // Deserialise the input from JSON
+    let user_id = user.id;
 
-    // Verify the token & extract the user ID from it
-    let user_id = verify_token(&jwt.token).await; 
-    if user_id.is_err() {
-        return Response::Unauthorized("Invalid token".to_string())
+    // Check the title is there and every field is valid
+    if let Err(errors) = validation::task(&input_task, true, None) {
+        return Response::BadRequest(Problem::fields(errors));
     }
-    let user_id = user_id.unwrap().sub; // Extract the user ID from the token
-
-    // Option<String> -> Option<&str>
-    let title = input_task.title.as_deref();
-    let description = input_task.description.as_deref();
-    let completed_at = input_task.completed_at.as_deref();
-    let created_at = input_task.created_at.as_deref();
-
-    // Check if the title is empty
-    if title.is_none() {
-        return Response::BadRequest("Title is required".to_string());
-    }
-    let title = title.unwrap();
 
     // Create the task 
-    let created_task = create_task(&user_id, title, description, completed_at, created_at).await;
+    let created_task = create_task(&user_id, &input_task).await;
 
     // Check if there was an error
     if created_task.is_err() {
         let err = created_task.unwrap_err();
-        return match err {
-            crate::database::DBCreateError::AlreadyExists(_) => Response::BadRequest("This task already exists".to_string()),
-            crate::database::DBCreateError::BadData(_) => Response::BadRequest("The data provided is invalid".to_string()),
-            crate::database::DBCreateError::Other(_) => {
-                dbg!("Unhandled/Unkown error creating task: {:?}", err);
-                Response::InternalServerError("There was an unkown error".to_string())
-            }
-        }
+        return Response::from(err);
     }
     let task = created_task.unwrap();
 
@@ -67,66 +56,284 @@ pub async fn create_task_handler(
 /// 
 /// # Arguments
 /// * `task_id` - The ID of the task to be retrieved.
-/// * `jwt` - A JWT token for authentication, which is passed in the request `Authorization` header.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// * `conditions` - The `If-None-Match` header, if the task still has the tag 304 Not Modified is returned instead.
 /// 
 /// # Returns
-/// * `Response<Json<ToDoTask>>` - A response indicating the result of the task retrieval process. If successful, it returns the task in JSON format.
-pub async fn get_task_handler(task_id: &str, jwt: JWT) -> super::Response<Json<ToDoTask>> {
-    // Verify the JWT and extract the user id
-    let user_id = verify_token(&jwt.token).await;
-    if user_id.is_err() {
-        return Response::Unauthorized("Invalid token".to_string())
-    }
-    let user_id = user_id.unwrap().sub;
+/// * `Response<Tagged<Json<ToDoTask>>>` - A response indicating the result of the task retrieval process. If successful, it returns the task in JSON format with its `ETag`.
+pub async fn get_task_handler(task_id: &str, user: AuthenticatedUser, conditions: Preconditions) -> super::Response<Tagged<Json<ToDoTask>>> {
+    let user_id = user.id;
 
-    // Check if the user is the owner of the task, return 403 if not
-    let is_owner = check_is_owner(task_id, &user_id).await;
-    if is_owner.is_err() || !is_owner.unwrap() {
-        return Response::Forbidden("You do not have permissions".to_string());
+    // Check if the user can see the task, the owner or anyone it is shared with. Return 403 if not
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
     }
 
-    // If the user is the owner of the task, return the task
+    // If the user can see the task, return it
     let task = get_task_by_id(task_id).await;
     match task {
-        Ok(task) => Response::Ok(Json(task)),
-        Err(err) => match err {
-            crate::database::DBReadError::NotFound(_) => Response::NotFound("Task not found".to_string()),
-            crate::database::DBReadError::Other(_) => {
-                dbg!("Unhandled/Unknown error retrieving task: {:?}", err);
-                Response::InternalServerError("There was an unknown error".to_string())
-            }
-        }
+        Ok(task) if conditions.not_modified(task.version) => Response::NotModified(Tagged::new((), task.version)),
+        Ok(task) => {
+            let version = task.version;
+            Response::Ok(Tagged::new(Json(task), version))
+        },
+        Err(err) => Response::from(err).about(Resource::Task)
     }
 }
 
-#[get("/tasks")]
-/// Get all tasks by user ID
-/// This function handles the retrieval of all tasks associated with a specific user ID.
+#[get("/tasks?<query..>")]
+/// Get the tasks of the user
+/// This function handles the retrieval of the tasks associated with the authenticated user, one page at a time.
 /// 
 /// # Arguments
-/// * `jwt` - A JWT token for authentication, which is passed in the request `Authorization` header.
+/// * `query` - The query parameters: `completed`, `created_after`, `created_before`, `search`, `project` (an id or `inbox`), `tag` (repeated for each tag id), `tag_match` (`any` or `all`), `sort` (`created_at`, `completed_at` or `title`), `direction` (`asc` or `desc`), `cursor` and `limit`.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
 /// 
 /// # Returns
-/// * `Response<Json<Vec<ToDoTask>>>` - A response indicating the result of the task retrieval process. If successful, it returns a list of tasks in JSON format.
-pub async fn get_tasks_by_user_handler(jwt: JWT) -> super::Response<Json<Vec<ToDoTask>>> {
-    // Verify the JWT and extract the user id
-    let user_id = verify_token(&jwt.token).await;
-    if user_id.is_err() {
-        return Response::Unauthorized("Invalid token".to_string())
-    }
-    let user_id = user_id.unwrap().sub;
+/// * `Response<Json<TaskPage>>` - A response indicating the result of the task retrieval process. If successful, it returns a page of tasks and the cursor of the next page in JSON format.
+pub async fn get_tasks_by_user_handler(query: TaskQuery, user: AuthenticatedUser) -> super::Response<Json<TaskPage>> {
+    let user_id = user.id;
 
-    // Get all tasks by user ID
-    let tasks = get_all_tasks_by_user(&user_id).await;
+    // Get the page of tasks by user ID
+    let tasks = get_tasks_by_user(&user_id, &query).await;
     match tasks {
         Ok(tasks) => Response::Ok(Json(tasks)),
-        Err(err) => match err {
-            crate::database::DBReadError::NotFound(_) => Response::NotFound("No tasks found".to_string()),
-            crate::database::DBReadError::Other(_) => {
-                dbg!("Unhandled/Unknown error retrieving tasks: {:?}", err);
-                Response::InternalServerError("There was an unknown error".to_string())
-            }
-        }
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/<task_id>/completions")]
+/// Get the completion history of a task
+/// This function handles the retrieval of the completed occurrences of a recurring task.
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<TaskCompletion>>>` - A response indicating the result of the retrieval. If successful, it returns the completions, most recent first, in JSON format.
+pub async fn get_task_completions_handler(task_id: &str, user: AuthenticatedUser) -> super::Response<Json<Vec<TaskCompletion>>> {
+    let user_id = user.id;
+
+    // Check if the user can see the task, return 403 if not
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_task_completions(task_id).await {
+        Ok(completions) => Response::Ok(Json(completions)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/<task_id>/subtasks")]
+/// Get the subtasks of a task
+/// This function handles the retrieval of the direct subtasks of a task.
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the parent task.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<ToDoTask>>>` - A response indicating the result of the retrieval. If successful, it returns the subtasks, oldest first, in JSON format.
+pub async fn get_subtasks_handler(task_id: &str, user: AuthenticatedUser) -> super::Response<Json<Vec<ToDoTask>>> {
+    let user_id = user.id;
+
+    // Check if the user can see the task, return 403 if not
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_subtasks(task_id).await {
+        Ok(subtasks) => Response::Ok(Json(subtasks)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/<task_id>/tree")]
+/// Get a task with all of its subtasks
+/// This function handles the retrieval of a task as a tree, with how many subtasks are completed at each level.
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task at the top of the tree.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<TaskTree>>` - A response indicating the result of the retrieval. If successful, it returns the tree in JSON format.
+pub async fn get_task_tree_handler(task_id: &str, user: AuthenticatedUser) -> super::Response<Json<TaskTree>> {
+    let user_id = user.id;
+
+    // Check if the user can see the task, return 403 if not
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Viewer)).await {
+        return response;
+    }
+
+    match get_task_tree(task_id).await {
+        Ok(tree) => Response::Ok(Json(tree)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/overdue")]
+/// Get the overdue tasks of the user
+/// This function handles the retrieval of the tasks which are past their due date and not completed.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<ToDoTask>>>` - A response indicating the result of the task retrieval process. If successful, it returns the overdue tasks, most overdue first, in JSON format.
+pub async fn get_overdue_tasks_handler(user: AuthenticatedUser) -> super::Response<Json<Vec<ToDoTask>>> {
+    let user_id = user.id;
+
+    match get_overdue_tasks(&user_id).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/upcoming?<within>")]
+/// Get the upcoming tasks of the user
+/// This function handles the retrieval of the tasks which are due soon and not completed.
+/// 
+/// # Arguments
+/// * `within` - How far ahead to look, a number followed by `m`, `h`, `d` or `w` e.g. `12h` or `3d`. Defaults to `7d`.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<ToDoTask>>>` - A response indicating the result of the task retrieval process. If successful, it returns the upcoming tasks, soonest first, in JSON format.
+pub async fn get_upcoming_tasks_handler(within: Option<&str>, user: AuthenticatedUser) -> super::Response<Json<Vec<ToDoTask>>> {
+    let user_id = user.id;
+
+    let within = match parse_within(within.unwrap_or("7d")) {
+        Some(w) => w,
+        None => return Response::BadRequest(Problem::field("within", "invalid", "within must be a number followed by m, h, d or w")),
+    };
+
+    match get_upcoming_tasks(&user_id, within).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+#[get("/tasks/shared-with-me")]
+/// Get the tasks shared with the user
+/// This function handles the retrieval of the tasks other users have shared with the authenticated user, directly or through a project.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<SharedTask>>>` - A response indicating the result of the retrieval. If successful, it returns the tasks with the role of the user, oldest first, in JSON format.
+pub async fn get_shared_tasks_handler(user: AuthenticatedUser) -> super::Response<Json<Vec<SharedTask>>> {
+    match get_tasks_shared_with(&user.id).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[get("/tasks/assigned-to-me")]
+/// Get the tasks assigned to the user
+/// This function handles the retrieval of the tasks assigned to the authenticated user which are not completed.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<ToDoTask>>>` - A response indicating the result of the retrieval. If successful, it returns the tasks, soonest due first, in JSON format.
+pub async fn get_assigned_tasks_handler(user: AuthenticatedUser) -> super::Response<Json<Vec<ToDoTask>>> {
+    match get_tasks_assigned_to(&user.id).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[put("/tasks/<task_id>/assignee", data = "<assign>")]
+/// Assign a task to a user
+/// This function handles assigning a task to a user by their username, the user is sent a notification. Assigning a task again replaces the assignee.
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `assign` - A JSON payload containing the `username` of the user to assign the task to.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to share the task, because the assignee can edit it.
+/// * `request_id` - The id of the request, for logging errors sending the notification.
+/// 
+/// # Returns
+/// * `Response<Json<ToDoTask>>` - A response indicating the result of the assignment. If successful, it returns the task in JSON format.
+pub async fn assign_task_handler(task_id: &str, assign: Json<AssignRequest>, user: AuthenticatedUser, request_id: RequestId) -> super::Response<Json<ToDoTask>> {
+    let assign = assign.into_inner();
+    let user_id = user.id;
+
+    // Check if the user can share the task, the owner or a co-owner it is shared with
+    // Editors can't assign it because that would give someone else edit access
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::CoOwner)).await {
+        return response;
+    }
+
+    let username = match assign.username.as_deref() {
+        Some(u) if !u.is_empty() => u,
+        _ => return Response::BadRequest(Problem::field("username", "required", "Username is required")),
+    };
+    let assignee = match get_user_by_username(username).await {
+        Ok(u) => u.id.map(|id| id.id.to_raw()).unwrap_or_default(),
+        Err(err) => return Response::from(err).about(Resource::User),
+    };
+
+    let task = match set_assignee(task_id, Some(&assignee), &user_id).await {
+        Ok(task) => task,
+        Err(err) => return Response::from(err).about(Resource::Task),
+    };
+
+    // Tell the assignee, unless they assigned it to themselves. The task has still been assigned if this fails
+    if assignee != user_id
+        && let Err(err) = create_notification(&assignee, NotificationKind::Assigned, task_id, &user_id).await {
+        request_id.log("creating notification", err);
+    }
+
+    Response::Ok(Json(task))
+}
+
+#[delete("/tasks/<task_id>/assignee")]
+/// Unassign a task
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header. They must be able to edit the task, which the assignee can.
+/// 
+/// # Returns
+/// * `Response<Json<ToDoTask>>` - A response indicating the result. If successful, it returns the task in JSON format.
+pub async fn unassign_task_handler(task_id: &str, user: AuthenticatedUser) -> super::Response<Json<ToDoTask>> {
+    let user_id = user.id;
+
+    // Check if the user can edit the task, the owner, the assignee or an editor or co-owner it is shared with
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Editor)).await {
+        return response;
+    }
+
+    match set_assignee(task_id, None, &user_id).await {
+        Ok(task) => Response::Ok(Json(task)),
+        Err(err) => Response::from(err).about(Resource::Task)
+    }
+}
+
+/// Parse a duration such as `30m`, `12h`, `3d` or `2w`
+/// 
+/// # Arguments
+/// * `within` - The duration to parse
+/// 
+/// # Returns
+/// * `Option<chrono::Duration>` - The duration, or None if it is invalid
+pub fn parse_within(within: &str) -> Option<chrono::Duration> {
+    let within = within.trim();
+    let unit = within.chars().last()?;
+    let amount: u32 = within[..within.len() - unit.len_utf8()].parse().ok()?;
+
+    match unit {
+        'm' => Some(chrono::Duration::minutes(amount.into())),
+        'h' => Some(chrono::Duration::hours(amount.into())),
+        'd' => Some(chrono::Duration::days(amount.into())),
+        'w' => Some(chrono::Duration::weeks(amount.into())),
+        _ => None,
     }
 }
 
@@ -136,111 +343,174 @@ pub async fn get_tasks_by_user_handler(jwt: JWT) -> super::Response<Json<Vec<ToD
 /// 
 /// # Arguments
 /// * `task_id` - The ID of the task to be updated.
-/// * `update_task` - A JSON payload containing the updated task's details, including title, description, and completed_at.
-/// * `jwt` - A JWT token for authentication, which is passed in the request `Authorization` header.
+/// * `update_task` - A JSON payload containing the updated task's details, including title, description, completed_at, due_at, remind_at and recurrence. Completing a recurring task moves it on to its next occurrence.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// * `conditions` - The `If-Match` header, if the task no longer has the tag 412 Precondition Failed is returned and nothing is changed.
+/// * `request_id` - The id of the request, for logging errors sending the notifications.
 /// 
 /// # Returns
-/// * `Response<Json<ToDoTask>>` - A response indicating the result of the task update process. If successful, it returns the updated task in JSON format.
-pub async fn update_task_handler(task_id: &str, update_task: Json<ToDoTask>, jwt: JWT) -> super::Response<Json<ToDoTask>> {
+/// * `Response<Tagged<Json<ToDoTask>>>` - A response indicating the result of the task update process. If successful, it returns the updated task in JSON format with its new `ETag`.
+pub async fn update_task_handler(task_id: &str, update_task: Json<TaskRequest>, user: AuthenticatedUser, conditions: Preconditions, request_id: RequestId) -> super::Response<Tagged<Json<ToDoTask>>> {
 
     // Deserialise the input from JSON
     let update_task = update_task.into_inner();
+    let user_id = user.id;
 
-    // Verify the token and extract the user id 
-    let user_id = verify_token(&jwt.token).await;
-    if user_id.is_err() {
-        return Response::Unauthorized("Invalid token".to_string())
+    // Check if the user can edit the task, the owner or an editor or co-owner it is shared with
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::Editor)).await {
+        return response;
     }
-    let user_id = user_id.unwrap().sub;
-
-    // Option<String> -> Option<&str>
-    let title = update_task.title.as_deref();
-    let description = update_task.description.as_deref();
-    let completed_at = update_task.completed_at.as_deref();
-    let owner = &user_id;
-
-    // Check if the user is the owner of the task -> THIS WILL CHANGE to use the JWT token
-    // This is a temporary solution until we have JWT authentication
-
-    let is_owner = check_is_owner(task_id, owner).await;
+    
+    // Get the task as it is, to check the changes and the version against
+    let before = get_task_by_id(task_id).await.ok();
 
-    if is_owner.is_err() {
-        let err = is_owner.unwrap_err();
-        return match err {
-            crate::database::DBReadError::NotFound(_) => Response::NotFound("Task not found".to_string()),
-            crate::database::DBReadError::Other(_) => {
-                dbg!("Unhandled/Unkown error checking owner: {:?}", err);
-                Response::InternalServerError("There was an unkown error".to_string())
-            }
-        }
+    // Check the fields which are being changed, the task can't be completed before it was created
+    if let Err(errors) = validation::task(&update_task, false, before.as_ref().and_then(|b| b.created_at.as_deref())) {
+        return Response::BadRequest(Problem::fields(errors));
     }
 
-    if !is_owner.unwrap() {
-        return Response::Forbidden("You do not have permissions".to_string());
+    // Only update the version the client has, if it sent one
+    let version = before.as_ref().and_then(|b| b.version);
+    if !conditions.if_match(version) {
+        return Response::PreconditionFailed(Problem::new("version_mismatch", "The task has been changed since it was read"));
     }
-    
+    let expected_version = conditions.if_match.as_ref().and(version);
+
     // Update the task in the DB
-    let updated_task = edit_task_by_id(task_id, title, description, completed_at, Some(owner)).await;
+    let updated_task = edit_task_by_id(task_id, &update_task, None, expected_version, &user_id).await;
 
     // If there was an error handle it
     if updated_task.is_err() {
         let err = updated_task.unwrap_err();
         return match err {
-            crate::database::DBEditError::NotFound(_) => Response::BadRequest("ToDoItem not found".to_string()),
-            crate::database::DBEditError::BadData(wrapped_err) => Response::BadRequest(format!("Invalid data: {}", wrapped_err).to_string()),
-            crate::database::DBEditError::Other(wrapped_err) => { // If the error is unkown log it and return Status 500
-                dbg!("Unkown/Unhandled error when updating a task: {:?}", wrapped_err);
-                Response::InternalServerError("There was an unkown error".to_string())
-            },
+            // Someone else changed the task between reading and updating it
+            crate::database::DBEditError::NotFound(_) if expected_version.is_some() => Response::PreconditionFailed(Problem::new("version_mismatch", "The task has been changed since it was read")),
+            err => Response::from(err).about(Resource::Task),
         }
     }
     let task = updated_task.unwrap();
 
-    // Return the edited task
-    Response::Ok(Json(task))
+    // Tell the owner and assignee the task was completed, only when it wasn't already so sending the whole task back doesn't notify again
+    // The request is checked rather than the result because completing a recurring task moves it on and clears `completed_at`
+    // The task has still been updated if this fails
+    let completed = before.as_ref().is_some_and(|b| b.completed_at.is_none()) && update_task.completed_at.is_some();
+    if completed
+        && let Err(err) = notify_task_users(&task, NotificationKind::Completed, &user_id).await {
+        request_id.log("creating notifications", err);
+    }
+
+    // Return the edited task with its new tag
+    let version = task.version;
+    Response::Ok(Tagged::new(Json(task), version))
     
 }
 
 #[delete("/tasks/<task_id>")]
 /// Delete a task
 /// This function handles the deletion of a task by its ID.
+/// What happens to its subtasks depends on the `subtasks_on_delete` database setting.
 /// 
 /// # Arguments
 /// * `task_id` - The ID of the task to be deleted.
-/// * `jwt` - A JWT token for authentication, which is passed in the request `Authorization` header.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
 /// 
 /// # Returns
 /// * `Response<Json<ToDoTask>>` - A response indicating the result of the task deletion process. If successful, it returns the deleted task in JSON format.
-pub async fn delete_task_handler(task_id: &str, jwt: JWT) -> super::Response<Json<ToDoTask>> {
-    // Verify the JWT
-    let user_id = verify_token(&jwt.token).await;
-    if user_id.is_err() {
-        return Response::Unauthorized("Unauthorised".to_string())
-    }
-    let user_id = user_id.unwrap().sub; // Extract the user ID from the token
+pub async fn delete_task_handler(task_id: &str, user: AuthenticatedUser) -> super::Response<Json<ToDoTask>> {
+    let user_id = user.id;
 
-    // Check if the user is the owner
-    let is_owner = check_is_owner(&user_id, task_id).await;
-    if is_owner.is_err() || !is_owner.unwrap() {
-        return Response::Forbidden("You are not the owner of the task".to_string())
+    // Check if the user can delete the task, the owner or a co-owner it is shared with
+    if let Err(response) = require(&user_id, Permission::Role(ShareTarget::Task(task_id), ShareRole::CoOwner)).await {
+        return response;
     }
     
     // Delete the task
-    let deleted_task = delete_task_by_id(task_id).await;
+    let policy = DatabaseConfig::get().subtasks_on_delete;
+    let deleted_task = delete_task_by_id(task_id, policy, &user_id).await;
 
     // If there was an error handle it correctly
     if deleted_task.is_err() {
         let err = deleted_task.unwrap_err();
-        return match err {
-            crate::database::DBReadError::NotFound(_) => Response::NotFound("Task not found".to_string()),
-            crate::database::DBReadError::Other(_) => {
-                dbg!("Unhandled/Unkown error deleting task: {:?}", err);
-                Response::InternalServerError("There was an unkown error".to_string())
-            }
-        }
+        return Response::from(err).about(Resource::Task);
     }
     let task = deleted_task.unwrap();
 
     // If the task was deleted, return a 200 OK response with the task
     Response::Ok(Json(task))
-}
\ No newline at end of file
+}
+/// Get a task in the trash if it belongs to the user
+/// Only the owner can see, restore or permanently delete the tasks in their trash
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `user_id` - The id of the user making the request
+/// 
+/// # Returns
+/// * `Result<ToDoTask, Response<Json<ToDoTask>>>` - The task, or the response to return
+async fn get_own_trashed_task(task_id: &str, user_id: &str) -> Result<ToDoTask, Response<Json<ToDoTask>>> {
+    match get_trashed_task_by_id(task_id).await {
+        Ok(task) if task.owner.as_ref().is_some_and(|o| o.id.to_raw() == user_id) => Ok(task),
+        Ok(_) => Err(Response::Forbidden(Problem::new("forbidden", "You do not have permissions"))),
+        Err(err) => Err(Response::from(err).about(Resource::TrashedTask)),
+    }
+}
+
+#[get("/trash")]
+/// Get the tasks in the trash of the authenticated user
+/// Subtasks deleted with their parent are not listed, they are restored and deleted with it
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<Vec<ToDoTask>>>` - The tasks, most recently deleted first
+pub async fn get_trash_handler(user: AuthenticatedUser) -> Response<Json<Vec<ToDoTask>>> {
+    match get_trash(&user.id).await {
+        Ok(tasks) => Response::Ok(Json(tasks)),
+        Err(err) => Response::from(err),
+    }
+}
+
+#[post("/tasks/<task_id>/restore")]
+/// Restore a task from the trash, with the subtasks which were deleted with it
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task to restore.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<ToDoTask>>` - The restored task
+pub async fn restore_task_handler(task_id: &str, user: AuthenticatedUser) -> Response<Json<ToDoTask>> {
+    let user_id = user.id;
+
+    if let Err(response) = get_own_trashed_task(task_id, &user_id).await {
+        return response;
+    }
+
+    match restore_task_by_id(task_id, &user_id).await {
+        Ok(task) => Response::Ok(Json(task)),
+        Err(err) => Response::from(err).about(Resource::TrashedTask),
+    }
+}
+
+#[delete("/trash/<task_id>")]
+/// Permanently delete a task in the trash, with all of its subtasks, comments and history
+/// 
+/// # Arguments
+/// * `task_id` - The ID of the task to delete.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<ToDoTask>>` - The deleted task
+pub async fn purge_task_handler(task_id: &str, user: AuthenticatedUser) -> Response<Json<ToDoTask>> {
+    let user_id = user.id;
+
+    if let Err(response) = get_own_trashed_task(task_id, &user_id).await {
+        return response;
+    }
+
+    match purge_task_by_id(task_id, &user_id).await {
+        Ok(task) => Response::Ok(Json(task)),
+        Err(err) => Response::from(err).about(Resource::TrashedTask),
+    }
+}
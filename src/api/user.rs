@@ -1,9 +1,20 @@
-use chrono::Duration;
-use rocket::{post, serde::json::Json};
+use rocket::{delete, get, patch, post, serde::json::Json};
 
-use crate::{database::users::{compare_email_password, compare_username_password, create_user}, model::users::User};
+use crate::{database::{password::verify_password, users::{check_availability, compare_email_password, compare_username_password, create_user, delete_user, edit_existing_user, get_user_by_email, get_user_by_username}}, model::{tokens::TokenPair, users::{LogInRequest, SignUpRequest, UpdateUserRequest, UserAvailability, UserProfile}}};
 
-use super::{auth::generate_token, Response};
+use super::{auth::{generate_token_pair, AuthenticatedUser}, conditional::{Preconditions, Tagged}, problem::{Problem, RequestId}, rate_limit::{account_key, check_account, record_failure, record_success, Availability, LogIn, RateLimit, SignUp}, validation, Resource, Response};
+
+/// The problem for a username or email which another user already has
+/// 
+/// # Arguments
+/// * `field` - The field which is taken, `username` or `email`
+/// 
+/// # Returns
+/// * `Problem` - The problem, with the code `user_exists` and the field in `errors`
+fn taken_problem(field: &str) -> Problem {
+    let message = format!("This {} is already taken", field);
+    Problem { code: "user_exists".to_string(), ..Problem::field(field, "taken", message) }
+}
 
 #[post("/users/sign-up", data = "<input_task>")]
 /// Create a new user
@@ -11,35 +22,25 @@ use super::{auth::generate_token, Response};
 /// 
 /// # Arguments
 /// * `input_task` - A JSON payload containing the user's details, including username, email, and password.
+/// * `_limit` - Limits how often each IP can sign up, 429 Too Many Requests is returned once it has signed up too often.
 /// 
 /// # Returns
-/// * `Response<String>` - A response indicating the result of the user creation process. If successful, it returns a JWT token for the user.
+/// * `Response<Json<TokenPair>>` - A response indicating the result of the user creation process. If successful, it returns an access token and refresh token for the user, 409 Conflict if the username or email is taken.
 pub async fn create_user_handler(
-    input_task: Json<User>
-) -> Response<String> {
+    input_task: Json<SignUpRequest>,
+    _limit: RateLimit<SignUp>,
+) -> Response<Json<TokenPair>> {
     let input_task = input_task.into_inner(); // Deserialise the input from JSON
 
-    // Option<String> -> Option<&str>
-    let username = input_task.username.as_deref();
-    let password = input_task.password.as_deref();
-    let email = input_task.email.as_deref();
-
-    // Check if any of the required fields are empty
-    if email.is_none() {
-        return Response::BadRequest("Email is required".to_string());
+    // Check the email, username and password are all there and valid
+    if let Err(errors) = validation::sign_up(&input_task) {
+        return Response::BadRequest(Problem::fields(errors));
     }
-    let email = email.unwrap();
-    // Here I should check if the email is valid
-    if username.is_none() {
-        return Response::BadRequest("Username is required".to_string());
-    }
-    let username = username.unwrap();
-    // I can add username requirements here if I want to
-    if password.is_none() {
-        return Response::BadRequest("Password is required".to_string());
-    }
-    let password = password.unwrap();
-    // I can add password requirements here if I want to
+
+    // Option<String> -> &str, these can't be None after validation
+    let username = input_task.username.as_deref().unwrap_or_default();
+    let password = input_task.password.as_deref().unwrap_or_default();
+    let email = input_task.email.as_deref().unwrap_or_default();
 
     // Create the task 
     let created_user = create_user(username, email, password).await;
@@ -48,38 +49,63 @@ pub async fn create_user_handler(
     if created_user.is_err() {
         let err = created_user.unwrap_err();
         return match err {
-            crate::database::DBCreateError::AlreadyExists(_) => Response::BadRequest("This user already exists".to_string()),
-            crate::database::DBCreateError::BadData(_) => Response::BadRequest("The data provided is invalid".to_string()),
-            crate::database::DBCreateError::Other(_) => {
-                dbg!("Unhandled/Unkown error creating user: {:?}", err);
-                Response::InternalServerError("There was an unkown error".to_string())
-            }
+            crate::database::DBCreateError::AlreadyExists(field) => Response::Conflict(taken_problem(&field)),
+            err => Response::from(err),
         }
     }
     let user = created_user.unwrap();
-    let id = user.id.unwrap().id.to_string();
+    let id = user.id.as_ref().unwrap().id.to_string();
 
-    // Generate a JWT for the user
-    let duration = Duration::days(7); // The token will be valid for 7 days
-    let jwt = generate_token(&id, duration).await;
+    // Generate the tokens for the user
+    let tokens = match generate_token_pair(&id, None).await {
+        Ok(t) => t,
+        Err(err) => return Response::from(err),
+    };
 
     // Return the response
-    Response::Created(jwt)
+    Response::Created(Json(tokens))
+}
+
+#[get("/users/availability?<username>&<email>")]
+/// Check if a username and email are free
+/// This lets a sign up form tell the user before they submit it, sign up still answers 409 Conflict if someone takes it in between.
+/// 
+/// # Arguments
+/// * `username` - The username to check.
+/// * `email` - The email to check.
+/// * `_limit` - Limits how often each IP can check, so the route can't be used to list every user.
+/// 
+/// # Returns
+/// * `Response<Json<UserAvailability>>` - A response indicating the result of the check. If successful, it returns whether each of the given fields is free in JSON format.
+pub async fn get_availability_handler(username: Option<&str>, email: Option<&str>, _limit: RateLimit<Availability>) -> Response<Json<UserAvailability>> {
+    if username.is_none() && email.is_none() {
+        return Response::BadRequest(Problem::field("username", "required", "Username or email is required"));
+    }
+
+    match check_availability(username, email).await {
+        Ok(availability) => Response::Ok(Json(availability)),
+        Err(err) => Response::from(err),
+    }
 }
 
 #[post("/users/log-in", data = "<input_user>")]
 /// Sign in a user
 /// This function handles the sign-in process for a user by accepting a JSON payload containing the user's credentials.
+/// Each IP and each account can only try so often, and an account has to wait longer after each failed log in until it is locked for a while.
 /// 
 /// # Arguments
 /// * `input_user` - A JSON payload containing the user's credentials, including username or email and password.
+/// * `_limit` - Limits how often each IP can log in, 429 Too Many Requests is returned once it has tried too often.
+/// * `request_id` - The id of the request, for logging errors from the rate limit store.
 /// 
 /// # Returns
-/// * `Response<String>` - A response indicating the result of the sign-in process. If successful, it returns a JWT token for the user.
+/// * `Response<Json<TokenPair>>` - A response indicating the result of the sign-in process. If successful, it returns an access token and refresh token for the user, 429 Too Many Requests with `Retry-After` if the account has to wait.
 pub async fn sign_in_user_handler(
-    input_user: Json<User>
-) -> Response<String> {
-    let user: User;
+    input_user: Json<LogInRequest>,
+    _limit: RateLimit<LogIn>,
+    request_id: RequestId,
+) -> Response<Json<TokenPair>> {
+    let user: UserProfile;
     let input_user = input_user.into_inner(); // Deserialise the input from JSON
 
     // Option<String> -> Option<&str>
@@ -89,20 +115,36 @@ pub async fn sign_in_user_handler(
 
     // Check there is a password
     if password.is_none() {
-        return Response::BadRequest("Password is required".to_string());
+        return Response::BadRequest(Problem::field("password", "required", "Password is required"));
     }
     
+    // Check the account can be tried before checking the password
+    // The failures are counted for the user, whether they log in with their username or email
+    let found = match (username, email) {
+        (Some(u), _) => get_user_by_username(u).await,
+        (None, Some(e)) => get_user_by_email(e).await,
+        (None, None) => return Response::BadRequest(Problem::field("username", "required", "Username or email is required")),
+    };
+    let account = match found {
+        Ok(found) => account_key(found.id.map(|id| id.id.to_raw()).as_deref()),
+        Err(crate::database::DBReadError::NotFound(_)) => account_key(None),
+        Err(err) => return Response::from(err),
+    };
+    if let Err(limited) = check_account(&account, &request_id).await {
+        return Response::TooManyRequests(Problem::from(limited));
+    }
+
     // Check there is a username OR password and call the correct function
     if username.is_some() {
         let compare_result = compare_username_password(username.unwrap(), password.unwrap()).await;
         if compare_result.is_err() {
             let err = compare_result.unwrap_err();
             return match err {
-                crate::database::DBReadError::NotFound(_) => Response::BadRequest("Incorrect Username/Password".to_string()),
-                crate::database::DBReadError::Other(_) => {
-                    dbg!("Unhandled/Unkown error logging in user: {:?}", err);
-                    Response::InternalServerError("There was an unkown error".to_string())
-                }
+                crate::database::DBReadError::NotFound(_) => {
+                    record_failure(&account, &request_id).await;
+                    Response::BadRequest(Problem::new("invalid_credentials", "Incorrect Username/Password"))
+                },
+                err => Response::from(err),
             }
         }
         user = compare_result.unwrap();
@@ -111,23 +153,149 @@ pub async fn sign_in_user_handler(
         if compare_result.is_err() {
             let err = compare_result.unwrap_err();
             return match err {
-                crate::database::DBReadError::NotFound(_) => Response::BadRequest("Incorrect Email/Password".to_string()),
-                crate::database::DBReadError::Other(_) => {
-                    dbg!("Unhandled/Unkown error logging in user: {:?}", err);
-                    Response::InternalServerError("There was an unkown error".to_string())
-                }
+                crate::database::DBReadError::NotFound(_) => {
+                    record_failure(&account, &request_id).await;
+                    Response::BadRequest(Problem::new("invalid_credentials", "Incorrect Email/Password"))
+                },
+                err => Response::from(err),
             }
         }
         user = compare_result.unwrap();
     } else {
-        return Response::BadRequest("Username or email is required".to_string());
+        return Response::BadRequest(Problem::field("username", "required", "Username or email is required"));
     }
 
-    // Generate a JWT for the user
+    // The account is no longer under attack, so forget its failed log ins
+    record_success(&account, &request_id).await;
+
+    // Generate the tokens for the user, each log in starts a new refresh token family
     let id = user.id.unwrap().id.to_string();
-    let duration = Duration::days(7); // The token will be valid for 7 days
-    let jwt = generate_token(&id, duration).await;
+    let tokens = match generate_token_pair(&id, None).await {
+        Ok(t) => t,
+        Err(err) => return Response::from(err),
+    };
 
     // Return the response
-    Response::Ok(jwt)
-}
\ No newline at end of file
+    Response::Ok(Json(tokens))
+}
+
+#[get("/users/me")]
+/// Get the authenticated user
+/// This function handles the retrieval of the profile of the user making the request.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// * `conditions` - The `If-None-Match` header, if the user still has the tag 304 Not Modified is returned instead.
+/// 
+/// # Returns
+/// * `Response<Tagged<Json<UserProfile>>>` - A response indicating the result of the retrieval. If successful, it returns the user's profile in JSON format with its `ETag`.
+pub async fn get_me_handler(user: AuthenticatedUser, conditions: Preconditions) -> Response<Tagged<Json<UserProfile>>> {
+    match user.user().await {
+        Ok(user) if conditions.not_modified(user.version) => Response::NotModified(Tagged::new((), user.version)),
+        Ok(user) => {
+            let version = user.version;
+            Response::Ok(Tagged::new(Json(UserProfile::from(user)), version))
+        },
+        Err(err) => Response::from(err).about(Resource::User),
+    }
+}
+
+#[patch("/users/me", data = "<update_user>")]
+/// Update the authenticated user
+/// This function handles the update of the user making the request by accepting a JSON payload containing the new details.
+/// Changing the password requires the current password, and revokes every existing token so the user has to log in again.
+/// The current password is limited the same way as logging in, so a stolen token can't be used to guess it.
+/// 
+/// # Arguments
+/// * `update_user` - A JSON payload containing the new username, email and/or password, and the current password if changing the password.
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// * `conditions` - The `If-Match` header, if the user no longer has the tag 412 Precondition Failed is returned and nothing is changed.
+/// * `_limit` - Limits how often each IP can log in or check a password, 429 Too Many Requests is returned once it has tried too often.
+/// * `request_id` - The id of the request, for logging errors from the rate limit store.
+/// 
+/// # Returns
+/// * `Response<Tagged<Json<UserProfile>>>` - A response indicating the result of the update. If successful, it returns the updated profile in JSON format with its new `ETag`, 409 Conflict if the new username or email is taken, 429 Too Many Requests with `Retry-After` if the account has to wait.
+pub async fn update_me_handler(
+    update_user: Json<UpdateUserRequest>,
+    user: AuthenticatedUser,
+    conditions: Preconditions,
+    _limit: RateLimit<LogIn>,
+    request_id: RequestId,
+) -> Response<Tagged<Json<UserProfile>>> {
+    let update_user = update_user.into_inner(); // Deserialise the input from JSON
+
+    // Check the fields which are being changed
+    if let Err(errors) = validation::update_user(&update_user) {
+        return Response::BadRequest(Problem::fields(errors));
+    }
+
+    // Option<String> -> Option<&str>
+    let username = update_user.username.as_deref();
+    let email = update_user.email.as_deref();
+    let password = update_user.password.as_deref();
+
+    // Check the current password before changing it
+    if password.is_some() {
+        let current_password = match update_user.current_password.as_deref() {
+            Some(p) => p,
+            None => return Response::BadRequest(Problem::field("current_password", "required", "Current password is required to change the password")),
+        };
+
+        let existing = match user.user().await {
+            Ok(u) => u,
+            Err(err) => return Response::from(err).about(Resource::User),
+        };
+
+        // Wrong passwords count towards the same delay and lockout as logging in
+        let account = account_key(Some(&user.id));
+        if let Err(limited) = check_account(&account, &request_id).await {
+            return Response::TooManyRequests(Problem::from(limited));
+        }
+        if !verify_password(current_password, existing.password.as_deref().unwrap_or_default()) {
+            record_failure(&account, &request_id).await;
+            return Response::Forbidden(Problem::new("incorrect_password", "Current password is incorrect"));
+        }
+        record_success(&account, &request_id).await;
+    }
+
+    // Get the profile as it is, to check the version against
+    let before = user.user().await.ok().map(UserProfile::from);
+
+    // Only update the version the client has, if it sent one
+    let version = before.as_ref().and_then(|b| b.version);
+    if !conditions.if_match(version) {
+        return Response::PreconditionFailed(Problem::new("version_mismatch", "The user has been changed since it was read"));
+    }
+    let expected_version = conditions.if_match.as_ref().and(version);
+
+    // Update the user in the DB
+    match edit_existing_user(&user.id, username, email, password, expected_version).await {
+        Ok(updated) => {
+            let updated = UserProfile::from(updated);
+            let version = updated.version;
+            Response::Ok(Tagged::new(Json(updated), version))
+        },
+        Err(err) => match err {
+            // Someone else changed the user between reading and updating it
+            crate::database::DBEditError::NotFound(_) if expected_version.is_some() => Response::PreconditionFailed(Problem::new("version_mismatch", "The user has been changed since it was read")),
+            crate::database::DBEditError::AlreadyExists(field) => Response::Conflict(taken_problem(&field)),
+            err => Response::from(err).about(Resource::User),
+        }
+    }
+}
+
+#[delete("/users/me")]
+/// Delete the authenticated user
+/// This function handles the deletion of the user making the request, along with all of their tasks.
+/// 
+/// # Arguments
+/// * `user` - The authenticated user, from the bearer token in the request `Authorization` header.
+/// 
+/// # Returns
+/// * `Response<Json<UserProfile>>` - A response indicating the result of the deletion. If successful, it returns the deleted profile in JSON format.
+pub async fn delete_me_handler(user: AuthenticatedUser) -> Response<Json<UserProfile>> {
+    match delete_user(&user.id).await {
+        Ok(deleted) => Response::Ok(Json(UserProfile::from(deleted))),
+        Err(err) => Response::from(err).about(Resource::User),
+    }
+}
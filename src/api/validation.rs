@@ -0,0 +1,363 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use chrono::DateTime;
+use rocket::figment::providers::Env;
+use serde::Deserialize;
+
+use crate::model::{todotask::TaskRequest, users::{SignUpRequest, UpdateUserRequest}};
+
+use super::problem::FieldError;
+
+/// The longest a password can be, hashing is slow so this stops very long passwords being used to tie up the server
+pub const PASSWORD_MAX_LENGTH: usize = 128;
+
+/// The rules for a username
+pub const USERNAME: &[Rule] = &[Rule::Length(3, 32), Rule::Charset(is_username_char, "letters, numbers, '_', '-' and '.'")];
+/// The rules for an email
+pub const EMAIL: &[Rule] = &[Rule::Length(3, 254), Rule::Email];
+/// The rules for a new password, the policy is set in the `validation` section of `Rocket.toml`
+pub const PASSWORD: &[Rule] = &[Rule::Password];
+/// The rules for the title of a task
+pub const TITLE: &[Rule] = &[Rule::NotBlank, Rule::Length(1, 200)];
+/// The rules for the description of a task, an empty description clears it
+pub const DESCRIPTION: &[Rule] = &[Rule::Length(0, 10_000)];
+/// The rules for a date and time
+pub const DATETIME: &[Rule] = &[Rule::DateTime];
+
+/// The common passwords which are rejected, bundled so they can be checked without a network request
+static COMMON_PASSWORDS: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    include_str!("validation/common_passwords.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+});
+
+/// The password policy, read once when it is first used
+static CONFIG: LazyLock<ValidationConfig> = LazyLock::new(ValidationConfig::load);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+/// The configuration of the password policy
+/// This is read from the `validation` section of `Rocket.toml`
+/// Each field can be overridden with a `ROCKET_VALIDATION_<FIELD>` environment variable, e.g. `ROCKET_VALIDATION_PASSWORD_MIN_LENGTH=12`
+///
+/// # Fields
+/// * `password_min_length` - The fewest characters a password can have
+/// * `password_min_classes` - How many of lowercase letters, uppercase letters, numbers and symbols a password must use
+/// * `reject_common_passwords` - Reject passwords in the bundled list of common passwords
+pub struct ValidationConfig {
+    pub password_min_length: usize,
+    pub password_min_classes: usize,
+    pub reject_common_passwords: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            password_min_length: 8,
+            password_min_classes: 2,
+            reject_common_passwords: true,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// Read the validation configuration
+    /// Its okay for this to panic because the backend shouldn't run with a password policy it can't read
+    ///
+    /// # Returns
+    /// `ValidationConfig` - The configuration, using the defaults for anything not set
+    pub fn load() -> ValidationConfig {
+        let figment = rocket::Config::figment()
+            .merge(Env::prefixed("ROCKET_VALIDATION_").map(|key| format!("validation.{}", key).into()).global());
+
+        // If there is no validation section at all use the defaults
+        if !figment.contains("validation") {
+            return ValidationConfig::default();
+        }
+
+        figment
+            .extract_inner("validation")
+            .expect("Invalid validation configuration")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A rule the value of a field has to follow
+///
+/// # Variants
+/// * `NotBlank` - The value can't be empty or only whitespace
+/// * `Length` - The number of characters has to be between the two numbers, inclusive
+/// * `Charset` - Every character has to be allowed by the function, with a description of the allowed characters
+/// * `Email` - The value has to look like an email address
+/// * `DateTime` - The value has to be an RFC 3339 date and time
+/// * `Password` - The value has to follow the password policy in `ValidationConfig`
+pub enum Rule {
+    NotBlank,
+    Length(usize, usize),
+    Charset(fn(char) -> bool, &'static str),
+    Email,
+    DateTime,
+    Password,
+}
+
+impl Rule {
+    /// Check a value follows the rule
+    ///
+    /// # Arguments
+    /// * `label` - The name of the field for the message, e.g. `Title`
+    /// * `value` - The value of the field
+    ///
+    /// # Returns
+    /// * `Result<(), (&'static str, String)>` - The code and message if the value breaks the rule
+    fn check(&self, label: &str, value: &str) -> Result<(), (&'static str, String)> {
+        match self {
+            Rule::NotBlank if value.trim().is_empty() => Err(("blank", format!("{} can't be empty", label))),
+            Rule::Length(min, _) if value.chars().count() < *min => Err(("too_short", format!("{} must be at least {} characters", label, min))),
+            Rule::Length(_, max) if value.chars().count() > *max => Err(("too_long", format!("{} must be at most {} characters", label, max))),
+            Rule::Charset(allowed, description) if !value.chars().all(allowed) => Err(("invalid_characters", format!("{} can only contain {}", label, description))),
+            Rule::Email if !is_email(value) => Err(("invalid_email", format!("{} is not a valid email address", label))),
+            Rule::DateTime if DateTime::parse_from_rfc3339(value).is_err() => Err(("invalid_datetime", format!("{} must be an RFC 3339 date and time", label))),
+            Rule::Password => check_password(value, &CONFIG),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// Checks the fields of a request against their rules, collecting a problem for each field which breaks one
+/// Only the first rule a field breaks is reported
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Create a validator with no problems
+    ///
+    /// # Returns
+    /// * `Validator` - The validator
+    pub fn new() -> Self {
+        Validator::default()
+    }
+
+    /// Check a field which has to be in the request
+    ///
+    /// # Arguments
+    /// * `field` - The name of the field
+    /// * `value` - The value of the field, None if it wasn't sent
+    /// * `rules` - The rules the value has to follow
+    ///
+    /// # Returns
+    /// * `&mut Validator` - The validator, to check more fields
+    pub fn require(&mut self, field: &str, value: Option<&str>, rules: &[Rule]) -> &mut Self {
+        match value {
+            Some(value) => self.check(field, Some(value), rules),
+            None => self.error(field, "required", format!("{} is required", label(field))),
+        }
+    }
+
+    /// Check a field if it is in the request
+    ///
+    /// # Arguments
+    /// * `field` - The name of the field
+    /// * `value` - The value of the field, None if it wasn't sent
+    /// * `rules` - The rules the value has to follow
+    ///
+    /// # Returns
+    /// * `&mut Validator` - The validator, to check more fields
+    pub fn check(&mut self, field: &str, value: Option<&str>, rules: &[Rule]) -> &mut Self {
+        let label = label(field);
+        if let Some(value) = value
+            && let Some((code, message)) = rules.iter().find_map(|rule| rule.check(&label, value).err()) {
+            self.error(field, code, message);
+        }
+        self
+    }
+
+    /// Add a problem which isn't about the rules of one field, e.g. two fields which don't agree
+    ///
+    /// # Arguments
+    /// * `field` - The name of the field
+    /// * `code` - What is wrong with the field
+    /// * `message` - A human readable explanation
+    ///
+    /// # Returns
+    /// * `&mut Validator` - The validator, to check more fields
+    pub fn error(&mut self, field: &str, code: &str, message: impl Into<String>) -> &mut Self {
+        self.errors.push(FieldError { field: field.to_string(), code: code.to_string(), message: message.into() });
+        self
+    }
+
+    /// Finish checking the fields
+    ///
+    /// # Returns
+    /// * `Result<(), Vec<FieldError>>` - Every field which broke a rule, for `Problem::fields`
+    pub fn finish(&mut self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(std::mem::take(&mut self.errors))
+    }
+}
+
+/// Check a request to sign up a new user
+///
+/// # Arguments
+/// * `request` - The request
+///
+/// # Returns
+/// * `Result<(), Vec<FieldError>>` - The problems with the fields, if there are any
+pub fn sign_up(request: &SignUpRequest) -> Result<(), Vec<FieldError>> {
+    Validator::new()
+        .require("email", request.email.as_deref(), EMAIL)
+        .require("username", request.username.as_deref(), USERNAME)
+        .require("password", request.password.as_deref(), PASSWORD)
+        .finish()
+}
+
+/// Check a request to update a user, only the fields which are being changed are checked
+///
+/// # Arguments
+/// * `request` - The request
+///
+/// # Returns
+/// * `Result<(), Vec<FieldError>>` - The problems with the fields, if there are any
+pub fn update_user(request: &UpdateUserRequest) -> Result<(), Vec<FieldError>> {
+    Validator::new()
+        .check("email", request.email.as_deref(), EMAIL)
+        .check("username", request.username.as_deref(), USERNAME)
+        .check("password", request.password.as_deref(), PASSWORD)
+        .finish()
+}
+
+/// Check a request to create or update a task
+///
+/// # Arguments
+/// * `request` - The request
+/// * `creating` - True if the task is being created, so the title is required
+/// * `created_at` - When the task was created, for a task which is being updated
+///
+/// # Returns
+/// * `Result<(), Vec<FieldError>>` - The problems with the fields, if there are any
+pub fn task(request: &TaskRequest, creating: bool, created_at: Option<&str>) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator::new();
+    if creating {
+        validator.require("title", request.title.as_deref(), TITLE);
+    } else {
+        validator.check("title", request.title.as_deref(), TITLE);
+    }
+    validator
+        .check("description", request.description.as_deref(), DESCRIPTION)
+        .check("completed_at", request.completed_at.as_deref(), DATETIME)
+        .check("created_at", request.created_at.as_deref(), DATETIME)
+        .check("due_at", request.due_at.as_deref(), DATETIME)
+        .check("remind_at", request.remind_at.as_deref(), DATETIME);
+
+    // A task can't be completed before it was created
+    let created_at = request.created_at.as_deref().or(created_at).and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    let completed_at = request.completed_at.as_deref().and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    if let (Some(created_at), Some(completed_at)) = (created_at, completed_at)
+        && completed_at < created_at {
+        validator.error("completed_at", "before_created_at", "Completed at can't be before the task was created");
+    }
+
+    validator.finish()
+}
+
+/// Check a password follows the password policy
+///
+/// # Arguments
+/// * `password` - The password
+/// * `config` - The password policy
+///
+/// # Returns
+/// * `Result<(), (&'static str, String)>` - The code and message if the password breaks the policy
+pub fn check_password(password: &str, config: &ValidationConfig) -> Result<(), (&'static str, String)> {
+    let length = password.chars().count();
+    if length < config.password_min_length {
+        return Err(("too_short", format!("Password must be at least {} characters", config.password_min_length)));
+    }
+    if length > PASSWORD_MAX_LENGTH {
+        return Err(("too_long", format!("Password must be at most {} characters", PASSWORD_MAX_LENGTH)));
+    }
+
+    // Count the kinds of character used
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    if classes.iter().filter(|used| **used).count() < config.password_min_classes {
+        return Err(("too_weak", format!("Password must use at least {} of lowercase letters, uppercase letters, numbers and symbols", config.password_min_classes)));
+    }
+
+    if config.reject_common_passwords && COMMON_PASSWORDS.contains(&password.to_lowercase()) {
+        return Err(("common_password", "Password is too common".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Check if a character can be in a username
+///
+/// # Arguments
+/// * `c` - The character
+///
+/// # Returns
+/// * `bool` - True if the character is allowed
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+/// Check if a value looks like an email address
+/// This is a sanity check rather than the full RFC 5322 grammar, the only real check is sending an email to it
+///
+/// # Arguments
+/// * `email` - The value
+///
+/// # Returns
+/// * `bool` - True if it has a local part, one `@` and a domain with at least two labels
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c));
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels.iter().all(|l| {
+            !l.is_empty()
+                && l.len() <= 63
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()));
+
+    local_ok && domain_ok
+}
+
+/// Turn the name of a field into a label for messages, e.g. `completed_at` into `Completed at`
+///
+/// # Arguments
+/// * `field` - The name of the field
+///
+/// # Returns
+/// * `String` - The label
+fn label(field: &str) -> String {
+    let field = field.replace('_', " ");
+    let mut chars = field.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => field,
+    }
+}
@@ -0,0 +1,95 @@
+use surrealdb::sql::{Value, Thing};
+
+use crate::model::activity::{Activity, ActivityPage, ActivityQuery};
+use super::{DBReadError, DB};
+
+/// The number of activities in a page when no limit is given
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// The largest number of activities a page can have
+const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Clone, Copy)]
+/// Something which has an activity log
+///
+/// # Variants
+/// * `Task` - A task, by id
+/// * `User` - A user, by id
+pub enum ActivityTarget<'a> {
+    Task(&'a str),
+    User(&'a str),
+}
+
+impl ActivityTarget<'_> {
+    /// The record id of the target in the database
+    fn thing(&self) -> Thing {
+        match self {
+            ActivityTarget::Task(id) => Thing::from(("ToDoTask", *id)),
+            ActivityTarget::User(id) => Thing::from(("User", *id)),
+        }
+    }
+}
+
+/// Get a page of the activity log of a task or user, newest first
+/// The activity of a user is the changes they made and the changes to their account
+///
+/// # Arguments
+/// * `target` - The task or user
+/// * `query` - The cursor and limit, the cursor is the id of the last activity of the previous page
+///
+/// # Returns
+/// * `Result<ActivityPage, DBReadError>` - The page of activities or an error, `BadData` if the cursor is invalid
+pub async fn get_activity(
+    target: ActivityTarget<'_>,
+    query: &ActivityQuery,
+) -> Result<ActivityPage, DBReadError> {
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut sql = String::from(match target {
+        ActivityTarget::Task(_) => "SELECT * FROM Activity WHERE target = $target",
+        ActivityTarget::User(_) => "SELECT * FROM Activity WHERE (actor = $target OR target = $target)",
+    });
+
+    // Only return activities older than the cursor, the id breaks ties between activities at the same time
+    let cursor = match query.cursor.as_deref() {
+        Some(c) => {
+            let cursor = Thing::from(("Activity", c));
+            let mut response = DB.query("SELECT * FROM ONLY $cursor;")
+                .bind(("cursor", Value::Thing(cursor.clone())))
+                .await
+                .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+            let cursor_activity: Option<Activity> = response.take(0).map_err(|_| DBReadError::BadData("Invalid cursor".to_string()))?;
+            if cursor_activity.is_none() {
+                return Err(DBReadError::BadData("Invalid cursor".to_string()));
+            }
+            sql.push_str(" AND (created_at < $cursor.created_at OR (created_at = $cursor.created_at AND id < $cursor))");
+            Value::Thing(cursor)
+        },
+        None => Value::None,
+    };
+
+    // Get one extra activity to know if there is another page
+    sql.push_str(" ORDER BY created_at DESC, id DESC LIMIT $limit;");
+
+    let mut response = DB.query(sql)
+        .bind(("target", Value::Thing(target.thing())))
+        .bind(("cursor", cursor))
+        .bind(("limit", Value::from(limit as i64 + 1)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let mut activities: Vec<Activity> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let next_cursor = if activities.len() > limit as usize {
+        activities.truncate(limit as usize);
+        activities.last().and_then(|a| a.id.as_ref()).map(|id| id.id.to_raw())
+    } else {
+        None
+    };
+
+    Ok(ActivityPage { activities, next_cursor })
+}
@@ -0,0 +1,284 @@
+use surrealdb::sql::{Value, Thing};
+
+use crate::model::comment::{Comment, CommentEdit, CommentPage, CommentQuery};
+use super::{DBCreateError, DBEditError, DBReadError, DB};
+
+/// The longest a comment can be, in characters
+const MAX_COMMENT_LENGTH: usize = 10000;
+/// The number of comments in a page when no limit is given
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// The largest number of comments a page can have
+const MAX_PAGE_SIZE: u32 = 200;
+
+/// Check the body of a comment isn't empty or too long
+///
+/// # Arguments
+/// * `body` - The text of the comment
+///
+/// # Returns
+/// * `Result<&str, String>` - The trimmed body or why it isn't allowed
+fn check_body(body: &str) -> Result<&str, String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err("Body is required".to_string());
+    }
+    if body.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(format!("Body can't be longer than {} characters", MAX_COMMENT_LENGTH));
+    }
+
+    Ok(body)
+}
+
+/// Write a comment on a task
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `author_id` - The id of the user writing the comment
+/// * `body` - The text of the comment
+///
+/// # Returns
+/// * `Result<Comment, DBCreateError>` - The created comment or an error
+pub async fn create_comment(
+    task_id: &str,
+    author_id: &str,
+    body: &str,
+) -> Result<Comment, DBCreateError> {
+
+    let sql = "CREATE Comment SET task = $task, author = $author, body = $body;";
+
+    let body = check_body(body).map_err(DBCreateError::BadData)?;
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+    let author: Value = Thing::from(("User", author_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("task", task))
+        .bind(("author", author))
+        .bind(("body", Value::from(body)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Comment> = response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBCreateError::Other("Failed to create comment".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a comment from the database by id
+///
+/// # Arguments
+/// * `id` - The id of the comment to get
+///
+/// # Returns
+/// * `Result<Comment, DBReadError>` - The comment or an error
+pub async fn get_comment_by_id(
+    id: &str,
+) -> Result<Comment, DBReadError> {
+
+    let sql = "SELECT * FROM $id;";
+
+    let id: Value = Thing::from(("Comment", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Comment> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get comment".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a page of the comments on a task, oldest first
+/// The cursor is the id of the last comment of the previous page, the id breaks ties between comments written at the same time
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `query` - The cursor and limit
+///
+/// # Returns
+/// * `Result<CommentPage, DBReadError>` - The page of comments or an error, `BadData` if the cursor isn't a comment on the task
+pub async fn get_comments(
+    task_id: &str,
+    query: &CommentQuery,
+) -> Result<CommentPage, DBReadError> {
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let mut sql = String::from("SELECT * FROM Comment WHERE task = $task");
+
+    let cursor = match query.cursor.as_deref() {
+        Some(c) => {
+            // The comment the cursor points to must still be on this task
+            let comment = get_comment_by_id(c).await.map_err(|_| DBReadError::BadData("Invalid cursor".to_string()))?;
+            if comment.task.as_ref().is_none_or(|t| t.id.to_raw() != task_id) {
+                return Err(DBReadError::BadData("Invalid cursor".to_string()));
+            }
+            sql.push_str(" AND (created_at > $cursor.created_at OR (created_at = $cursor.created_at AND id > $cursor))");
+            Value::Thing(Thing::from(("Comment", c)))
+        },
+        None => Value::None,
+    };
+
+    // Get one extra comment to know if there is another page
+    sql.push_str(" ORDER BY created_at, id LIMIT $limit;");
+
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("task", task))
+        .bind(("cursor", cursor))
+        .bind(("limit", Value::from(limit as i64 + 1)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let mut comments: Vec<Comment> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let next_cursor = if comments.len() > limit as usize {
+        comments.truncate(limit as usize);
+        comments.last().and_then(|c| c.id.as_ref()).map(|id| id.id.to_raw())
+    } else {
+        None
+    };
+
+    Ok(CommentPage { comments, next_cursor })
+}
+
+/// Edit the body of a comment, the previous body is kept in its history
+///
+/// # Arguments
+/// * `id` - The id of the comment
+/// * `body` - The new text of the comment
+///
+/// # Returns
+/// * `Result<Comment, DBEditError>` - The edited comment or an error
+pub async fn edit_comment(
+    id: &str,
+    body: &str,
+) -> Result<Comment, DBEditError> {
+
+    // Nothing is added to the history if the body doesn't change
+    let sql = "
+    BEGIN TRANSACTION;
+    LET $before = (SELECT * FROM ONLY $id);
+    IF $before.body != $body {
+        CREATE CommentEdit SET comment = $id, body = $before.body;
+        UPDATE $id SET body = $body, edited_at = time::now();
+    };
+    SELECT * FROM ONLY $id;
+    COMMIT TRANSACTION;
+    ";
+
+    let body = check_body(body).map_err(DBEditError::BadData)?;
+
+    // Make sure the comment exists so the history is never made for a missing comment
+    get_comment_by_id(id).await.map_err(|e| match e {
+        DBReadError::NotFound(e) => DBEditError::NotFound(e),
+        DBReadError::BadData(e) | DBReadError::Other(e) => DBEditError::Other(e),
+    })?;
+
+    let id: Value = Thing::from(("Comment", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("body", Value::from(body)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Comment> = response
+        .take(2)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to edit comment".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the previous versions of a comment
+///
+/// # Arguments
+/// * `id` - The id of the comment
+///
+/// # Returns
+/// * `Result<Vec<CommentEdit>, DBReadError>` - The previous versions, oldest first, or an error
+pub async fn get_comment_history(
+    id: &str,
+) -> Result<Vec<CommentEdit>, DBReadError> {
+
+    let sql = "SELECT * FROM CommentEdit WHERE comment = $id ORDER BY edited_at, id;";
+
+    let id: Value = Thing::from(("Comment", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<CommentEdit> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Delete a comment and its history from the database by id
+///
+/// # Arguments
+/// * `id` - The id of the comment to delete
+///
+/// # Returns
+/// * `Result<Comment, DBReadError>` - The deleted comment or an error
+pub async fn delete_comment_by_id(
+    id: &str,
+) -> Result<Comment, DBReadError> {
+
+    let sql = "
+    BEGIN TRANSACTION;
+    DELETE CommentEdit WHERE comment = $id;
+    DELETE ONLY $id RETURN BEFORE;
+    COMMIT TRANSACTION;
+    ";
+
+    let id: Value = Thing::from(("Comment", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Comment> = response
+        .take(1)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to delete comment".to_string())
+    })?;
+
+    Ok(result)
+}
@@ -0,0 +1,141 @@
+use std::sync::LazyLock;
+
+use rocket::figment::{providers::Env, Figment};
+use serde::Deserialize;
+
+/// The database configuration, read once when it is first used
+static CONFIG: LazyLock<DatabaseConfig> = LazyLock::new(DatabaseConfig::load);
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The level the backend signs in to the database at
+///
+/// # Variants
+/// * `Root` - Sign in as a root user, with access to every namespace and database
+/// * `Namespace` - Sign in as a user defined on the configured namespace
+/// * `Database` - Sign in as a user defined on the configured database
+/// * `None` - Do not sign in, used for the embedded `mem://` and `rocksdb://` engines
+pub enum AuthLevel {
+    Root,
+    Namespace,
+    Database,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// What to do with pending migrations when the backend starts
+///
+/// # Variants
+/// * `Apply` - Apply any pending migrations
+/// * `DryRun` - Only report the pending migrations, without applying them
+/// * `Off` - Do not check the migrations at all
+pub enum MigrationMode {
+    Apply,
+    DryRun,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// What happens to the subtasks of a task when it is deleted
+///
+/// # Variants
+/// * `Cascade` - Delete every subtask, and their subtasks, with the task
+/// * `Promote` - Move the direct subtasks up to the parent of the deleted task
+/// * `Restrict` - Refuse to delete a task which has subtasks
+pub enum SubtaskDeletePolicy {
+    Cascade,
+    Promote,
+    Restrict,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+/// The configuration used to connect to the database
+/// This is read from the `database` section of `Rocket.toml`
+/// Each field can be overridden with a `ROCKET_DATABASE_<FIELD>` environment variable, e.g. `ROCKET_DATABASE_ENDPOINT=mem://`
+///
+/// # Fields
+/// * `endpoint` - The address of the database, e.g. `ws://127.0.0.1:8000`, `mem://` or `rocksdb://path/to/data`
+/// * `namespace` - The namespace to use
+/// * `database` - The database to use
+/// * `auth` - The level to sign in at
+/// * `username` - The username to sign in with, not needed when `auth` is `none`
+/// * `password` - The password to sign in with, not needed when `auth` is `none`
+/// * `migrations` - What to do with pending migrations on start up
+/// * `subtasks_on_delete` - What happens to the subtasks of a task when it is deleted
+/// * `trash_retention_days` - How many days deleted tasks stay in the trash before they are purged, 0 keeps them forever
+/// * `trash_purge_interval_minutes` - How often the trash is checked for tasks to purge
+pub struct DatabaseConfig {
+    pub endpoint: String,
+    pub namespace: String,
+    pub database: String,
+    pub auth: AuthLevel,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub migrations: MigrationMode,
+    pub subtasks_on_delete: SubtaskDeletePolicy,
+    pub trash_retention_days: u32,
+    pub trash_purge_interval_minutes: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            endpoint: "ws://127.0.0.1:8000".to_string(),
+            namespace: "Dev".to_string(),
+            database: "Dev".to_string(),
+            auth: AuthLevel::Root,
+            username: Some("root".to_string()),
+            password: Some("root".to_string()),
+            migrations: MigrationMode::Apply,
+            subtasks_on_delete: SubtaskDeletePolicy::Cascade,
+            trash_retention_days: 30,
+            trash_purge_interval_minutes: 60,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// The figment the database configuration is read from
+    /// This is Rocket's figment with the `ROCKET_DATABASE_` environment variables merged on top
+    /// Tests use the `test` profile unless `ROCKET_PROFILE` is set, so they run against an embedded database
+    ///
+    /// # Returns
+    /// `Figment` - The figment to extract the configuration from
+    pub fn figment() -> Figment {
+        let figment = rocket::Config::figment();
+        #[cfg(test)]
+        let figment = figment.select(rocket::figment::Profile::from_env_or("ROCKET_PROFILE", "test"));
+
+        figment
+            .merge(Env::prefixed("ROCKET_DATABASE_").map(|key| format!("database.{}", key).into()).global())
+    }
+
+    /// Read the database configuration
+    /// Its okay for this to panic because the backend cannot run without a valid database configuration
+    ///
+    /// # Returns
+    /// `DatabaseConfig` - The configuration, using the defaults for anything not set
+    pub fn load() -> DatabaseConfig {
+        let figment = Self::figment();
+
+        // If there is no database section at all use the defaults
+        if !figment.contains("database") {
+            return DatabaseConfig::default();
+        }
+
+        figment
+            .extract_inner("database")
+            .expect("Invalid database configuration")
+    }
+
+    /// Get the database configuration the backend is using
+    ///
+    /// # Returns
+    /// `&DatabaseConfig` - The configuration
+    pub fn get() -> &'static DatabaseConfig {
+        &CONFIG
+    }
+}
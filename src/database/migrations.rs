@@ -0,0 +1,323 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use surrealdb::sql::Value;
+
+use super::{config::MigrationMode, DBMigrationError, DB};
+
+#[derive(Debug, Clone)]
+/// A schema migration embedded in the binary
+///
+/// # Fields
+/// * `version` - The version of the migration, these must be unique and increasing
+/// * `name` - A short description of the migration
+/// * `sql` - The SurrealQL run to apply the migration
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration in the order they are applied
+/// New migrations should be added to the end of this list with the next version, existing ones must never be edited
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_user_and_todotask",
+        sql: include_str!("migrations/0001_create_user_and_todotask.surql"),
+    },
+    Migration {
+        version: 2,
+        name: "create_refreshtoken",
+        sql: include_str!("migrations/0002_create_refreshtoken.surql"),
+    },
+    Migration {
+        version: 3,
+        name: "token_revocation",
+        sql: include_str!("migrations/0003_token_revocation.surql"),
+    },
+    Migration {
+        version: 4,
+        name: "task_list_indexes",
+        sql: include_str!("migrations/0004_task_list_indexes.surql"),
+    },
+    Migration {
+        version: 5,
+        name: "task_due_dates",
+        sql: include_str!("migrations/0005_task_due_dates.surql"),
+    },
+    Migration {
+        version: 6,
+        name: "task_recurrence",
+        sql: include_str!("migrations/0006_task_recurrence.surql"),
+    },
+    Migration {
+        version: 7,
+        name: "subtasks",
+        sql: include_str!("migrations/0007_subtasks.surql"),
+    },
+    Migration {
+        version: 8,
+        name: "projects",
+        sql: include_str!("migrations/0008_projects.surql"),
+    },
+    Migration {
+        version: 9,
+        name: "tags",
+        sql: include_str!("migrations/0009_tags.surql"),
+    },
+    Migration {
+        version: 10,
+        name: "sharing",
+        sql: include_str!("migrations/0010_sharing.surql"),
+    },
+    Migration {
+        version: 11,
+        name: "assignment_notifications",
+        sql: include_str!("migrations/0011_assignment_notifications.surql"),
+    },
+    Migration {
+        version: 12,
+        name: "comments",
+        sql: include_str!("migrations/0012_comments.surql"),
+    },
+    Migration {
+        version: 13,
+        name: "activity",
+        sql: include_str!("migrations/0013_activity.surql"),
+    },
+    Migration {
+        version: 14,
+        name: "trash",
+        sql: include_str!("migrations/0014_trash.surql"),
+    },
+    Migration {
+        version: 15,
+        name: "sync",
+        sql: include_str!("migrations/0015_sync.surql"),
+    },
+    Migration {
+        version: 16,
+        name: "versions",
+        sql: include_str!("migrations/0016_versions.surql"),
+    },
+    Migration {
+        version: 17,
+        name: "rate_limits",
+        sql: include_str!("migrations/0017_rate_limits.surql"),
+    },
+    Migration {
+        version: 18,
+        name: "activity_events",
+        sql: include_str!("migrations/0018_activity_events.surql"),
+    },
+    Migration {
+        version: 19,
+        name: "activity_append_only",
+        sql: include_str!("migrations/0019_activity_append_only.surql"),
+    },
+    Migration {
+        version: 20,
+        name: "task_removals",
+        sql: include_str!("migrations/0020_task_removals.surql"),
+    },
+];
+
+impl Migration {
+    /// The SHA-256 checksum of the migration, used to detect migrations edited after being applied
+    /// Line endings are normalised first so the checksum is the same on every platform
+    ///
+    /// # Returns
+    /// `String` - The checksum as lowercase hex
+    pub fn checksum(&self) -> String {
+        let sql = self.sql.replace("\r\n", "\n");
+        Sha256::digest(sql.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Whether a migration has been applied to the database
+///
+/// # Variants
+/// * `Applied` - The migration has been applied
+/// * `Pending` - The migration has not been applied yet
+pub enum MigrationState {
+    Applied,
+    Pending,
+}
+
+#[derive(Debug, Clone)]
+/// The status of a single migration
+///
+/// # Fields
+/// * `version` - The version of the migration
+/// * `name` - The name of the migration
+/// * `state` - Whether the migration has been applied
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+/// A row of the `_migrations` bookkeeping table
+struct AppliedMigration {
+    version: i64,
+    checksum: String,
+}
+
+/// Create the `_migrations` bookkeeping table if it does not exist yet
+///
+/// # Returns
+/// `Result<(), DBMigrationError>` - Nothing or an error
+async fn create_bookkeeping() -> Result<(), DBMigrationError> {
+    let sql = "
+    DEFINE TABLE IF NOT EXISTS _migrations SCHEMAFULL;
+    DEFINE FIELD IF NOT EXISTS version ON TABLE _migrations TYPE int;
+    DEFINE FIELD IF NOT EXISTS name ON TABLE _migrations TYPE string;
+    DEFINE FIELD IF NOT EXISTS checksum ON TABLE _migrations TYPE string;
+    DEFINE FIELD IF NOT EXISTS applied_at ON TABLE _migrations TYPE datetime DEFAULT time::now();
+    DEFINE INDEX IF NOT EXISTS uniqueVersion ON TABLE _migrations COLUMNS version UNIQUE;
+    ";
+
+    let mut response = DB.query(sql)
+        .await
+        .map_err(|e| DBMigrationError::Other(e.to_string()))?;
+
+    let errors = response.take_errors();
+    if !errors.is_empty() {
+        return Err(DBMigrationError::Other(format!("Failed to create _migrations table: {:?}", errors)));
+    }
+
+    Ok(())
+}
+
+/// Get the status of every migration, checking the database against the migrations in the binary
+///
+/// # Returns
+/// `Result<Vec<MigrationStatus>, DBMigrationError>` - The status of each migration in order, or an error if the database is ahead of the binary or a migration has been edited
+pub async fn status() -> Result<Vec<MigrationStatus>, DBMigrationError> {
+    create_bookkeeping().await?;
+
+    let sql = "SELECT version, checksum FROM _migrations ORDER BY version;";
+
+    let mut response = DB.query(sql)
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let applied: Vec<AppliedMigration> = response
+        .take(0)
+        .map_err(|e| DBMigrationError::Other(e.to_string()))?;
+
+    // Every applied migration must be one this binary knows about, with the same contents
+    for row in &applied {
+        let migration = MIGRATIONS.iter().find(|m| m.version == row.version);
+        match migration {
+            None => {
+                return Err(DBMigrationError::DatabaseAhead(format!(
+                    "Migration {} has been applied but is not in this binary",
+                    row.version
+                )))
+            }
+            Some(m) if m.checksum() != row.checksum => {
+                return Err(DBMigrationError::ChecksumMismatch(format!(
+                    "Migration {} ({}) has changed since it was applied",
+                    m.version, m.name
+                )))
+            }
+            Some(_) => {}
+        }
+    }
+
+    let result = MIGRATIONS
+        .iter()
+        .map(|m| MigrationStatus {
+            version: m.version,
+            name: m.name,
+            state: if applied.iter().any(|row| row.version == m.version) {
+                MigrationState::Applied
+            } else {
+                MigrationState::Pending
+            },
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// Apply a single migration and record it in `_migrations` in one transaction
+///
+/// # Arguments
+/// * `migration` - The migration to apply
+///
+/// # Returns
+/// `Result<(), DBMigrationError>` - Nothing or an error
+async fn apply(migration: &Migration) -> Result<(), DBMigrationError> {
+    let sql = format!(
+        "BEGIN TRANSACTION;
+        {}
+        ;
+        CREATE _migrations SET version = $version, name = $name, checksum = $checksum;
+        COMMIT TRANSACTION;",
+        migration.sql
+    );
+
+    let mut response = DB.query(sql)
+        .bind(("version", Value::from(migration.version)))
+        .bind(("name", Value::from(migration.name)))
+        .bind(("checksum", Value::from(migration.checksum())))
+        .await
+        .map_err(|e| DBMigrationError::Other(e.to_string()))?;
+
+    // If any statement failed the whole transaction is cancelled
+    let errors = response.take_errors();
+    if !errors.is_empty() {
+        return Err(DBMigrationError::Other(format!(
+            "Failed to apply migration {} ({}): {:?}",
+            migration.version, migration.name, errors
+        )));
+    }
+
+    Ok(())
+}
+
+/// Run the migrations according to the mode
+///
+/// # Arguments
+/// * `mode` - Whether to apply pending migrations, only report them, or do nothing
+///
+/// # Returns
+/// `Result<Vec<MigrationStatus>, DBMigrationError>` - The status of each migration after running, or an error
+pub async fn migrate(mode: MigrationMode) -> Result<Vec<MigrationStatus>, DBMigrationError> {
+    if mode == MigrationMode::Off {
+        return Ok(Vec::new());
+    }
+
+    let mut statuses = status().await?;
+
+    for migration_status in statuses.iter_mut() {
+        if migration_status.state == MigrationState::Applied {
+            continue;
+        }
+
+        match mode {
+            MigrationMode::DryRun => {
+                println!("Pending migration {} ({})", migration_status.version, migration_status.name);
+            }
+            _ => {
+                // This will always be found as the statuses are made from MIGRATIONS
+                let migration = MIGRATIONS
+                    .iter()
+                    .find(|m| m.version == migration_status.version)
+                    .unwrap();
+                apply(migration).await?;
+                println!("Applied migration {} ({})", migration.version, migration.name);
+                migration_status.state = MigrationState::Applied;
+            }
+        }
+    }
+
+    Ok(statuses)
+}
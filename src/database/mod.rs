@@ -1,70 +1,102 @@
+pub mod activity;
+pub mod comment;
+pub mod config;
+pub mod migrations;
+pub mod notification;
+pub mod project;
+pub mod rate_limit;
+pub mod share;
+pub mod sync;
+pub mod tag;
 pub mod todotask;
+pub mod tokens;
 pub mod users;
+pub mod password;
+pub mod recurrence;
 
 use std::{fmt::Display, sync::LazyLock};
-use surrealdb::{engine::any::Any, opt::auth::Root, Surreal};
+use surrealdb::{engine::any::Any, opt::auth::{Database, Namespace, Root}, Surreal};
+
+use config::{AuthLevel, DatabaseConfig};
 
 pub static DB:LazyLock<Surreal<Any>> = LazyLock::new(surrealdb::Surreal::init);
 
-/// Connects the static singleton DB to the database via WS, localhost:8000
+/// The runtime the connection of `DB` runs on
+/// The engines run on the runtime they were connected from, so this keeps the connection alive when the runtime that called `connect` stops, e.g. at the end of each test
+static RUNTIME: LazyLock<tokio::runtime::Runtime> = LazyLock::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .thread_name("database")
+        .enable_all()
+        .build()
+        .expect("Failed to start the database runtime")
+});
+
+/// Connects the static singleton DB to the database using the configuration from `Rocket.toml` and the environment
+/// Then runs the migrations according to the configured mode, panicking if the database does not match this binary
 /// 
 /// # Returns
 /// `()` - Nothing
 pub async fn connect() -> () {
+    let config = DatabaseConfig::get();
+
+    // Its okay for this to panic because the backend can't do anything without the database
+    connect_with(config)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to connect to database: {}", e));
 
-    let _ = DB.connect("ws://127.0.0.1:8000")
-        .await;
-        // .map_err(|error| {
-        //     match &error {
-        //         surrealdb::Error::Api(api_error) => match api_error {
-        //             surrealdb::error::Api::AlreadyConnected => {} // If the database is already connected, we dont have to do anything
-        //             _ => panic!("Failed to connect to database: {}", error),
-        //         },
-        //         _ => panic!("Failed to connect to database: {}", error),
-        //     }
-        // });
-    DB.use_ns("Dev").await.expect("Failed to use namespace 'Dev'");
-    DB.use_db("Dev").await.expect("Failed to use database 'Dev'");
-    DB.signin(Root {
-        username: "root",
-        password: "root",
-    }).await.expect("Failed to login as root user");
+    // Its okay for this to panic because the backend should refuse to start if the schema is wrong
+    migrations::migrate(config.migrations)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to run migrations: {}", e));
 }
 
-#[allow(dead_code)]
-/// Creates the tables and fields in the database
-/// This function is used to create the tables and fields in the database
-/// It is only used when setting up the database or during testing, not during production
+/// Connects the static singleton DB to the database using the given configuration
+/// Supports any engine enabled for `Surreal<Any>`, e.g. `ws://`, `mem://` and `rocksdb://`
+/// Every caller shares the one connection, so connecting again only selects the namespace and database and signs in
+/// 
+/// # Arguments
+/// * `config` - The configuration to connect with
 /// 
 /// # Returns
-/// `()` - Nothing
-pub async fn create_all() -> () {
-    let mut response = DB.query("
-    DEFINE TABLE User SCHEMAFULL;
-    DEFINE FIELD username ON TABLE User TYPE string;
-    DEFINE FIELD email ON TABLE User TYPE string;
-    DEFINE FIELD password ON TABLE User TYPE string;
-    DEFINE FIELD created_at ON TABLE User TYPE datetime DEFAULT time::now();
-    DEFINE INDEX uniqueUsername ON TABLE User COLUMNS username UNIQUE;
-    DEFINE INDEX uniqueEmail ON TABLE User COLUMNS email UNIQUE;
-
-    DEFINE TABLE ToDoTask SCHEMAFULL; 
-    DEFINE FIELD title ON TABLE ToDoTask TYPE string;
-    DEFINE FIELD description ON TABLE ToDoTask TYPE option<string>;
-    DEFINE FIELD owner ON TABLE ToDoTask TYPE record<User>;
-    DEFINE FIELD completed_at ON TABLE ToDoTask TYPE option<datetime>;
-    DEFINE FIELD created_at ON TABLE ToDoTask TYPE datetime DEFAULT time::now();
-
-    ")
-    .await
-    .expect("Failed to create table ToDoTask and fields"); // Its okay for this function to panic as it is only used when setting up the database or during testing, not during production
-
-    let result = response.take_errors();
-    if result.is_empty() {
-        println!("Table and fields created successfully.");
-    } else {
-        panic!("Failed to create table and fields: {:?}", result);
+/// `Result<(), String>` - Nothing, or why the database couldn't be used
+pub async fn connect_with(config: &DatabaseConfig) -> Result<(), String> {
+    let endpoint = config.endpoint.clone();
+    let connected = RUNTIME
+        .spawn(async move { DB.connect(endpoint).await })
+        .await
+        .map_err(|e| format!("Failed to connect to '{}': {}", config.endpoint, e))?;
+    match connected {
+        Ok(()) | Err(surrealdb::Error::Api(surrealdb::error::Api::AlreadyConnected)) => {}
+        Err(e) => return Err(format!("Failed to connect to '{}': {}", config.endpoint, e)),
     }
+
+    DB.use_ns(&config.namespace).await.map_err(|e| format!("Failed to use namespace '{}': {}", config.namespace, e))?;
+    DB.use_db(&config.database).await.map_err(|e| format!("Failed to use database '{}': {}", config.database, e))?;
+
+    // Sign in at the configured level, the embedded engines do not need this
+    let username = config.username.as_deref().unwrap_or_default();
+    let password = config.password.as_deref().unwrap_or_default();
+    let signin = match config.auth {
+        AuthLevel::Root => DB.signin(Root {
+            username,
+            password,
+        }).await,
+        AuthLevel::Namespace => DB.signin(Namespace {
+            namespace: &config.namespace,
+            username,
+            password,
+        }).await,
+        AuthLevel::Database => DB.signin(Database {
+            namespace: &config.namespace,
+            database: &config.database,
+            username,
+            password,
+        }).await,
+        AuthLevel::None => return Ok(()),
+    };
+    signin.map_err(|e| format!("Failed to sign in as '{}': {}", username, e))?;
+
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -73,11 +105,27 @@ pub async fn create_all() -> () {
 /// # Returns
 /// `()` - Nothing
 pub async fn clear_all_test() -> () {
+    // Deleting the test data adds to the activity log, so the activity is deleted last by the users and tasks it was about
+    // Activity can't be deleted unless $clear_test_activity is set, which nothing else does
     let sql = "
+    LET $users = SELECT VALUE id FROM User WHERE username CONTAINS \"TEST\";
+    LET $tasks = SELECT VALUE id FROM ToDoTask WHERE title CONTAINS \"TEST\";
+    DELETE RefreshToken WHERE user.username CONTAINS \"TEST\";
+    DELETE RevokedToken WHERE user.username CONTAINS \"TEST\";
+    DELETE Notification WHERE user.username CONTAINS \"TEST\";
+    DELETE TaskTombstone WHERE owner.username CONTAINS \"TEST\";
+    DELETE TaskRemoval WHERE user.username CONTAINS \"TEST\";
     DELETE User WHERE username CONTAINS \"TEST\";
-    DELETE ToDoTask WHERE title CONTAINS \"TEST\";";
+    DELETE TaskCompletion WHERE task.title CONTAINS \"TEST\";
+    DELETE CommentEdit WHERE comment.task.title CONTAINS \"TEST\";
+    DELETE Comment WHERE task.title CONTAINS \"TEST\";
+    DELETE ToDoTask WHERE title CONTAINS \"TEST\";
+    DELETE Project WHERE name CONTAINS \"TEST\";
+    DELETE Tag WHERE name CONTAINS \"TEST\";
+    DELETE Activity WHERE actor IN $users OR target IN $users OR target IN $tasks;";
 
     let mut response = DB.query(sql)
+        .bind(("clear_test_activity", true))
         .await
         .expect("Failed to clear test data");
 
@@ -94,7 +142,6 @@ pub async fn clear_all_test() -> () {
 /// * `BadData` - The data provided is invalid
 /// * `Other` - Any other error that may occur
 pub enum DBCreateError {
-    #[allow(dead_code)]
     AlreadyExists(String),
     BadData(String),
     Other(String)
@@ -105,22 +152,54 @@ pub enum DBCreateError {
 /// 
 /// # Variants
 /// * `NotFound` - The record was not found in the database
+/// * `AlreadyExists` - The change would make the record the same as another one, e.g. a taken username
 /// * `BadData` - The data provided is invalid
 /// * `Other` - Any other error that may occur
 pub enum DBEditError {
     NotFound(String),
+    AlreadyExists(String),
     BadData(String),
     Other(String)
 }
 
+#[derive(Debug, Clone)]
+/// Error type returned when checking or applying migrations
+/// 
+/// # Variants
+/// * `DatabaseAhead` - The database has migrations applied which are not in this binary
+/// * `ChecksumMismatch` - A migration has been edited since it was applied
+/// * `Other` - Any other error that may occur
+pub enum DBMigrationError {
+    DatabaseAhead(String),
+    ChecksumMismatch(String),
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+/// Error type returned when using refresh tokens
+/// 
+/// # Variants
+/// * `NotFound` - The token does not exist
+/// * `Expired` - The token has expired
+/// * `Reused` - The token has already been used or revoked, so its family has been revoked
+/// * `Other` - Any other error that may occur
+pub enum DBTokenError {
+    NotFound(String),
+    Expired(String),
+    Reused(String),
+    Other(String),
+}
+
 #[derive(Debug, Clone)]
 /// Error type returned when reading records from the database
 /// 
 /// # Variants
 /// * `NotFound` - The record was not found in the database
+/// * `BadData` - The data used to query the database is invalid
 /// * `Other` - Any other error that may occur
 pub enum DBReadError {
     NotFound(String),
+    BadData(String),
     Other(String),
 }
 
@@ -138,6 +217,7 @@ impl Display for DBEditError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DBEditError::NotFound(msg) => write!(f, "Not found error: {}", msg),
+            DBEditError::AlreadyExists(msg) => write!(f, "Already exists error: {}", msg),
             DBEditError::BadData(msg) => write!(f, "Bad data error: {}", msg),
             DBEditError::Other(msg) => write!(f, "Other error: {}", msg),
         }
@@ -148,7 +228,29 @@ impl Display for DBReadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DBReadError::NotFound(msg) => write!(f, "Not found error: {}", msg),
+            DBReadError::BadData(msg) => write!(f, "Bad data error: {}", msg),
             DBReadError::Other(msg) => write!(f, "Other error: {}", msg),
         }
     }
+}
+
+impl Display for DBMigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DBMigrationError::DatabaseAhead(msg) => write!(f, "Database ahead error: {}", msg),
+            DBMigrationError::ChecksumMismatch(msg) => write!(f, "Checksum mismatch error: {}", msg),
+            DBMigrationError::Other(msg) => write!(f, "Other error: {}", msg),
+        }
+    }
+}
+
+impl Display for DBTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DBTokenError::NotFound(msg) => write!(f, "Not found error: {}", msg),
+            DBTokenError::Expired(msg) => write!(f, "Expired error: {}", msg),
+            DBTokenError::Reused(msg) => write!(f, "Reused error: {}", msg),
+            DBTokenError::Other(msg) => write!(f, "Other error: {}", msg),
+        }
+    }
 }
\ No newline at end of file
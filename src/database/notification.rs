@@ -0,0 +1,190 @@
+use surrealdb::sql::{Value, Thing};
+
+use crate::model::notification::{Notification, NotificationKind};
+use crate::model::todotask::ToDoTask;
+use super::{DBCreateError, DBEditError, DBReadError, DB};
+
+/// The most notifications returned at once
+const MAX_NOTIFICATIONS: i64 = 100;
+
+/// Create a notification for a user
+///
+/// # Arguments
+/// * `user_id` - The id of the user the notification is for
+/// * `kind` - What the notification is about
+/// * `task_id` - The id of the task the notification is about
+/// * `actor_id` - The id of the user who did the thing the notification is about
+///
+/// # Returns
+/// * `Result<Notification, DBCreateError>` - The created notification or an error
+pub async fn create_notification(
+    user_id: &str,
+    kind: NotificationKind,
+    task_id: &str,
+    actor_id: &str,
+) -> Result<Notification, DBCreateError> {
+
+    let sql = "CREATE Notification SET user = $user, kind = $kind, task = $task, actor = $actor;";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+    let actor: Value = Thing::from(("User", actor_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .bind(("kind", Value::from(kind.as_str())))
+        .bind(("task", task))
+        .bind(("actor", actor))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Notification> = response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBCreateError::Other("Failed to create notification".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Notify the owner and assignee of a task about something, except the user who did it
+///
+/// # Arguments
+/// * `task` - The task the notification is about
+/// * `kind` - What the notification is about
+/// * `actor_id` - The id of the user who did the thing the notification is about
+///
+/// # Returns
+/// * `Result<(), DBCreateError>` - Nothing or an error
+pub async fn notify_task_users(
+    task: &ToDoTask,
+    kind: NotificationKind,
+    actor_id: &str,
+) -> Result<(), DBCreateError> {
+
+    let task_id = match &task.id {
+        Some(id) => id.id.to_raw(),
+        None => return Err(DBCreateError::BadData("The task has no id".to_string())),
+    };
+
+    let mut users: Vec<String> = [&task.owner, &task.assignee]
+        .into_iter()
+        .flatten()
+        .map(|u| u.id.to_raw())
+        .filter(|u| u != actor_id)
+        .collect();
+    users.dedup();
+
+    for user in users {
+        create_notification(&user, kind, &task_id, actor_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the notifications of a user
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+/// * `unread` - Only get the notifications which have not been read
+///
+/// # Returns
+/// * `Result<Vec<Notification>, DBReadError>` - The most recent notifications, newest first, or an error
+pub async fn get_notifications(
+    user_id: &str,
+    unread: bool,
+) -> Result<Vec<Notification>, DBReadError> {
+
+    let sql = if unread {
+        "SELECT * FROM Notification WHERE user = $user AND read_at = NONE ORDER BY created_at DESC LIMIT $limit;"
+    } else {
+        "SELECT * FROM Notification WHERE user = $user ORDER BY created_at DESC LIMIT $limit;"
+    };
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .bind(("limit", Value::from(MAX_NOTIFICATIONS)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Notification> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Mark a notification of a user as read
+///
+/// # Arguments
+/// * `id` - The id of the notification
+/// * `user_id` - The id of the user, a user can only read their own notifications
+///
+/// # Returns
+/// * `Result<Notification, DBEditError>` - The notification or an error, `NotFound` if it isn't one of the users notifications
+pub async fn mark_notification_read(
+    id: &str,
+    user_id: &str,
+) -> Result<Notification, DBEditError> {
+
+    // Reading a notification again keeps the time it was first read
+    let sql = "UPDATE $id SET read_at = read_at ?? time::now() WHERE user = $user;";
+
+    let id: Value = Thing::from(("Notification", id)).into();
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Notification> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to get notification".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Mark all the notifications of a user as read
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+///
+/// # Returns
+/// * `Result<usize, DBEditError>` - How many notifications were marked as read, or an error
+pub async fn mark_all_notifications_read(
+    user_id: &str,
+) -> Result<usize, DBEditError> {
+
+    let sql = "UPDATE Notification SET read_at = time::now() WHERE user = $user AND read_at = NONE RETURN VALUE id;";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Thing> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    Ok(result.len())
+}
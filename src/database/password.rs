@@ -0,0 +1,109 @@
+use std::sync::LazyLock;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+/// The Argon2id memory cost in KiB used when hashing new passwords
+/// Raising any of these values will cause existing hashes to be upgraded the next time the user logs in
+const MEMORY_COST: u32 = 19 * 1024;
+/// The Argon2id number of iterations used when hashing new passwords
+const TIME_COST: u32 = 2;
+/// The Argon2id degree of parallelism used when hashing new passwords
+const PARALLELISM: u32 = 1;
+
+/// A hash of a random password with the current parameters, checked when there is no real hash
+/// This makes a log in for an unknown user take as long as one for a known user
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    let password = SaltString::generate(&mut OsRng);
+    hash_password(password.as_str()).expect("Failed to hash dummy password")
+});
+
+/// Build the Argon2id hasher with the current parameters
+///
+/// # Returns
+/// `Argon2<'static>` - The configured hasher
+fn hasher() -> Argon2<'static> {
+    // These are constants so this can only panic if they are changed to invalid values
+    let params = Params::new(MEMORY_COST, TIME_COST, PARALLELISM, None).expect("Invalid Argon2 parameters");
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Hash a password with Argon2id and a random salt
+///
+/// # Arguments
+/// * `password` - The plaintext password to hash
+///
+/// # Returns
+/// `Result<String, String>` - The PHC string to store in the database, or an error message
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    let hash = hasher()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| format!("Failed to hash password: {}", e))?;
+
+    Ok(hash.to_string())
+}
+
+/// Verify a password against a stored PHC string
+///
+/// # Arguments
+/// * `password` - The plaintext password to check
+/// * `stored` - The PHC string stored in the database
+///
+/// # Returns
+/// `bool` - True if the password matches, false otherwise (including if the stored hash is unreadable)
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let hash = match PasswordHash::new(stored) {
+        Ok(h) => h,
+        Err(_) => {
+            verify_dummy_password(password);
+            return false;
+        }
+    };
+
+    // Verifying uses the algorithm and parameters stored in the hash, not the current ones
+    // This means hashes created with older parameters can still be checked
+    Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok()
+}
+
+/// Verify a password against a hash no password matches
+/// This is called when there is no user so the check takes as long as a real one
+///
+/// # Arguments
+/// * `password` - The plaintext password to check
+pub fn verify_dummy_password(password: &str) {
+    let hash = PasswordHash::new(&DUMMY_HASH).expect("Invalid dummy hash");
+    let _ = Argon2::default().verify_password(password.as_bytes(), &hash);
+}
+
+/// Check if a stored PHC string was created with outdated hashing parameters
+///
+/// # Arguments
+/// * `stored` - The PHC string stored in the database
+///
+/// # Returns
+/// `bool` - True if the password should be hashed again with the current parameters
+pub fn needs_rehash(stored: &str) -> bool {
+    let hash = match PasswordHash::new(stored) {
+        Ok(h) => h,
+        Err(_) => return true,
+    };
+
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13.into()) {
+        return true;
+    }
+
+    match Params::try_from(&hash) {
+        Ok(params) => {
+            params.m_cost() != MEMORY_COST
+                || params.t_cost() != TIME_COST
+                || params.p_cost() != PARALLELISM
+        }
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,274 @@
+use chrono::Utc;
+use surrealdb::sql::{Value, Datetime as sdbDateTime, Thing};
+
+use crate::model::project::{Project, ProjectRequest};
+use crate::model::share::ShareRole;
+use super::share::{check_permission, ShareTarget};
+use super::{DBCreateError, DBEditError, DBReadError, DB};
+
+/// The id used for the tasks which are not in a project
+pub const INBOX: &str = "inbox";
+
+/// Create a project in the database
+///
+/// # Arguments
+/// * `owner` - The id of the user that owns the project
+/// * `project` - The fields of the new project, the name is required
+///
+/// # Returns
+/// * `Result<Project, DBCreateError>` - The created project or an error
+pub async fn create_project(
+    owner: &str,
+    project: &ProjectRequest,
+) -> Result<Project, DBCreateError> {
+
+    let sql = "
+    CREATE Project
+    SET name = $name,
+    description = $description,
+    archived_at = $archived_at,
+    owner = $owner;
+    ";
+
+    // Take each value and make it a surrealdb::sql::value, if optional values are None then we set them to Value::None
+    let name: Value = match project.name.as_deref() {
+        Some(n) if !n.trim().is_empty() => Value::from(n.trim()),
+        _ => return Err(DBCreateError::BadData("Name is required".to_string())),
+    };
+    let description = match project.description.as_deref() {
+        Some(d) => Value::from(d),
+        None => Value::None,
+    };
+    let archived_at = match project.archived {
+        Some(true) => Value::Datetime(sdbDateTime::from(Utc::now())),
+        _ => Value::None,
+    };
+    let owner: Value = Thing::from(("User", owner)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("name", name))
+        .bind(("description", description))
+        .bind(("archived_at", archived_at))
+        .bind(("owner", owner))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Project> = response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBCreateError::Other("Failed to create project".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a project from the database by id
+///
+/// # Arguments
+/// * `id` - The id of the project to get
+///
+/// # Returns
+/// * `Result<Project, DBReadError>` - The project or an error
+pub async fn get_project_by_id(
+    id: &str,
+) -> Result<Project, DBReadError> {
+
+    let sql = "SELECT * FROM $id;";
+
+    // Convert the id to a surrealdb::sql::value
+    let id: Value = Thing::from(("Project", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Project> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get project".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the projects of a user, including the projects shared with them
+///
+/// # Arguments
+/// * `user_id` - The id of the user to get projects for
+/// * `archived` - Get the archived projects instead of the active ones
+///
+/// # Returns
+/// * `Result<Vec<Project>, DBReadError>` - The projects, oldest first, or an error
+pub async fn get_projects_by_user(
+    user_id: &str,
+    archived: bool,
+) -> Result<Vec<Project>, DBReadError> {
+
+    let sql = if archived {
+        "SELECT * FROM Project WHERE (owner = $owner OR id IN (SELECT VALUE out FROM shared WHERE in = $owner)) AND archived_at != NONE ORDER BY created_at, id;"
+    } else {
+        "SELECT * FROM Project WHERE (owner = $owner OR id IN (SELECT VALUE out FROM shared WHERE in = $owner)) AND archived_at = NONE ORDER BY created_at, id;"
+    };
+
+    // Convert the id to a surrealdb::sql::value
+    let owner: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("owner", owner))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Project> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Edit a project in the database by id
+///
+/// # Arguments
+/// * `id` - The id of the project to edit
+/// * `project` - The fields to change, fields which are None are left as they are
+///
+/// # Returns
+/// * `Result<Project, DBEditError>` - The edited project or an error
+pub async fn edit_project_by_id(
+    id: &str,
+    project: &ProjectRequest,
+) -> Result<Project, DBEditError> {
+
+    let mut sql = String::from("UPDATE $id SET ");
+
+    // Create the sql string depending on what parameters are passed in
+    let name = match project.name.as_deref() {
+        Some(n) if n.trim().is_empty() => return Err(DBEditError::BadData("Name can't be empty".to_string())),
+        Some(n) => {
+            sql.push_str("name = $name, ");
+            Value::from(n.trim())
+        },
+        None => Value::None,
+    };
+    let description = match project.description.as_deref() {
+        Some(d) => {
+            sql.push_str("description = $description, ");
+            Value::from(d)
+        },
+        None => Value::None,
+    };
+    // Archiving a project which is already archived keeps the original time
+    match project.archived {
+        Some(true) => sql.push_str("archived_at = archived_at ?? time::now(), "),
+        Some(false) => sql.push_str("archived_at = NONE, "),
+        None => {},
+    }
+
+    if !sql.ends_with(", ") {
+        return Err(DBEditError::BadData("Nothing to change".to_string()));
+    }
+    // Remove the last comma and space from the SQL string
+    sql.pop();
+    sql.pop();
+    sql.push(';');
+
+    // Convert the id to a surrealdb::sql::value
+    let id: Value = Thing::from(("Project", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("name", name))
+        .bind(("description", description))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Project> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to edit project".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Delete a project from the database by id, its tasks are moved to the inbox
+///
+/// # Arguments
+/// * `id` - The id of the project to delete
+/// * `actor_id` - The id of the user deleting the project, for the activity log
+///
+/// # Returns
+/// * `Result<Project, DBReadError>` - The deleted project or an error
+pub async fn delete_project_by_id(
+    id: &str,
+    actor_id: &str,
+) -> Result<Project, DBReadError> {
+
+    // The tasks are moved out first, so the members of a shared project are told they can no longer see them
+    let sql = "
+    BEGIN TRANSACTION;
+    UPDATE ToDoTask SET project = NONE WHERE project = $id;
+    DELETE ONLY $id RETURN BEFORE;
+    COMMIT TRANSACTION;
+    ";
+
+    // Convert the id to a surrealdb::sql::value
+    let id: Value = Thing::from(("Project", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Project> = response
+        .take(1)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to delete project".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Check a task can be put in a project
+/// The project must exist, be owned by the user or shared with them as at least an editor, and not be archived
+///
+/// # Arguments
+/// * `project_id` - The id of the project
+/// * `user_id` - The id of the user adding the task to the project
+///
+/// # Returns
+/// * `Result<(), DBReadError>` - Nothing if the project is allowed, or an error explaining why not
+pub async fn check_task_project(project_id: &str, user_id: &str) -> Result<(), DBReadError> {
+    let project = get_project_by_id(project_id).await.map_err(|e| match e {
+        DBReadError::NotFound(_) => DBReadError::NotFound("Project not found".to_string()),
+        e => e,
+    })?;
+
+    if !check_permission(user_id, ShareTarget::Project(project_id), ShareRole::Editor).await? {
+        return Err(DBReadError::NotFound("Project not found".to_string()));
+    }
+    if project.archived_at.is_some() {
+        return Err(DBReadError::BadData("The project is archived".to_string()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use surrealdb::sql::{Datetime as sdbDateTime, Thing, Value};
+
+use crate::model::rate_limit::LoginAttempt;
+
+use super::{DBEditError, DBReadError, DB};
+
+/// Refill a token bucket for the time since it was last used and take a token if there is one
+/// This is one transaction so instances sharing the database can't both take the last token
+const TAKE_TOKEN_SQL: &str = "
+BEGIN TRANSACTION;
+LET $now = time::now();
+LET $bucket = (SELECT * FROM ONLY $id);
+LET $tokens = IF $bucket THEN math::min([$capacity, $bucket.tokens + duration::millis($now - $bucket.updated_at) * $per_milli]) ELSE $capacity END;
+UPSERT $id SET tokens = IF $tokens >= 1 THEN $tokens - 1 ELSE $tokens END, updated_at = $now, expires_at = $expires_at;
+RETURN $tokens;
+COMMIT TRANSACTION;
+";
+
+/// Count a failed log in, starting again from one if the last failure was too long ago
+const RECORD_FAILURE_SQL: &str = "
+BEGIN TRANSACTION;
+LET $now = time::now();
+LET $attempt = (SELECT * FROM ONLY $id);
+LET $failures = IF $attempt AND $attempt.updated_at > $forget_before THEN $attempt.failures + 1 ELSE 1 END;
+UPSERT $id SET failures = $failures, retry_at = NONE, updated_at = $now, expires_at = $expires_at;
+RETURN $failures;
+COMMIT TRANSACTION;
+";
+
+/// Take a token from a bucket shared by every instance of the backend
+///
+/// # Arguments
+/// * `key` - The bucket, e.g. `log_in:ip:127.0.0.1`
+/// * `capacity` - The most tokens the bucket can hold, a new bucket starts full
+/// * `per_minute` - How many tokens are added back each minute
+///
+/// # Returns
+/// * `Result<f64, DBEditError>` - The tokens in the bucket before this request, one was taken if there was at least one
+pub async fn take_token(key: &str, capacity: u32, per_minute: u32) -> Result<f64, DBEditError> {
+    // The bucket can be forgotten once it would have filled up again
+    let refill = chrono::Duration::milliseconds((f64::from(capacity) / f64::from(per_minute.max(1)) * 60_000.0) as i64);
+    let expires_at = Utc::now() + refill;
+
+    let mut response = DB.query(TAKE_TOKEN_SQL)
+        .bind(("id", Value::Thing(Thing::from(("RateLimit", key)))))
+        .bind(("capacity", f64::from(capacity)))
+        .bind(("per_milli", f64::from(per_minute) / 60_000.0))
+        .bind(("expires_at", Value::Datetime(sdbDateTime::from(expires_at))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let tokens: Option<f64> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    tokens.ok_or_else(|| DBEditError::Other("Failed to take a token".to_string()))
+}
+
+/// Get the failed log ins for an account
+///
+/// # Arguments
+/// * `key` - The account, e.g. `username:bob`
+///
+/// # Returns
+/// * `Result<Option<LoginAttempt>, DBReadError>` - The failed log ins, None if there haven't been any
+pub async fn get_login_attempt(key: &str) -> Result<Option<LoginAttempt>, DBReadError> {
+    let mut response = DB.query("SELECT * FROM ONLY $id;")
+        .bind(("id", Value::Thing(Thing::from(("LoginAttempt", key)))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })
+}
+
+/// Count a failed log in for an account
+///
+/// # Arguments
+/// * `key` - The account, e.g. `username:bob`
+/// * `forget_after` - How long after the last failure the count starts again
+///
+/// # Returns
+/// * `Result<i64, DBEditError>` - How many log ins have failed in a row, including this one
+pub async fn record_login_failure(key: &str, forget_after: chrono::Duration) -> Result<i64, DBEditError> {
+    let now = Utc::now();
+
+    let mut response = DB.query(RECORD_FAILURE_SQL)
+        .bind(("id", Value::Thing(Thing::from(("LoginAttempt", key)))))
+        .bind(("forget_before", Value::Datetime(sdbDateTime::from(now - forget_after))))
+        .bind(("expires_at", Value::Datetime(sdbDateTime::from(now + forget_after))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let failures: Option<i64> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    failures.ok_or_else(|| DBEditError::Other("Failed to record the failed log in".to_string()))
+}
+
+/// Stop an account being tried again until a time
+///
+/// # Arguments
+/// * `key` - The account, e.g. `username:bob`
+/// * `retry_at` - When the account can be tried again
+///
+/// # Returns
+/// * `Result<(), DBEditError>` - Nothing, or an error
+pub async fn set_login_retry_at(key: &str, retry_at: DateTime<Utc>) -> Result<(), DBEditError> {
+    let sql = "UPDATE $id SET retry_at = $retry_at, expires_at = IF $retry_at > expires_at THEN $retry_at ELSE expires_at END;";
+
+    DB.query(sql)
+        .bind(("id", Value::Thing(Thing::from(("LoginAttempt", key)))))
+        .bind(("retry_at", Value::Datetime(sdbDateTime::from(retry_at))))
+        .await
+        .unwrap() // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+        .check()
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Forget the failed log ins for an account, after it has been logged in to
+///
+/// # Arguments
+/// * `key` - The account, e.g. `username:bob`
+///
+/// # Returns
+/// * `Result<(), DBEditError>` - Nothing, or an error
+pub async fn clear_login_attempts(key: &str) -> Result<(), DBEditError> {
+    DB.query("DELETE $id;")
+        .bind(("id", Value::Thing(Thing::from(("LoginAttempt", key)))))
+        .await
+        .unwrap() // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+        .check()
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Delete the buckets which have filled up again and the failed log ins which have been forgotten
+///
+/// # Returns
+/// * `Result<usize, DBReadError>` - How many records were deleted, or an error
+pub async fn purge_rate_limits() -> Result<usize, DBReadError> {
+    let sql = "
+    DELETE RateLimit WHERE expires_at < time::now() RETURN BEFORE;
+    DELETE LoginAttempt WHERE expires_at < time::now() RETURN BEFORE;
+    ";
+
+    let mut response = DB.query(sql)
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let buckets: Vec<Thing> = response
+        .take((0, "id"))
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    let attempts: Vec<Thing> = response
+        .take((1, "id"))
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(buckets.len() + attempts.len())
+}
+
+/// Purge the rate limits forever, this is started in the background when the server launches with the database store
+///
+/// # Arguments
+/// * `every` - How long to wait between purges
+pub async fn purge_rate_limits_periodically(every: std::time::Duration) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        if let Err(err) = purge_rate_limits().await {
+            println!("Failed to purge rate limits: {:?}", err);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc, Weekday};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How often a recurring task repeats
+///
+/// # Variants
+/// * `Daily` - Every `interval` days
+/// * `Weekly` - Every `interval` weeks
+/// * `Monthly` - Every `interval` months, on the same day of the month
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A recurrence rule, the subset of an iCalendar RRULE supported for tasks
+/// e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10`
+///
+/// # Fields
+/// * `frequency` - `FREQ`, how often the task repeats
+/// * `interval` - `INTERVAL`, the number of days, weeks or months between occurrences
+/// * `by_day` - `BYDAY`, the weekdays the task happens on, only for daily and weekly rules
+/// * `until` - `UNTIL`, the last time an occurrence can be due
+/// * `count` - `COUNT`, the total number of occurrences
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub until: Option<DateTime<Utc>>,
+    pub count: Option<u32>,
+}
+
+/// The iCalendar names of the weekdays
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+/// How many months to look ahead for a month which has the day of a monthly rule, e.g. the 31st
+const MAX_MONTHS_SKIPPED: u32 = 12;
+
+impl RecurrenceRule {
+    /// Parse a recurrence rule, an optional `RRULE:` prefix is allowed
+    ///
+    /// # Arguments
+    /// * `rule` - The rule to parse
+    ///
+    /// # Returns
+    /// `Result<RecurrenceRule, String>` - The rule, or a message explaining why it is invalid
+    pub fn parse(rule: &str) -> Result<RecurrenceRule, String> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut until = None;
+        let mut count = None;
+
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Expected KEY=VALUE but found '{}'", part))?;
+
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("Unsupported FREQ '{}', expected DAILY, WEEKLY or MONTHLY", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| format!("INTERVAL must be a positive number, found '{}'", value))?
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let weekday = WEEKDAYS
+                            .iter()
+                            .find(|(name, _)| name.eq_ignore_ascii_case(day))
+                            .map(|(_, weekday)| *weekday)
+                            .ok_or_else(|| format!("Unknown BYDAY weekday '{}'", day))?;
+                        if !by_day.contains(&weekday) {
+                            by_day.push(weekday);
+                        }
+                    }
+                }
+                "UNTIL" => until = Some(parse_until(value)?),
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|c| *c > 0)
+                            .ok_or_else(|| format!("COUNT must be a positive number, found '{}'", value))?,
+                    )
+                }
+                _ => return Err(format!("Unsupported recurrence part '{}'", key)),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| "FREQ is required".to_string())?;
+        if until.is_some() && count.is_some() {
+            return Err("UNTIL and COUNT can't both be used".to_string());
+        }
+        if frequency == Frequency::Monthly && !by_day.is_empty() {
+            return Err("BYDAY is only supported for DAILY and WEEKLY rules".to_string());
+        }
+        by_day.sort_by_key(|d| d.num_days_from_monday());
+
+        Ok(RecurrenceRule { frequency, interval, by_day, until, count })
+    }
+
+    /// Find the occurrence after the one which was just completed
+    ///
+    /// # Arguments
+    /// * `previous` - When the completed occurrence was due
+    /// * `completed` - How many occurrences have been completed, including this one
+    ///
+    /// # Returns
+    /// `Option<DateTime<Utc>>` - When the next occurrence is due, or None if the rule has finished
+    pub fn next_occurrence(&self, previous: DateTime<Utc>, completed: u32) -> Option<DateTime<Utc>> {
+        if self.count.is_some_and(|count| completed >= count) {
+            return None;
+        }
+
+        let interval = i64::from(self.interval);
+        let next = match self.frequency {
+            Frequency::Daily if self.by_day.is_empty() => previous.checked_add_signed(Duration::days(interval)),
+            // The weekdays repeat every 7 steps so there is no match if none is found by then
+            Frequency::Daily => (1..=7)
+                .filter_map(|step| previous.checked_add_signed(Duration::days(interval * step)))
+                .find(|day| self.by_day.contains(&day.weekday())),
+            Frequency::Weekly if self.by_day.is_empty() => previous.checked_add_signed(Duration::weeks(interval)),
+            // Only weeks which are a multiple of the interval after the week of the previous occurrence count
+            Frequency::Weekly => {
+                let week_start = |day: DateTime<Utc>| day.date_naive().week(Weekday::Mon).first_day();
+                let previous_week = week_start(previous);
+                (1..=7 * (interval + 1))
+                    .filter_map(|days| previous.checked_add_signed(Duration::days(days)))
+                    .find(|day| {
+                        let weeks = (week_start(*day) - previous_week).num_days() / 7;
+                        weeks % interval == 0 && self.by_day.contains(&day.weekday())
+                    })
+            }
+            // Months without the day of the previous occurrence are skipped rather than moving the day
+            Frequency::Monthly => (1..=MAX_MONTHS_SKIPPED)
+                .filter_map(|step| {
+                    let months = previous.year() * 12 + previous.month0() as i32 + (self.interval * step) as i32;
+                    NaiveDate::from_ymd_opt(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, previous.day())
+                })
+                .map(|date| NaiveDateTime::new(date, previous.time()).and_utc())
+                .next(),
+        }?;
+
+        match self.until {
+            Some(until) if next > until => None,
+            _ => Some(next),
+        }
+    }
+}
+
+impl Display for RecurrenceRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={};INTERVAL={}", frequency, self.interval)?;
+
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self
+                .by_day
+                .iter()
+                .filter_map(|day| WEEKDAYS.iter().find(|(_, d)| d == day).map(|(name, _)| *name))
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse the `UNTIL` of a rule, either in the iCalendar format (`20240131T000000Z` or `20240131`) or RFC 3339
+///
+/// # Arguments
+/// * `until` - The value to parse
+///
+/// # Returns
+/// `Result<DateTime<Utc>, String>` - The time, or a message explaining why it is invalid
+fn parse_until(until: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = NaiveDateTime::parse_from_str(until, "%Y%m%dT%H%M%SZ") {
+        return Ok(time.and_utc());
+    }
+    // A date on its own includes the whole day
+    if let Ok(date) = NaiveDate::parse_from_str(until, "%Y%m%d") {
+        return Ok(date.and_hms_opt(23, 59, 59).unwrap_or_default().and_utc());
+    }
+    DateTime::parse_from_rfc3339(until)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("Couldn't format UNTIL '{}'", until))
+}
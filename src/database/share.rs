@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use surrealdb::sql::{Value, Thing};
+
+use crate::model::share::{Share, ShareRole, SharedTask};
+use crate::model::todotask::ToDoTask;
+use super::{DBCreateError, DBReadError, DB};
+
+#[derive(Debug, Clone, Copy)]
+/// Something which can be shared with a user
+///
+/// # Variants
+/// * `Task` - A task, by id
+/// * `Project` - A project by id, sharing a project shares all of its tasks
+pub enum ShareTarget<'a> {
+    Task(&'a str),
+    Project(&'a str),
+}
+
+impl ShareTarget<'_> {
+    /// The record id of the target in the database
+    fn thing(&self) -> Thing {
+        match self {
+            ShareTarget::Task(id) => Thing::from(("ToDoTask", *id)),
+            ShareTarget::Project(id) => Thing::from(("Project", *id)),
+        }
+    }
+}
+
+/// Owner of a task or project, only used to read the owner, assignee, owner of the project and if it is in the trash
+#[derive(Debug, serde::Deserialize)]
+struct Owned {
+    owner: Option<Thing>,
+    assignee: Option<Thing>,
+    project_owner: Option<Thing>,
+    deleted_at: Option<surrealdb::sql::Datetime>,
+}
+
+/// Check if a user can do something with a task or project
+/// The owner, or the owner of the project a task is in, can do everything, anyone else needs a role at least as high as the one required. A role on a project applies to all of its tasks
+/// The assignee of a task is an editor so they can complete it
+///
+/// # Arguments
+/// * `user_id` - The id of the user making the request
+/// * `target` - The task or project
+/// * `required` - The lowest role which is allowed
+///
+/// # Returns
+/// * `Result<bool, DBReadError>` - True if the user is allowed, false otherwise, or an error. `NotFound` if the target doesn't exist or is in the trash
+pub async fn check_permission(user_id: &str, target: ShareTarget<'_>, required: ShareRole) -> Result<bool, DBReadError> {
+    // A task also gets the roles given on its project
+    let sql = "
+    SELECT owner, assignee, project.owner AS project_owner, deleted_at FROM $target;
+    SELECT VALUE role FROM shared WHERE in = $user AND (out = $target OR out = $target.project);
+    ";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("target", Value::Thing(target.thing())))
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let owned: Option<Owned> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    let owned = owned.filter(|o| o.deleted_at.is_none()).ok_or_else(|| {
+        DBReadError::NotFound("Failed to get owner".to_string())
+    })?;
+    if [owned.owner, owned.project_owner].into_iter().flatten().any(|o| o.id.to_raw() == user_id) {
+        return Ok(true);
+    }
+
+    let mut roles: Vec<ShareRole> = response
+        .take(1)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    if owned.assignee.is_some_and(|a| a.id.to_raw() == user_id) {
+        roles.push(ShareRole::Editor);
+    }
+
+    Ok(roles.into_iter().max().is_some_and(|role| role >= required))
+}
+
+/// Share a task or project with a user, sharing again with the same user changes their role
+///
+/// # Arguments
+/// * `target` - The task or project to share
+/// * `user_id` - The id of the user to share with
+/// * `role` - What the user can do
+///
+/// # Returns
+/// * `Result<Share, DBCreateError>` - The share or an error, `BadData` if the user is the owner
+pub async fn share(target: ShareTarget<'_>, user_id: &str, role: ShareRole) -> Result<Share, DBCreateError> {
+    let sql = "INSERT RELATION INTO shared { in: $user, out: $target, role: $role } ON DUPLICATE KEY UPDATE role = $input.role;";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+    let role = Value::from(role.as_str());
+
+    // The owner is checked first so the edge isn't made for them
+    let mut owner_response = DB.query("SELECT owner, deleted_at FROM $target;")
+        .bind(("target", Value::Thing(target.thing())))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+    let owned: Option<Owned> = owner_response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+    let owned = owned.ok_or_else(|| {
+        DBCreateError::BadData("The task or project doesn't exist".to_string())
+    })?;
+    if owned.owner.is_some_and(|o| o.id.to_raw() == user_id) {
+        return Err(DBCreateError::BadData("It can't be shared with its owner".to_string()));
+    }
+
+    let mut response = DB.query(sql)
+        .bind(("target", Value::Thing(target.thing())))
+        .bind(("user", user))
+        .bind(("role", role))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Share> = response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBCreateError::Other("Failed to share".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Stop sharing a task or project with a user
+///
+/// # Arguments
+/// * `target` - The task or project
+/// * `user_id` - The id of the user it is shared with
+///
+/// # Returns
+/// * `Result<Share, DBReadError>` - The removed share or an error, `NotFound` if it wasn't shared with the user
+pub async fn unshare(target: ShareTarget<'_>, user_id: &str) -> Result<Share, DBReadError> {
+    let sql = "DELETE shared WHERE in = $user AND out = $target RETURN BEFORE;";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("target", Value::Thing(target.thing())))
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Share> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("It isn't shared with this user".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the users a task or project is shared with
+///
+/// # Arguments
+/// * `target` - The task or project
+///
+/// # Returns
+/// * `Result<Vec<Share>, DBReadError>` - The shares, oldest first, or an error
+pub async fn get_shares(target: ShareTarget<'_>) -> Result<Vec<Share>, DBReadError> {
+    let sql = "SELECT * FROM shared WHERE out = $target ORDER BY created_at, id;";
+
+    let mut response = DB.query(sql)
+        .bind(("target", Value::Thing(target.thing())))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Share> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Get the tasks other users have shared with a user, directly or through a project
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+///
+/// # Returns
+/// * `Result<Vec<SharedTask>, DBReadError>` - The tasks with the role of the user, oldest first, or an error
+pub async fn get_tasks_shared_with(user_id: &str) -> Result<Vec<SharedTask>, DBReadError> {
+    let sql = "SELECT * FROM ToDoTask WHERE owner != $user AND deleted_at = NONE AND (id IN $targets OR project IN $targets) ORDER BY created_at, id;";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    // Find what is shared first so the tasks can be found by id
+    let mut response = DB.query("SELECT * FROM shared WHERE in = $user;")
+        .bind(("user", user.clone()))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+    let shares: Vec<Share> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    // The roles are looked up by the record id as a string, e.g. `ToDoTask:abc`
+    let targets: Vec<Thing> = shares.iter().filter_map(|s| s.target.clone()).collect();
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+    let roles: HashMap<String, ShareRole> = shares
+        .into_iter()
+        .filter_map(|s| s.target.map(|t| (t.to_string(), s.role)))
+        .collect();
+    let targets = Value::from(targets.into_iter().map(Value::Thing).collect::<Vec<Value>>());
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .bind(("targets", targets))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let tasks: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    // The role is the highest of the role on the task and the role on its project
+    let result = tasks
+        .into_iter()
+        .filter_map(|task| {
+            let direct = task.id.as_ref().and_then(|id| roles.get(&id.to_string()));
+            let project = task.project.as_ref().and_then(|p| roles.get(&p.to_string()));
+            let role = direct.max(project).copied()?;
+            Some(SharedTask { task, role })
+        })
+        .collect();
+
+    Ok(result)
+}
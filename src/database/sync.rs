@@ -0,0 +1,170 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use surrealdb::sql::{Value, Datetime as sdbDateTime, Thing};
+
+use crate::model::sync::{SyncChange, SyncPage, SyncQuery, TaskRemoval, TaskTombstone};
+use crate::model::todotask::ToDoTask;
+use super::{DBReadError, DB};
+
+/// The number of changes in a page when no limit is given
+const DEFAULT_PAGE_SIZE: u32 = 100;
+/// The largest number of changes a page can have
+const MAX_PAGE_SIZE: u32 = 500;
+/// How far back the cursor of the last page goes, so a write which committed after a sync but set an earlier `updated_at` is still sent
+const SYNC_OVERLAP_SECONDS: i64 = 60;
+
+/// A position in the changes, the time of a change and the task it was to
+/// A position without a task is before every change at that time
+type Position = (DateTime<Utc>, Option<Thing>);
+
+/// Read a cursor, `<time>|<task id>` or just `<time>`
+///
+/// # Arguments
+/// * `cursor` - The cursor from the last page
+///
+/// # Returns
+/// * `Result<Position, DBReadError>` - The position or an error, `BadData` if the cursor is invalid
+fn parse_cursor(cursor: &str) -> Result<Position, DBReadError> {
+    let (at, task) = match cursor.split_once('|') {
+        Some((at, task)) if !task.is_empty() => (at, Some(Thing::from(("ToDoTask", task)))),
+        Some(_) => return Err(DBReadError::BadData("The cursor is invalid".to_string())),
+        None => (cursor, None),
+    };
+    let at = DateTime::parse_from_rfc3339(at)
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|_| DBReadError::BadData("The cursor is invalid".to_string()))?;
+    Ok((at, task))
+}
+
+/// Write a position as a cursor
+fn format_cursor((at, task): &Position) -> String {
+    let at = at.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+    match task {
+        Some(task) => format!("{}|{}", at, task.id.to_raw()),
+        None => at,
+    }
+}
+
+/// Get a page of the changes to the tasks a user can see since a cursor
+/// These are the tasks they own, are assigned, are in their projects or are shared with them directly or through a project
+/// Tasks in the trash are sent as deleted, tasks deleted permanently are sent from their tombstone and tasks the user can no longer see from their removal
+///
+/// The changes are ordered by time and then task id, so a page ends between two changes even if they happened at the same time.
+/// The cursor of the last page is never later than `SYNC_OVERLAP_SECONDS` ago, so the most recent changes are sent again on the next sync
+/// instead of being skipped if they committed late. Applying a change twice leaves the client with the same task
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+/// * `query` - The cursor returned by the last page, None to get every task, and the limit
+///
+/// # Returns
+/// * `Result<SyncPage, DBReadError>` - The changes, oldest first, and the cursor for the next page or sync, or an error. `BadData` if the cursor is invalid
+pub async fn get_changes(
+    user_id: &str,
+    query: &SyncQuery,
+) -> Result<SyncPage, DBReadError> {
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let since = query.since.as_deref().map(parse_cursor).transpose()?;
+
+    // A client syncing for the first time has nothing to remove, so tombstones are only needed with a cursor
+    // Get one extra change of each kind to know if there is another page
+    let sql = "
+    LET $shared = SELECT VALUE out FROM shared WHERE in = $user;
+    SELECT * FROM ToDoTask
+        WHERE (owner = $user OR assignee = $user OR project.owner = $user OR id IN $shared OR project IN $shared)
+        AND ($at = NONE OR updated_at > $at OR (updated_at = $at AND ($after = NONE OR id > $after)))
+        ORDER BY updated_at, id LIMIT $limit;
+    SELECT * FROM TaskTombstone
+        WHERE $at != NONE AND owner = $user
+        AND (deleted_at > $at OR (deleted_at = $at AND ($after = NONE OR task > $after)))
+        ORDER BY deleted_at, task LIMIT $limit;
+    SELECT * FROM TaskRemoval
+        WHERE $at != NONE AND user = $user
+        AND (removed_at > $at OR (removed_at = $at AND ($after = NONE OR task > $after)))
+        ORDER BY removed_at, task LIMIT $limit;
+    ";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+    let (at, after) = match &since {
+        Some((at, after)) => (Value::Datetime(sdbDateTime::from(*at)), after.clone().map(Value::Thing).unwrap_or(Value::None)),
+        None => (Value::None, Value::None),
+    };
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .bind(("at", at))
+        .bind(("after", after))
+        .bind(("limit", Value::from(limit as i64 + 1)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let tasks: Vec<ToDoTask> = response
+        .take(1)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    let tombstones: Vec<TaskTombstone> = response
+        .take(2)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    let removals: Vec<TaskRemoval> = response
+        .take(3)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let mut changes: Vec<SyncChange> = tasks
+        .into_iter()
+        .filter_map(|task| {
+            Some(SyncChange {
+                id: task.id.clone()?,
+                deleted: task.deleted_at.is_some(),
+                changed_at: task.updated_at.clone()?,
+                task: Some(task),
+            })
+        })
+        .chain(tombstones.into_iter().filter_map(|tombstone| {
+            Some(SyncChange {
+                id: tombstone.task,
+                deleted: true,
+                task: None,
+                changed_at: tombstone.deleted_at?,
+            })
+        }))
+        .chain(removals.into_iter().filter_map(|removal| {
+            Some(SyncChange {
+                id: removal.task,
+                deleted: true,
+                task: None,
+                changed_at: removal.removed_at?,
+            })
+        }))
+        .collect();
+
+    // The tasks, tombstones and removals are merged in the same order as the database sorted them, the times are parsed so differing precision doesn't matter
+    let position = |change: &SyncChange| -> Option<Position> {
+        let at = DateTime::parse_from_rfc3339(&change.changed_at).ok()?.with_timezone(&Utc);
+        Some((at, Some(change.id.clone())))
+    };
+    changes.sort_by_key(position);
+
+    let has_more = changes.len() > limit as usize;
+    changes.truncate(limit as usize);
+
+    // The next page carries on after the last change, the last page goes back to catch late writes
+    // Without any changes the client keeps its cursor, or starts from the overlap before now
+    let last = changes.last().and_then(position).or(since);
+    let cursor = if has_more {
+        last
+    } else {
+        let overlap: Position = (Utc::now() - Duration::seconds(SYNC_OVERLAP_SECONDS), None);
+        Some(match last {
+            Some(last) if last.0 < overlap.0 => last,
+            _ => overlap,
+        })
+    };
+    let cursor = cursor.as_ref().map(format_cursor).unwrap_or_default();
+
+    Ok(SyncPage { changes, cursor, has_more })
+}
@@ -0,0 +1,351 @@
+use surrealdb::sql::{Value, Thing};
+
+use crate::model::tag::Tag;
+use super::{DBCreateError, DBEditError, DBReadError, DB};
+
+/// Create a tag in the database
+///
+/// # Arguments
+/// * `owner` - The id of the user that owns the tag
+/// * `name` - The name of the tag, a user can't have two tags with the same name
+///
+/// # Returns
+/// * `Result<Tag, DBCreateError>` - The created tag or an error, `AlreadyExists` if the user already has a tag with this name
+pub async fn create_tag(
+    owner: &str,
+    name: &str,
+) -> Result<Tag, DBCreateError> {
+
+    let sql = "CREATE Tag SET name = $name, owner = $owner;";
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DBCreateError::BadData("Name is required".to_string()));
+    }
+    let owner: Value = Thing::from(("User", owner)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("name", Value::from(name)))
+        .bind(("owner", owner))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            // The unique index on owner and name rejects a second tag with the same name
+            if e.to_string().contains("uniqueTagName") {
+                DBCreateError::AlreadyExists("A tag with this name already exists".to_string())
+            } else {
+                DBCreateError::Other(e.to_string())
+            }
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBCreateError::Other("Failed to create tag".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a tag from the database by id
+///
+/// # Arguments
+/// * `id` - The id of the tag to get
+///
+/// # Returns
+/// * `Result<Tag, DBReadError>` - The tag or an error
+pub async fn get_tag_by_id(
+    id: &str,
+) -> Result<Tag, DBReadError> {
+
+    let sql = "SELECT * FROM $id;";
+
+    // Convert the id to a surrealdb::sql::value
+    let id: Value = Thing::from(("Tag", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get tag".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the tags of a user
+///
+/// # Arguments
+/// * `user_id` - The id of the user to get tags for
+///
+/// # Returns
+/// * `Result<Vec<Tag>, DBReadError>` - The tags in order of name, or an error
+pub async fn get_tags_by_user(
+    user_id: &str,
+) -> Result<Vec<Tag>, DBReadError> {
+
+    let sql = "SELECT * FROM Tag WHERE owner = $owner ORDER BY name;";
+
+    // Convert the id to a surrealdb::sql::value
+    let owner: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("owner", owner))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Rename a tag
+///
+/// # Arguments
+/// * `id` - The id of the tag to rename
+/// * `name` - The new name of the tag
+///
+/// # Returns
+/// * `Result<Tag, DBEditError>` - The renamed tag or an error, `BadData` if the user already has a tag with this name
+pub async fn rename_tag(
+    id: &str,
+    name: &str,
+) -> Result<Tag, DBEditError> {
+
+    let sql = "UPDATE $id SET name = $name;";
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DBEditError::BadData("Name can't be empty".to_string()));
+    }
+    let id: Value = Thing::from(("Tag", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("name", Value::from(name)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            // The unique index on owner and name rejects a second tag with the same name
+            if e.to_string().contains("uniqueTagName") {
+                DBEditError::AlreadyExists("A tag with this name already exists".to_string())
+            } else {
+                DBEditError::Other(e.to_string())
+            }
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to rename tag".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Merge a tag into another, the tasks of the merged tag get the kept tag and the merged tag is deleted
+///
+/// # Arguments
+/// * `id` - The id of the tag to merge, this is deleted
+/// * `into` - The id of the tag to keep
+///
+/// # Returns
+/// * `Result<Tag, DBEditError>` - The kept tag or an error
+pub async fn merge_tags(
+    id: &str,
+    into: &str,
+) -> Result<Tag, DBEditError> {
+
+    if id == into {
+        return Err(DBEditError::BadData("A tag can't be merged into itself".to_string()));
+    }
+
+    // Tasks which already have both tags keep their existing edge, deleting the merged tag removes its edges
+    let sql = "
+    BEGIN TRANSACTION;
+    INSERT RELATION INTO tagged (SELECT in, $into AS out FROM tagged WHERE out = $id) ON DUPLICATE KEY UPDATE in = in;
+    DELETE $id;
+    SELECT * FROM ONLY $into;
+    COMMIT TRANSACTION;
+    ";
+
+    let id: Value = Thing::from(("Tag", id)).into();
+    let into: Value = Thing::from(("Tag", into)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("into", into))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Tag> = response
+        .take(2)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to merge tags".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Delete a tag from the database by id, it is removed from all of its tasks
+///
+/// # Arguments
+/// * `id` - The id of the tag to delete
+///
+/// # Returns
+/// * `Result<Tag, DBReadError>` - The deleted tag or an error
+pub async fn delete_tag_by_id(
+    id: &str,
+) -> Result<Tag, DBReadError> {
+
+    // Deleting a record also deletes the edges to it
+    let sql = "DELETE ONLY $id RETURN BEFORE;";
+
+    let id: Value = Thing::from(("Tag", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to delete tag".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Put a tag on a task, nothing changes if the task already has the tag
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `tag_id` - The id of the tag
+///
+/// # Returns
+/// * `Result<Vec<Tag>, DBEditError>` - The tags of the task or an error
+pub async fn tag_task(
+    task_id: &str,
+    tag_id: &str,
+) -> Result<Vec<Tag>, DBEditError> {
+
+    let sql = "INSERT RELATION INTO tagged { in: $task, out: $tag } ON DUPLICATE KEY UPDATE in = in;";
+
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+    let tag: Value = Thing::from(("Tag", tag_id)).into();
+
+    DB.query(sql)
+        .bind(("task", task))
+        .bind(("tag", tag))
+        .await
+        .unwrap() // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+        .check()
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    get_task_tags(task_id).await.map_err(|e| DBEditError::Other(e.to_string()))
+}
+
+/// Take a tag off a task
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `tag_id` - The id of the tag
+///
+/// # Returns
+/// * `Result<Vec<Tag>, DBEditError>` - The tags of the task or an error, `NotFound` if the task didn't have the tag
+pub async fn untag_task(
+    task_id: &str,
+    tag_id: &str,
+) -> Result<Vec<Tag>, DBEditError> {
+
+    let sql = "DELETE tagged WHERE in = $task AND out = $tag RETURN VALUE $before.id;";
+
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+    let tag: Value = Thing::from(("Tag", tag_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("task", task))
+        .bind(("tag", tag))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let removed: Vec<Thing> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    if removed.is_empty() {
+        return Err(DBEditError::NotFound("The task doesn't have this tag".to_string()));
+    }
+
+    get_task_tags(task_id).await.map_err(|e| DBEditError::Other(e.to_string()))
+}
+
+/// Get the tags of a task
+///
+/// # Arguments
+/// * `task_id` - The id of the task
+///
+/// # Returns
+/// * `Result<Vec<Tag>, DBReadError>` - The tags in order of name, or an error
+pub async fn get_task_tags(
+    task_id: &str,
+) -> Result<Vec<Tag>, DBReadError> {
+
+    let sql = "SELECT * FROM $task->tagged->Tag ORDER BY name;";
+
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("task", task))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<Tag> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Check if the requester is the owner of the tag
+///
+/// # Arguments
+/// * `requester_id` - The id of the user making the request
+/// * `tag_id` - The id of the tag to check
+///
+/// # Returns
+/// * `Result<bool, DBReadError>` - True if the requester is the owner of the tag, false otherwise, or an error
+pub async fn check_is_tag_owner(requester_id: &str, tag_id: &str) -> Result<bool, DBReadError> {
+    let tag = get_tag_by_id(tag_id).await?;
+
+    Ok(tag.owner.is_some_and(|o| o.id.to_raw() == requester_id))
+}
@@ -1,28 +1,48 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use surrealdb::sql::{Value, Datetime as sdbDateTime, Thing};
 
-use crate::model::todotask::ToDoTask;
-use super::{DBCreateError, DBEditError, DBReadError, DB};
+use crate::model::todotask::{SortDirection, TagMatch, TaskCompletion, TaskPage, TaskProgress, TaskQuery, TaskRequest, TaskSort, TaskTree, ToDoTask};
+use super::{config::SubtaskDeletePolicy, project::{check_task_project, INBOX}, recurrence::RecurrenceRule, DBCreateError, DBEditError, DBReadError, DB};
 
 /// Create a task in the database
 /// 
 /// # Arguments
 /// * `owner` - The id of the user that owns the task
-/// * `title` - The title of the task
-/// * `description` - The description of the task
-/// * `completed_at` - The time the task was completed
-/// * `created_at` - The time the task was created. Should only be used when 'uploading' a task created earlier offline
+/// * `task` - The fields of the new task, the title is required. `created_at` should only be used when 'uploading' a task created earlier offline
 /// 
 /// # Returns
 /// * `Result<ToDoTask, DBCreateError>` - The created task or an error
 pub async fn create_task(
     owner: &str,
-    title: &str,
-    description: Option<&str>,
-    completed_at: Option<&str>,
-    created_at: Option<&str>,
+    task: &TaskRequest,
 ) -> Result<ToDoTask, DBCreateError> {
 
+    // A subtask must belong to the same user and can't make a cycle
+    let parent = match task.parent.as_deref() {
+        Some(p) if !p.is_empty() => {
+            check_parent(None, p, owner).await.map_err(|e| match e {
+                DBReadError::NotFound(msg) | DBReadError::BadData(msg) => DBCreateError::BadData(msg),
+                DBReadError::Other(msg) => DBCreateError::Other(msg),
+            })?;
+            Value::Thing(Thing::from(("ToDoTask", p)))
+        },
+        _ => Value::None,
+    };
+
+    // Tasks without a project go in the inbox
+    let project = match task.project.as_deref() {
+        Some(p) if !p.is_empty() && p != INBOX => {
+            check_task_project(p, owner).await.map_err(|e| match e {
+                DBReadError::NotFound(msg) | DBReadError::BadData(msg) => DBCreateError::BadData(msg),
+                DBReadError::Other(msg) => DBCreateError::Other(msg),
+            })?;
+            Value::Thing(Thing::from(("Project", p)))
+        },
+        _ => Value::None,
+    };
+
     let owner: Value = Thing::from(("User", owner)).into();
     
     let sql = String::from("
@@ -31,61 +51,55 @@ pub async fn create_task(
     description = $description,
     completed_at = $completed_at,
     created_at = $created_at,
+    due_at = $due_at,
+    remind_at = $remind_at,
+    recurrence = $recurrence,
+    parent = $parent,
+    project = $project,
     owner = $owner;
     ");
 
-    // Convert the times to a chrono::DateTime<Utc>, leaving None untouched
-    let completed_at: Option<DateTime<Utc>> = match completed_at {
-        Some(c) => {
-            Some(DateTime::parse_from_rfc3339(c)
-                .map_err(|e| {
-                    DBCreateError::BadData(format!("Couldn't format completed_at: {}", e.to_string()))
-                })?
-                .with_timezone(&Utc))
-        },
-        None => None,
-    };
-    let created_at: Option<DateTime<Utc>> = match created_at {
-        Some(c) => {
-            Some(DateTime::parse_from_rfc3339(c)
-                .map_err(|e| {
-                    DBCreateError::BadData(format!("Couldn't format created_at: {}", e.to_string()))
-                })?
-                .with_timezone(&Utc))
-        },
-        None => None,
-    };
-
-    // Take each value and make it a surrealdb::sql::value, if optional values are None then we set them to Value::Null
+    // Take each value and make it a surrealdb::sql::value, if optional values are None then we set them to Value::None
     // I do this so i dont have to cast the type in the SQL statement, because that causes problems if the value is None
     // This lets me keep the actual SQL as simple as possible
-    let title: Value = Value::from(title);
-    let description = match description {
-        Some(d) => Value::from(d),
-        None => Value::None,
-    };
-    let completed_at = match completed_at {
-        Some(c) => Value::Datetime(sdbDateTime::from(c)),
-        None => Value::None,
+    let title: Value = match task.title.as_deref() {
+        Some(t) => Value::from(t),
+        None => return Err(DBCreateError::BadData("Title is required".to_string())),
     };
-    let created_at = match created_at {
-        Some(c) => Value::Datetime(sdbDateTime::from(c)),
+    let description = match task.description.as_deref() {
+        Some(d) => Value::from(d),
         None => Value::None,
     };
+    let completed_at = parse_time(task.completed_at.as_deref(), "completed_at").map_err(DBCreateError::BadData)?;
+    let created_at = parse_time(task.created_at.as_deref(), "created_at").map_err(DBCreateError::BadData)?;
+    let due_at = parse_time(task.due_at.as_deref(), "due_at").map_err(DBCreateError::BadData)?;
+    let remind_at = parse_time(task.remind_at.as_deref(), "remind_at").map_err(DBCreateError::BadData)?;
+    let recurrence = parse_recurrence(task.recurrence.as_deref()).map_err(DBCreateError::BadData)?;
 
     let mut response = DB.query(sql)
         .bind(("title", title))
         .bind(("description", description)) 
         .bind(("completed_at", completed_at))
         .bind(("created_at", created_at))
-        .bind(("owner", owner))
+        .bind(("due_at", due_at))
+        .bind(("remind_at", remind_at))
+        .bind(("recurrence", recurrence))
+        .bind(("parent", parent))
+        .bind(("project", project))
+        .bind(("owner", owner.clone()))
+        .bind(("actor", owner)) // The owner is the one creating the task, for the activity log
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
     let result: Option<ToDoTask> = response
         .take(0)
         .map_err(|e| {
-            DBCreateError::Other(e.to_string())
+            // The schema rejects a reminder after the due date
+            if e.to_string().contains("`remind_at`") {
+                DBCreateError::BadData("remind_at can't be after due_at".to_string())
+            } else {
+                DBCreateError::Other(e.to_string())
+            }
         })?;
         
     let result = result.ok_or_else(|| {
@@ -95,6 +109,44 @@ pub async fn create_task(
     Ok(result)
 }
 
+/// Parse an optional RFC 3339 time from a request into a surrealdb::sql::value
+/// 
+/// # Arguments
+/// * `time` - The time to parse
+/// * `name` - The name of the field, used in the error
+/// 
+/// # Returns
+/// * `Result<Value, String>` - The time, `Value::None` if there is no time, or an error message
+fn parse_time(time: Option<&str>, name: &str) -> Result<Value, String> {
+    match time {
+        Some(t) => {
+            let time = DateTime::parse_from_rfc3339(t)
+                .map_err(|e| format!("Couldn't format {}: {}", name, e))?
+                .with_timezone(&Utc);
+            Ok(Value::Datetime(sdbDateTime::from(time)))
+        },
+        None => Ok(Value::None),
+    }
+}
+
+/// Parse an optional recurrence rule from a request into a surrealdb::sql::value
+/// The rule is stored in its normalised form, an empty rule removes the recurrence
+/// 
+/// # Arguments
+/// * `recurrence` - The rule to parse
+/// 
+/// # Returns
+/// * `Result<Value, String>` - The normalised rule, `Value::None` if there is no rule, or an error message
+fn parse_recurrence(recurrence: Option<&str>) -> Result<Value, String> {
+    match recurrence {
+        Some(r) if !r.trim().is_empty() => {
+            let rule = RecurrenceRule::parse(r).map_err(|e| format!("Invalid recurrence: {}", e))?;
+            Ok(Value::from(rule.to_string()))
+        },
+        _ => Ok(Value::None),
+    }
+}
+
 #[allow(dead_code)]
 /// Get a task from the database by id
 /// 
@@ -102,12 +154,12 @@ pub async fn create_task(
 /// * `id` - The id of the task to get
 /// 
 /// # Returns
-/// * `Result<ToDoTask, DBReadError>` - The task or an error
+/// * `Result<ToDoTask, DBReadError>` - The task or an error, `NotFound` if it is in the trash
 pub async fn get_task_by_id(
     id: &str,
 ) -> Result<ToDoTask, DBReadError> {
 
-    let sql = "SELECT * FROM $id;";
+    let sql = "SELECT * FROM $id WHERE deleted_at = NONE;";
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to case anything in the SQL
@@ -132,98 +184,388 @@ pub async fn get_task_by_id(
     Ok(result)
 }
 
-#[allow(dead_code)]
-/// Get all tasks from the database by user id
+/// The number of tasks in a page when no limit is given
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// The largest number of tasks a page can have
+const MAX_PAGE_SIZE: u32 = 200;
+
+/// Get a page of a users tasks from the database, filtered and sorted by the query
+/// Pages are found with a cursor of the sort value and id of the last task, so pages stay consistent when tasks are added
+/// 
+/// # Arguments
+/// * `user_id` - The id of the user to get tasks for
+/// * `query` - The filters, sort order and cursor
+/// 
+/// # Returns
+/// * `Result<TaskPage, DBReadError>` - The page of tasks or an error
+pub async fn get_tasks_by_user(
+    user_id: &str,
+    query: &TaskQuery,
+) -> Result<TaskPage, DBReadError> {
+
+    let sort = query.sort.unwrap_or_default();
+    let direction = query.direction.unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Build the SQL statement from the filters that are given, every value is bound so only the field names are formatted in
+    let mut sql = String::from("SELECT * FROM ToDoTask WHERE deleted_at = NONE");
+
+    match query.completed {
+        Some(true) => sql.push_str(" AND completed_at != NONE"),
+        Some(false) => sql.push_str(" AND completed_at = NONE"),
+        None => {},
+    }
+
+    let created_after = match query.created_after.as_deref() {
+        Some(c) => {
+            sql.push_str(" AND created_at > $created_after");
+            Value::Datetime(sdbDateTime::from(parse_query_time(c, "created_after")?))
+        },
+        None => Value::None,
+    };
+    let created_before = match query.created_before.as_deref() {
+        Some(c) => {
+            sql.push_str(" AND created_at < $created_before");
+            Value::Datetime(sdbDateTime::from(parse_query_time(c, "created_before")?))
+        },
+        None => Value::None,
+    };
+
+    // The search ignores case, a missing description is treated as empty
+    let search = match query.search.as_deref() {
+        Some(s) if !s.trim().is_empty() => {
+            sql.push_str(" AND (string::lowercase(title) CONTAINS $search OR string::lowercase(description ?? '') CONTAINS $search)");
+            Value::from(s.trim().to_lowercase())
+        },
+        _ => Value::None,
+    };
+
+    // Tasks in archived projects are only returned when their project is asked for
+    // Asking for a project gives every task in it when the user owns the project or it is shared with them
+    let project = match query.project.as_deref() {
+        Some(INBOX) => {
+            sql.push_str(" AND owner = $owner AND project = NONE");
+            Value::None
+        },
+        Some(p) => {
+            sql.push_str(" AND project = $project AND (owner = $owner OR project.owner = $owner OR $project IN (SELECT VALUE out FROM shared WHERE in = $owner))");
+            Value::Thing(Thing::from(("Project", p)))
+        },
+        None => {
+            sql.push_str(" AND owner = $owner AND (project = NONE OR project.archived_at = NONE)");
+            Value::None
+        },
+    };
+
+    // Tags are matched by following the tagged edges of each task
+    let tags = if query.tag.is_empty() {
+        Value::None
+    } else {
+        match query.tag_match.unwrap_or_default() {
+            TagMatch::Any => sql.push_str(" AND ->tagged->Tag CONTAINSANY $tags"),
+            TagMatch::All => sql.push_str(" AND ->tagged->Tag CONTAINSALL $tags"),
+        }
+        Value::from(query.tag.iter().map(|t| Value::Thing(Thing::from(("Tag", t.as_str())))).collect::<Vec<Value>>())
+    };
+
+    // Only return tasks after the cursor, the id breaks ties between tasks with the same sort value
+    let field = sort.field();
+    let (operator, order) = match direction {
+        SortDirection::Asc => (">", "ASC"),
+        SortDirection::Desc => ("<", "DESC"),
+    };
+    let (cursor_value, cursor_id) = match query.cursor.as_deref() {
+        Some(c) => {
+            let (value, id) = decode_cursor(c, sort)?;
+            sql.push_str(&format!(
+                " AND ({field} {operator} $cursor_value OR ({field} = $cursor_value AND id {operator} $cursor_id))"
+            ));
+            (value, id)
+        },
+        None => (Value::None, Value::None),
+    };
+
+    // Get one extra task to know if there is another page
+    sql.push_str(&format!(" ORDER BY {field} {order}, id {order} LIMIT $limit;"));
+
+    // Convert the id to a surrealdb::sql::value
+    let owner: Value = Thing::from(("User", user_id)).into();
+
+    // Make the query and bind the values to the SQL statement
+    let mut response = DB.query(sql)
+        .bind(("owner", owner))
+        .bind(("created_after", created_after))
+        .bind(("created_before", created_before))
+        .bind(("search", search))
+        .bind(("project", project))
+        .bind(("tags", tags))
+        .bind(("cursor_value", cursor_value))
+        .bind(("cursor_id", cursor_id))
+        .bind(("limit", Value::from(limit as i64 + 1)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    // Take the response and convert it to a Vec<ToDoTask>
+    let mut tasks: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    // If there is another page make a cursor from the last task of this one
+    let next_cursor = if tasks.len() > limit as usize {
+        tasks.truncate(limit as usize);
+        tasks.last().map(|task| encode_cursor(task, sort))
+    } else {
+        None
+    };
+
+    Ok(TaskPage { tasks, next_cursor })
+}
+
+/// Get the tasks of a user which are past their due date and not completed
 /// 
 /// # Arguments
 /// * `user_id` - The id of the user to get tasks for
 /// 
 /// # Returns
-/// * `Result<Vec<ToDoTask>, DBReadError>` - The tasks or an error
-pub async fn get_all_tasks_by_user(
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The overdue tasks, most overdue first, or an error
+pub async fn get_overdue_tasks(
     user_id: &str,
 ) -> Result<Vec<ToDoTask>, DBReadError> {
 
     // Make the SQL statement
-    let sql = "SELECT * FROM ToDoTask WHERE owner = $owner;";
+    let sql = "
+    SELECT * FROM ToDoTask
+    WHERE owner = $owner AND deleted_at = NONE AND completed_at = NONE AND due_at != NONE AND due_at < time::now()
+    ORDER BY due_at ASC;
+    ";
 
     // Convert the id to a surrealdb::sql::value
     let owner: Value = Thing::from(("User", user_id)).into();
 
-    // Make the query and bind the id to the SQL statement
     let mut response = DB.query(sql)
         .bind(("owner", owner))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
-    // Take the response and convert it to a Vec<ToDoTask>
     let result: Vec<ToDoTask> = response
         .take(0)
         .map_err(|e| {
             DBReadError::Other(e.to_string())
         })?;
 
-    // Return the vec of tasks
     Ok(result)
+}
+
+/// Get the tasks of a user which are due soon and not completed
+/// 
+/// # Arguments
+/// * `user_id` - The id of the user to get tasks for
+/// * `within` - How far ahead to look for tasks which are due
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The upcoming tasks, soonest first, or an error
+pub async fn get_upcoming_tasks(
+    user_id: &str,
+    within: chrono::Duration,
+) -> Result<Vec<ToDoTask>, DBReadError> {
+
+    // Make the SQL statement
+    let sql = "
+    SELECT * FROM ToDoTask
+    WHERE owner = $owner AND deleted_at = NONE AND completed_at = NONE AND due_at >= time::now() AND due_at <= $until
+    ORDER BY due_at ASC;
+    ";
+
+    // Convert the inputs to surrealdb::sql::value
+    let owner: Value = Thing::from(("User", user_id)).into();
+    let until = Utc::now().checked_add_signed(within).ok_or_else(|| {
+        DBReadError::BadData("within is too large".to_string())
+    })?;
+    let until = Value::Datetime(sdbDateTime::from(until));
+
+    let mut response = DB.query(sql)
+        .bind(("owner", owner))
+        .bind(("until", until))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
+    let result: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Parse a time from a query parameter
+/// 
+/// # Arguments
+/// * `time` - The RFC 3339 time to parse
+/// * `name` - The name of the parameter, used in the error
+/// 
+/// # Returns
+/// * `Result<DateTime<Utc>, DBReadError>` - The time or an error
+fn parse_query_time(time: &str, name: &str) -> Result<DateTime<Utc>, DBReadError> {
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| DBReadError::BadData(format!("Couldn't format {}: {}", name, e)))
+}
+
+/// Make an opaque cursor pointing after a task
+/// The cursor is the hex encoded JSON of the sort field, the tasks sort value and the tasks id so it is safe to put in a URL
+/// 
+/// # Arguments
+/// * `task` - The last task of a page
+/// * `sort` - The field the page is sorted by
+/// 
+/// # Returns
+/// * `String` - The cursor
+fn encode_cursor(task: &ToDoTask, sort: TaskSort) -> String {
+    let value = match sort {
+        TaskSort::CreatedAt => task.created_at.clone(),
+        TaskSort::CompletedAt => task.completed_at.clone(),
+        TaskSort::Title => task.title.clone(),
+        TaskSort::DueAt => task.due_at.clone(),
+    };
+    let id = task.id.as_ref().map(|id| id.id.to_raw()).unwrap_or_default();
+
+    serde_json::json!([sort, value, id])
+        .to_string()
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Read a cursor made by `encode_cursor`
+/// 
+/// # Arguments
+/// * `cursor` - The cursor from the query
+/// * `sort` - The field the page is sorted by, this must match the cursor
+/// 
+/// # Returns
+/// * `Result<(Value, Value), DBReadError>` - The sort value and id to start after, or an error if the cursor is invalid
+fn decode_cursor(cursor: &str, sort: TaskSort) -> Result<(Value, Value), DBReadError> {
+    let invalid = || DBReadError::BadData("Invalid cursor".to_string());
+
+    // Hex -> JSON
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    let (cursor_sort, value, id): (TaskSort, Option<String>, String) = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+    // A cursor from a page with a different sort order would skip or repeat tasks
+    if cursor_sort != sort {
+        return Err(DBReadError::BadData("The cursor was made with a different sort".to_string()));
+    }
+
+    let value = match (sort, value) {
+        (_, None) => Value::None,
+        (TaskSort::Title, Some(v)) => Value::from(v),
+        (_, Some(v)) => {
+            let time = DateTime::parse_from_rfc3339(&v).map_err(|_| invalid())?;
+            Value::Datetime(sdbDateTime::from(time.with_timezone(&Utc)))
+        },
+    };
+    let id: Value = Thing::from(("ToDoTask", id.as_str())).into();
+
+    Ok((value, id))
 }
 
 /// Edit a task in the database by id
+/// Completing a recurring task records the completion and moves the task on to its next occurrence instead,
+/// the task is only completed once its recurrence has finished
 /// 
 /// # Arguments
 /// * `id` - The id of the task to edit
-/// * `title` - The new title of the task
-/// * `description` - The new description of the task
-/// * `completed_at` - The new time the task was completed
+/// * `task` - The fields to change, fields which are None are left as they are. `created_at` can't be changed
 /// * `owner` - The new owner of the task
+/// * `version` - Only edit the task if it is still this version
+/// * `actor_id` - The id of the user making the change, for the activity log
 /// 
 /// # Returns
-/// * `Result<ToDoTask, DBEditError>` - The edited task or an error
+/// * `Result<ToDoTask, DBEditError>` - The edited task or an error, `NotFound` if the task has a different version
 pub async fn edit_task_by_id(
     id: &str,
-    title: Option<&str>,
-    description: Option<&str>,
-    completed_at: Option<&str>,
+    task: &TaskRequest,
     owner: Option<&str>,
+    version: Option<i64>,
+    actor_id: &str,
 ) -> Result<ToDoTask, DBEditError> {
 
     let mut sql = String::from("UPDATE $id SET ");
 
-    // Convert the times to a chrono::DateTime<Utc>, leaving None untouched
-    let completed_at: Option<DateTime<Utc>> = match completed_at {
-        Some(c) => {
-            Some(DateTime::parse_from_rfc3339(c)
-                .map_err(|e| {
-                    DBEditError::BadData(format!("Couldn't format completed_at: {}", e.to_string()))
-                })?
-                .with_timezone(&Utc))
-        },
-        None => None,
-    };
-
-    // Take each value and make it a surrealdb::sql::value, if optional values are None then we set them to Value::Null
+    // Take each value and make it a surrealdb::sql::value, if optional values are None then we set them to Value::None
     // I do this so i dont have to cast the type in the SQL statement, because that causes problems if the value is None
     // This lets me keep the actual SQL as simple as possible
     // This is the same as in create_task but we dont need created_at here
     // Also create the sql string here depending on what parameters are passed in 
-    let title = match title {
+    let title = match task.title.as_deref() {
         Some(t) => {
             sql.push_str("title = $title, ");
             Value::from(t)
         },
         None => Value::None,
     };
-    let description = match description {
+    let description = match task.description.as_deref() {
         Some(d) => {
             sql.push_str("description = $description, ");
             Value::from(d)
         },
         None => Value::None,
     };
-    let completed_at = match completed_at {
-        Some(c) => {
-            sql.push_str("completed_at = $completed_at, ");
-            Value::Datetime(sdbDateTime::from(c))
+    let mut due_at = parse_time(task.due_at.as_deref(), "due_at").map_err(DBEditError::BadData)?;
+    let mut remind_at = parse_time(task.remind_at.as_deref(), "remind_at").map_err(DBEditError::BadData)?;
+    let completed_at = parse_time(task.completed_at.as_deref(), "completed_at").map_err(DBEditError::BadData)?;
+    let recurrence = parse_recurrence(task.recurrence.as_deref()).map_err(DBEditError::BadData)?;
+    if task.recurrence.is_some() {
+        sql.push_str("recurrence = $recurrence, ");
+    }
+    // The parent must belong to the owner of the task, the project only has to be one the user making the change can edit
+    let owner_id = match (owner, task.parent.as_deref(), task.project.as_deref()) {
+        (Some(o), _, _) => o.to_string(),
+        (None, Some(p), _) | (None, _, Some(p)) if !p.is_empty() && p != INBOX => get_task_by_id(id).await
+            .map_err(|e| DBEditError::NotFound(e.to_string()))?
+            .owner
+            .map(|o| o.id.to_raw())
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    let parent = match task.parent.as_deref() {
+        Some("") => {
+            sql.push_str("parent = NONE, ");
+            Value::None
+        },
+        Some(p) => {
+            // A subtask must belong to the same user and can't make a cycle
+            check_parent(Some(id), p, &owner_id).await.map_err(|e| match e {
+                DBReadError::NotFound(msg) | DBReadError::BadData(msg) => DBEditError::BadData(msg),
+                DBReadError::Other(msg) => DBEditError::Other(msg),
+            })?;
+            sql.push_str("parent = $parent, ");
+            Value::Thing(Thing::from(("ToDoTask", p)))
+        },
+        None => Value::None,
+    };
+    let project = match task.project.as_deref() {
+        Some("") | Some(INBOX) => {
+            sql.push_str("project = NONE, ");
+            Value::None
+        },
+        Some(p) => {
+            check_task_project(p, actor_id).await.map_err(|e| match e {
+                DBReadError::NotFound(msg) | DBReadError::BadData(msg) => DBEditError::BadData(msg),
+                DBReadError::Other(msg) => DBEditError::Other(msg),
+            })?;
+            sql.push_str("project = $project, ");
+            Value::Thing(Thing::from(("Project", p)))
         },
         None => Value::None,
     };
@@ -235,11 +577,108 @@ pub async fn edit_task_by_id(
         None => Value::None,
     };
 
+    // Completing a recurring task moves it on to the next occurrence rather than setting completed_at
+    let mut completed_occurrences = Value::None;
+    let mut occurrence_due_at = Value::None;
+    let recurring = if let Value::Datetime(completed_time) = &completed_at {
+        let existing = get_task_by_id(id).await.map_err(|e| match e {
+            DBReadError::NotFound(msg) => DBEditError::NotFound(msg),
+            DBReadError::BadData(msg) => DBEditError::BadData(msg),
+            DBReadError::Other(msg) => DBEditError::Other(msg),
+        })?;
+        // The completion is recorded as well as the update, so the version is checked first
+        if version.is_some() && existing.version != version {
+            return Err(DBEditError::NotFound("The task has a different version".to_string()));
+        }
+
+        // A rule in this request replaces the existing one
+        let rule = match &recurrence {
+            Value::Strand(r) => Some(r.as_str().to_string()),
+            _ if task.recurrence.is_some() => None,
+            _ => existing.recurrence.clone(),
+        };
+
+        match rule {
+            Some(rule) => {
+                // This has already been validated when it was stored
+                let rule = RecurrenceRule::parse(&rule).map_err(DBEditError::Other)?;
+                let completed = existing.completed_occurrences.unwrap_or_default() + 1;
+
+                // The occurrence being completed, or the time it was completed if it had no due date
+                let previous_due = match &due_at {
+                    Value::Datetime(d) => Some(d.0),
+                    _ => existing.due_at.as_deref().and_then(|d| DateTime::parse_from_rfc3339(d).ok()).map(|d| d.with_timezone(&Utc)),
+                };
+                let previous_remind = match &remind_at {
+                    Value::Datetime(r) => Some(r.0),
+                    _ => existing.remind_at.as_deref().and_then(|r| DateTime::parse_from_rfc3339(r).ok()).map(|r| r.with_timezone(&Utc)),
+                };
+                let base = previous_due.unwrap_or(completed_time.0);
+                occurrence_due_at = previous_due.map(|d| Value::Datetime(sdbDateTime::from(d))).unwrap_or(Value::None);
+                completed_occurrences = Value::from(completed);
+
+                match rule.next_occurrence(base, u32::try_from(completed).unwrap_or(u32::MAX)) {
+                    Some(next) => {
+                        // The reminder keeps the same offset from the due date
+                        due_at = Value::Datetime(sdbDateTime::from(next));
+                        remind_at = match previous_remind {
+                            Some(r) => Value::Datetime(sdbDateTime::from(next + (r - base))),
+                            None => Value::None,
+                        };
+                        sql.push_str("due_at = $due_at, remind_at = $remind_at, completed_occurrences = $completed_occurrences, ");
+                    },
+                    None => {
+                        // The recurrence has finished so this is the last occurrence
+                        sql.push_str("completed_at = $completed_at, completed_occurrences = $completed_occurrences, ");
+                        if task.due_at.is_some() {
+                            sql.push_str("due_at = $due_at, ");
+                        }
+                        if task.remind_at.is_some() {
+                            sql.push_str("remind_at = $remind_at, ");
+                        }
+                    },
+                }
+                true
+            },
+            None => false,
+        }
+    } else {
+        false
+    };
+    if !recurring {
+        if task.completed_at.is_some() {
+            sql.push_str("completed_at = $completed_at, ");
+        }
+        if task.due_at.is_some() {
+            sql.push_str("due_at = $due_at, ");
+        }
+        if task.remind_at.is_some() {
+            sql.push_str("remind_at = $remind_at, ");
+        }
+    }
+
+    // Check something is being changed as this will create an invalid SQL statement
+    if !sql.ends_with(", ") {
+        return Err(DBEditError::BadData("Nothing to change".to_string()));
+    }
+
     // Remove the last comma and space from the SQL string
     sql.pop();
     sql.pop();
-    // Add a semicolon to the end of the SQL string 
-    sql.push_str(";");
+    // Tasks in the trash can't be edited
+    sql.push_str(" WHERE deleted_at = NONE");
+    if version.is_some() {
+        sql.push_str(" AND version = $version");
+    }
+    sql.push(';');
+
+    // Record the completed occurrence in the same transaction
+    if recurring {
+        sql = format!(
+            "BEGIN TRANSACTION; {} CREATE TaskCompletion SET task = $id, due_at = $occurrence_due_at, completed_at = $completed_at; COMMIT TRANSACTION;",
+            sql
+        );
+    }
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to case anything in the SQL
@@ -251,14 +690,28 @@ pub async fn edit_task_by_id(
         .bind(("title", title))
         .bind(("description", description)) 
         .bind(("completed_at", completed_at))
+        .bind(("due_at", due_at))
+        .bind(("remind_at", remind_at))
+        .bind(("recurrence", recurrence))
+        .bind(("completed_occurrences", completed_occurrences))
+        .bind(("occurrence_due_at", occurrence_due_at))
+        .bind(("parent", parent))
+        .bind(("project", project))
         .bind(("owner", owner))
+        .bind(("version", version.map(Value::from).unwrap_or(Value::None)))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
     let result: Option<ToDoTask> = response
         .take(0)
         .map_err(|e| {
-            DBEditError::Other(e.to_string())
+            // The schema rejects a reminder after the due date
+            if e.to_string().contains("`remind_at`") {
+                DBEditError::BadData("remind_at can't be after due_at".to_string())
+            } else {
+                DBEditError::Other(e.to_string())
+            }
         })?;
 
     let result = result.ok_or_else(|| {
@@ -268,19 +721,77 @@ pub async fn edit_task_by_id(
     Ok(result)
 }
 
-/// Delete a task from the database by id
+/// Get the completed occurrences of a recurring task
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task
+/// 
+/// # Returns
+/// * `Result<Vec<TaskCompletion>, DBReadError>` - The completions, most recent first, or an error
+pub async fn get_task_completions(
+    task_id: &str,
+) -> Result<Vec<TaskCompletion>, DBReadError> {
+
+    let sql = "SELECT * FROM TaskCompletion WHERE task = $task ORDER BY completed_at DESC;";
+
+    // Convert the id to a surrealdb::sql::value
+    let task: Value = Thing::from(("ToDoTask", task_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("task", task))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<TaskCompletion> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Move a task to the trash by id
+/// Subtasks moved to the trash with the task get the same `deleted_at` so they are restored with it
 /// 
 /// # Arguments
 /// * `id` - The id of the task to delete
+/// * `policy` - What to do with the subtasks of the task
+/// * `actor_id` - The id of the user deleting the task, for the activity log
 /// 
 /// # Returns
-/// * `Result<ToDoTask, DBReadError>` - The deleted task or an error
+/// * `Result<ToDoTask, DBReadError>` - The task in the trash or an error, `BadData` if the policy is `Restrict` and the task has subtasks
 pub async fn delete_task_by_id(
     id: &str,
+    policy: SubtaskDeletePolicy,
+    actor_id: &str,
 ) -> Result<ToDoTask, DBReadError> {
 
+    let mut sql = String::from("
+    BEGIN TRANSACTION;
+    UPDATE $id SET deleted_at = $deleted_at WHERE deleted_at = NONE;
+    ");
 
-    let sql = "DELETE ONLY $id RETURN BEFORE;";
+    // Decide what happens to the subtasks
+    let mut parent = Value::None;
+    let mut descendants = Value::None;
+    match policy {
+        SubtaskDeletePolicy::Cascade => {
+            let tasks = get_descendants(id).await?;
+            descendants = Value::from(tasks.into_iter().filter_map(|t| t.id).map(Value::from).collect::<Vec<Value>>());
+            sql.push_str("UPDATE ToDoTask SET deleted_at = $deleted_at WHERE id IN $descendants;");
+        },
+        SubtaskDeletePolicy::Promote => {
+            parent = get_task_by_id(id).await?.parent.map(Value::from).unwrap_or(Value::None);
+            sql.push_str("UPDATE ToDoTask SET parent = $parent WHERE parent = $id AND deleted_at = NONE;");
+        },
+        SubtaskDeletePolicy::Restrict => {
+            if !get_subtasks(id).await?.is_empty() {
+                return Err(DBReadError::BadData("The task has subtasks".to_string()));
+            }
+        },
+    }
+    sql.push_str(" COMMIT TRANSACTION;");
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to case anything in the SQL
@@ -289,6 +800,10 @@ pub async fn delete_task_by_id(
 
     let mut response = DB.query(sql)
         .bind(("id", id))
+        .bind(("parent", parent))
+        .bind(("descendants", descendants))
+        .bind(("deleted_at", Value::Datetime(sdbDateTime::from(Utc::now()))))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
     
@@ -305,6 +820,488 @@ pub async fn delete_task_by_id(
     Ok(result)
 }
 
+/// Get a task in the trash by id
+/// 
+/// # Arguments
+/// * `id` - The id of the task
+/// 
+/// # Returns
+/// * `Result<ToDoTask, DBReadError>` - The task or an error, `NotFound` if it isn't in the trash
+pub async fn get_trashed_task_by_id(
+    id: &str,
+) -> Result<ToDoTask, DBReadError> {
+
+    let sql = "SELECT * FROM $id WHERE deleted_at != NONE;";
+
+    let id: Value = Thing::from(("ToDoTask", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("The task isn't in the trash".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the tasks of a user in the trash
+/// Subtasks moved to the trash with their parent are left out, they are restored and deleted with it
+/// 
+/// # Arguments
+/// * `user_id` - The id of the user
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The tasks, most recently deleted first, or an error
+pub async fn get_trash(
+    user_id: &str,
+) -> Result<Vec<ToDoTask>, DBReadError> {
+
+    let sql = "
+    SELECT * FROM ToDoTask
+    WHERE owner = $owner AND deleted_at != NONE AND (parent = NONE OR parent.deleted_at = NONE OR parent.deleted_at != deleted_at)
+    ORDER BY deleted_at DESC, id;
+    ";
+
+    let owner: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("owner", owner))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Restore a task from the trash with the subtasks which were deleted with it
+/// If its parent is still in the trash or gone the task becomes a top level task
+/// 
+/// # Arguments
+/// * `id` - The id of the task
+/// * `actor_id` - The id of the user restoring the task, for the activity log
+/// 
+/// # Returns
+/// * `Result<ToDoTask, DBEditError>` - The restored task or an error, `NotFound` if it isn't in the trash
+pub async fn restore_task_by_id(
+    id: &str,
+    actor_id: &str,
+) -> Result<ToDoTask, DBEditError> {
+
+    let sql = "
+    BEGIN TRANSACTION;
+    UPDATE ToDoTask SET deleted_at = NONE WHERE id IN $descendants AND deleted_at = $deleted_at;
+    UPDATE $id SET deleted_at = NONE, parent = $parent;
+    COMMIT TRANSACTION;
+    ";
+
+    let task = get_trashed_task_by_id(id).await.map_err(|e| match e {
+        DBReadError::NotFound(msg) => DBEditError::NotFound(msg),
+        DBReadError::BadData(msg) | DBReadError::Other(msg) => DBEditError::Other(msg),
+    })?;
+
+    // The subtasks deleted at the same time as the task
+    let descendants = collect_descendants(id, true).await.map_err(|e| DBEditError::Other(e.to_string()))?;
+    let descendants = Value::from(descendants.into_iter().filter_map(|t| t.id).map(Value::from).collect::<Vec<Value>>());
+    let deleted_at = task.deleted_at.as_deref()
+        .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+        .map(|d| Value::Datetime(sdbDateTime::from(d.with_timezone(&Utc))))
+        .unwrap_or(Value::None);
+
+    // Keep the parent only if it is still there
+    let parent = match &task.parent {
+        Some(p) if get_task_by_id(&p.id.to_raw()).await.is_ok() => Value::Thing(p.clone()),
+        _ => Value::None,
+    };
+
+    let id: Value = Thing::from(("ToDoTask", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("descendants", descendants))
+        .bind(("deleted_at", deleted_at))
+        .bind(("parent", parent))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<ToDoTask> = response
+        .take(1)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to restore task".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Delete tasks permanently with everything attached to them
+/// The completion history, notifications and comments are removed, the tag and share edges go with the tasks
+/// A tombstone is left for the owner of each task and a removal for everyone else who could see it, so synced clients remove it
+/// The activity log records who purged it, `$actor` is NONE for the trash purge
+const PURGE_TASKS_SQL: &str = "
+BEGIN TRANSACTION;
+INSERT INTO TaskTombstone (SELECT id AS task, owner FROM ToDoTask WHERE id IN $tasks);
+LET $viewers = SELECT id, fn::task_viewers($this) AS users FROM ToDoTask WHERE id IN $tasks;
+DELETE TaskCompletion WHERE task IN $tasks;
+DELETE Notification WHERE task IN $tasks;
+DELETE CommentEdit WHERE comment.task IN $tasks;
+DELETE Comment WHERE task IN $tasks;
+DELETE ToDoTask WHERE id IN $tasks;
+FOR $task IN $viewers {
+    fn::remove_task_for($task.id, $task.users);
+};
+COMMIT TRANSACTION;
+";
+
+/// Delete a task in the trash permanently by id, with all of its subtasks
+/// 
+/// # Arguments
+/// * `id` - The id of the task
+/// * `actor_id` - The id of the user deleting the task, for the activity log
+/// 
+/// # Returns
+/// * `Result<ToDoTask, DBReadError>` - The deleted task or an error, `NotFound` if it isn't in the trash
+pub async fn purge_task_by_id(
+    id: &str,
+    actor_id: &str,
+) -> Result<ToDoTask, DBReadError> {
+
+    let task = get_trashed_task_by_id(id).await?;
+
+    let mut tasks: Vec<Value> = vec![Thing::from(("ToDoTask", id)).into()];
+    tasks.extend(collect_descendants(id, true).await?.into_iter().filter_map(|t| t.id).map(Value::from));
+
+    DB.query(PURGE_TASKS_SQL)
+        .bind(("tasks", Value::from(tasks)))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
+        .await
+        .unwrap() // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+        .check()
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(task)
+}
+
+/// Permanently delete every task which has been in the trash for longer than the retention window
+/// 
+/// # Arguments
+/// * `retention` - How long tasks stay in the trash
+/// 
+/// # Returns
+/// * `Result<usize, DBReadError>` - How many tasks were deleted, or an error
+pub async fn purge_trash(
+    retention: chrono::Duration,
+) -> Result<usize, DBReadError> {
+
+    let before = Utc::now().checked_sub_signed(retention).ok_or_else(|| {
+        DBReadError::BadData("The retention is too large".to_string())
+    })?;
+
+    let mut response = DB.query("SELECT VALUE id FROM ToDoTask WHERE deleted_at != NONE AND deleted_at < $before;")
+        .bind(("before", Value::Datetime(sdbDateTime::from(before))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+    let tasks: Vec<Thing> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    if tasks.is_empty() {
+        return Ok(0);
+    }
+    let count = tasks.len();
+
+    DB.query(PURGE_TASKS_SQL)
+        .bind(("tasks", Value::from(tasks.into_iter().map(Value::Thing).collect::<Vec<Value>>())))
+        .await
+        .unwrap() // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+        .check()
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(count)
+}
+
+/// Purge the trash forever, this is started in the background when the server launches
+/// 
+/// # Arguments
+/// * `retention` - How long tasks stay in the trash
+/// * `every` - How long to wait between purges
+pub async fn purge_trash_periodically(retention: chrono::Duration, every: std::time::Duration) {
+    let mut interval = tokio::time::interval(every);
+    loop {
+        interval.tick().await;
+        match purge_trash(retention).await {
+            Ok(0) => {},
+            Ok(count) => println!("Purged {} tasks from the trash", count),
+            Err(err) => println!("Failed to purge the trash: {:?}", err),
+        }
+    }
+}
+
+/// How deep subtasks can be nested, this also stops a corrupted hierarchy being followed forever
+const MAX_TASK_DEPTH: usize = 32;
+
+/// Check a task can be made a subtask of a parent
+/// The parent must exist, belong to the same user, and not be the task or one of its subtasks
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task being moved, None if it is being created
+/// * `parent_id` - The id of the new parent
+/// * `owner_id` - The id of the user that owns the task
+/// 
+/// # Returns
+/// * `Result<(), DBReadError>` - Nothing if the parent is allowed, or an error explaining why not
+async fn check_parent(task_id: Option<&str>, parent_id: &str, owner_id: &str) -> Result<(), DBReadError> {
+    let not_found = || DBReadError::NotFound("Parent task not found".to_string());
+
+    let parent = get_task_by_id(parent_id).await.map_err(|e| match e {
+        DBReadError::NotFound(_) => not_found(),
+        e => e,
+    })?;
+    if parent.owner.as_ref().map(|o| o.id.to_raw()).as_deref() != Some(owner_id) {
+        return Err(not_found());
+    }
+
+    // Walk up from the parent, if the task is found the move would make a cycle
+    let mut current = parent;
+    for _ in 0..MAX_TASK_DEPTH {
+        if task_id.is_some() && current.id.as_ref().map(|i| i.id.to_raw()).as_deref() == task_id {
+            return Err(DBReadError::BadData("A task can't be a subtask of itself or one of its subtasks".to_string()));
+        }
+        current = match current.parent {
+            Some(p) => get_task_by_id(&p.id.to_raw()).await?,
+            None => return Ok(()),
+        };
+    }
+
+    Err(DBReadError::BadData(format!("Subtasks can't be nested more than {} deep", MAX_TASK_DEPTH)))
+}
+
+/// Get the direct subtasks of a task
+/// 
+/// # Arguments
+/// * `task_id` - The id of the parent task
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The subtasks, oldest first, or an error
+pub async fn get_subtasks(
+    task_id: &str,
+) -> Result<Vec<ToDoTask>, DBReadError> {
+
+    let sql = "SELECT * FROM ToDoTask WHERE parent = $parent AND deleted_at = NONE ORDER BY created_at, id;";
+
+    // Convert the id to a surrealdb::sql::value
+    let parent: Value = Thing::from(("ToDoTask", task_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("parent", parent))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
+
+/// Get every task below a task which isn't in the trash
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task at the top
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The subtasks at every level, or an error
+pub async fn get_descendants(task_id: &str) -> Result<Vec<ToDoTask>, DBReadError> {
+    collect_descendants(task_id, false).await
+}
+
+/// Get every task below a task, one level of subtasks at a time
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task at the top
+/// * `include_deleted` - Whether to include the subtasks in the trash
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The subtasks at every level, or an error
+async fn collect_descendants(task_id: &str, include_deleted: bool) -> Result<Vec<ToDoTask>, DBReadError> {
+    let sql = if include_deleted {
+        "SELECT * FROM ToDoTask WHERE parent IN $parents ORDER BY created_at, id;"
+    } else {
+        "SELECT * FROM ToDoTask WHERE parent IN $parents AND deleted_at = NONE ORDER BY created_at, id;"
+    };
+
+    let mut result = Vec::new();
+    let mut level: Vec<Value> = vec![Thing::from(("ToDoTask", task_id)).into()];
+
+    for _ in 0..MAX_TASK_DEPTH {
+        if level.is_empty() {
+            break;
+        }
+
+        let mut response = DB.query(sql)
+            .bind(("parents", Value::from(level)))
+            .await
+            .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+        let tasks: Vec<ToDoTask> = response
+            .take(0)
+            .map_err(|e| {
+                DBReadError::Other(e.to_string())
+            })?;
+
+        level = tasks.iter().filter_map(|t| t.id.clone()).map(Value::from).collect();
+        result.extend(tasks);
+    }
+
+    Ok(result)
+}
+
+/// Get a task with all of its subtasks and how many of each tasks subtasks are completed
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task at the top
+/// 
+/// # Returns
+/// * `Result<TaskTree, DBReadError>` - The task and its subtasks or an error
+pub async fn get_task_tree(
+    task_id: &str,
+) -> Result<TaskTree, DBReadError> {
+
+    let task = get_task_by_id(task_id).await?;
+
+    // Group the subtasks by their parent so the tree can be built from the top
+    let mut by_parent: HashMap<String, Vec<ToDoTask>> = HashMap::new();
+    for subtask in get_descendants(task_id).await? {
+        if let Some(parent) = &subtask.parent {
+            by_parent.entry(parent.id.to_raw()).or_default().push(subtask);
+        }
+    }
+
+    Ok(build_tree(task, &mut by_parent))
+}
+
+/// Build the tree of a task from its subtasks grouped by parent
+/// 
+/// # Arguments
+/// * `task` - The task at the top of this tree
+/// * `by_parent` - The subtasks grouped by the id of their parent, these are taken out as they are used
+/// 
+/// # Returns
+/// * `TaskTree` - The task and its subtasks
+fn build_tree(task: ToDoTask, by_parent: &mut HashMap<String, Vec<ToDoTask>>) -> TaskTree {
+    let id = task.id.as_ref().map(|i| i.id.to_raw()).unwrap_or_default();
+    let children = by_parent.remove(&id).unwrap_or_default();
+
+    let progress = TaskProgress {
+        completed: children.iter().filter(|c| c.completed_at.is_some()).count(),
+        total: children.len(),
+    };
+    let subtasks = children.into_iter().map(|c| build_tree(c, by_parent)).collect();
+
+    TaskTree { task, progress, subtasks }
+}
+
+/// Assign a task to a user, or unassign it
+/// 
+/// # Arguments
+/// * `task_id` - The id of the task
+/// * `assignee_id` - The id of the user to assign the task to, None to unassign it
+/// * `actor_id` - The id of the user making the change, for the activity log
+/// 
+/// # Returns
+/// * `Result<ToDoTask, DBEditError>` - The edited task or an error
+pub async fn set_assignee(
+    task_id: &str,
+    assignee_id: Option<&str>,
+    actor_id: &str,
+) -> Result<ToDoTask, DBEditError> {
+
+    let sql = "UPDATE $id SET assignee = $assignee WHERE deleted_at = NONE;";
+
+    // Convert the ids to surrealdb::sql::values
+    let id: Value = Thing::from(("ToDoTask", task_id)).into();
+    let assignee = match assignee_id {
+        Some(a) => Value::Thing(Thing::from(("User", a))),
+        None => Value::None,
+    };
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .bind(("assignee", assignee))
+        .bind(("actor", Value::Thing(Thing::from(("User", actor_id)))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBEditError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBEditError::NotFound("Failed to get task".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get the tasks assigned to a user which are not completed
+/// 
+/// # Arguments
+/// * `user_id` - The id of the user
+/// 
+/// # Returns
+/// * `Result<Vec<ToDoTask>, DBReadError>` - The tasks, soonest due first with tasks without a due date last, or an error
+pub async fn get_tasks_assigned_to(
+    user_id: &str,
+) -> Result<Vec<ToDoTask>, DBReadError> {
+
+    let sql = "
+    SELECT *, due_at = NONE AS undated FROM ToDoTask
+    WHERE assignee = $assignee AND deleted_at = NONE AND completed_at = NONE
+    ORDER BY undated, due_at, created_at;
+    ";
+
+    // Convert the id to a surrealdb::sql::value
+    let assignee: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("assignee", assignee))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Vec<ToDoTask> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    Ok(result)
+}
 
 /// Check if the requester is the owner of the task
 /// 
@@ -315,7 +1312,7 @@ pub async fn delete_task_by_id(
 /// # Returns
 /// * `Result<bool, DBReadError>` - True if the requester is the owner of the task, false otherwise, or an error 
 pub async fn check_is_owner(requester_id: &str, task_id: &str) -> Result<bool, DBReadError> {
-    let sql = "SELECT owner FROM $id;";
+    let sql = "SELECT owner FROM $id WHERE deleted_at = NONE;";
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to cast anything in the SQL
@@ -0,0 +1,418 @@
+use std::{collections::{HashMap, HashSet}, sync::{LazyLock, RwLock}, time::Instant};
+
+use chrono::{DateTime, Duration, Utc};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use surrealdb::sql::{Value, Datetime as sdbDateTime, Thing};
+
+use crate::model::tokens::RefreshToken;
+use super::{DBCreateError, DBEditError, DBReadError, DBTokenError, DB};
+
+/// How long the revocation state of a user is cached for before it is read from the database again
+/// This bounds how long a revocation made by another instance of the backend can go unnoticed
+const REVOCATION_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+/// The cached revocation state of a user
+/// 
+/// # Fields
+/// * `generation` - The current token generation of the user, tokens from older generations are revoked
+/// * `revoked` - The jti of every revoked token of the user which has not expired yet
+/// * `fetched_at` - When this was read from the database
+struct RevocationState {
+    generation: i64,
+    revoked: HashSet<String>,
+    fetched_at: Instant,
+}
+
+/// The in memory cache of revocation state, keyed by user id
+/// A user missing from the database is cached as None
+static REVOCATION_CACHE: LazyLock<RwLock<HashMap<String, Option<RevocationState>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Generate a random 256 bit token as hex
+///
+/// # Returns
+/// `String` - The random token
+pub fn generate_random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hash a refresh token for storing in or looking up from the database
+/// The tokens are random so a fast hash is enough, unlike passwords
+///
+/// # Arguments
+/// * `token` - The refresh token to hash
+///
+/// # Returns
+/// `String` - The SHA-256 hash of the token as hex
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Create a new refresh token for a user
+///
+/// # Arguments
+/// * `user_id` - The id of the user the token belongs to
+/// * `family` - The family to add the token to, if None then a new family is started
+/// * `duration` - How long the token is valid for
+///
+/// # Returns
+/// `Result<String, DBCreateError>` - The refresh token to give to the client, or an error
+pub async fn create_refresh_token(user_id: &str, family: Option<&str>, duration: Duration) -> Result<String, DBCreateError> {
+    let sql = "
+    CREATE RefreshToken SET
+    user = $user,
+    family = $family,
+    token_hash = $token_hash,
+    expires_at = $expires_at;
+    ";
+
+    let token = generate_random_token();
+
+    // A new log in starts a new family
+    let family = match family {
+        Some(f) => f.to_string(),
+        None => generate_random_token(),
+    };
+
+    let expires_at = Utc::now()
+        .checked_add_signed(duration)
+        .ok_or_else(|| DBCreateError::BadData("Invalid refresh token duration".to_string()))?;
+
+    // Convert the inputs
+    let user: Value = Thing::from(("User", user_id)).into();
+    let family = Value::from(family);
+    let token_hash = Value::from(hash_token(&token));
+    let expires_at = Value::Datetime(sdbDateTime::from(expires_at));
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .bind(("family", family))
+        .bind(("token_hash", token_hash))
+        .bind(("expires_at", expires_at))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<RefreshToken> = response
+        .take(0)
+        .map_err(|e| {
+            DBCreateError::Other(e.to_string())
+        })?;
+
+    if result.is_none() {
+        return Err(DBCreateError::Other("Failed to create refresh token".to_string()));
+    }
+
+    Ok(token)
+}
+
+/// Rotate a refresh token, marking it as used and creating a new one in the same family
+/// If the token has already been used the whole family is revoked, as the token must have been stolen
+///
+/// # Arguments
+/// * `token` - The refresh token given by the client
+/// * `duration` - How long the new token is valid for
+///
+/// # Returns
+/// `Result<(String, String), DBTokenError>` - The id of the user and the new refresh token, or an error
+pub async fn rotate_refresh_token(token: &str, duration: Duration) -> Result<(String, String), DBTokenError> {
+    // Mark the token as used, only if it is still valid
+    // Doing this in one statement means two requests with the same token cannot both succeed
+    let sql = "
+    UPDATE RefreshToken SET used_at = time::now()
+    WHERE token_hash = $token_hash
+    AND used_at = NONE
+    AND revoked_at = NONE
+    AND expires_at > time::now()
+    RETURN BEFORE;
+    ";
+
+    let token_hash = hash_token(token);
+
+    let mut response = DB.query(sql)
+        .bind(("token_hash", Value::from(token_hash.clone())))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<RefreshToken> = response
+        .take(0)
+        .map_err(|e| {
+            DBTokenError::Other(e.to_string())
+        })?;
+
+    // If nothing was updated find out why
+    let result = match result {
+        Some(r) => r,
+        None => return Err(check_invalid_token(&token_hash).await),
+    };
+
+    let user_id = result.user
+        .ok_or_else(|| DBTokenError::Other("Refresh token has no user".to_string()))?
+        .id
+        .to_string();
+    let family = result.family
+        .ok_or_else(|| DBTokenError::Other("Refresh token has no family".to_string()))?;
+
+    // Create the next token in the family
+    let new_token = create_refresh_token(&user_id, Some(&family), duration)
+        .await
+        .map_err(|e| DBTokenError::Other(e.to_string()))?;
+
+    Ok((user_id, new_token))
+}
+
+/// Work out why a refresh token could not be rotated, revoking its family if it has been reused
+///
+/// # Arguments
+/// * `token_hash` - The hash of the refresh token
+///
+/// # Returns
+/// `DBTokenError` - The reason the token is invalid
+async fn check_invalid_token(token_hash: &str) -> DBTokenError {
+    let sql = "SELECT * FROM RefreshToken WHERE token_hash = $token_hash;";
+
+    let mut response = DB.query(sql)
+        .bind(("token_hash", Value::from(token_hash)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<RefreshToken> = match response.take(0) {
+        Ok(r) => r,
+        Err(e) => return DBTokenError::Other(e.to_string()),
+    };
+
+    let result = match result {
+        Some(r) => r,
+        None => return DBTokenError::NotFound("Refresh token not found".to_string()),
+    };
+
+    // A used or revoked token being presented again means it has been replayed
+    if result.used_at.is_some() || result.revoked_at.is_some() {
+        if let Some(family) = result.family
+            && let Err(e) = revoke_token_family(&family).await
+        {
+            return e;
+        }
+        return DBTokenError::Reused("Refresh token has already been used".to_string());
+    }
+
+    DBTokenError::Expired("Refresh token has expired".to_string())
+}
+
+/// Revoke every refresh token in a family
+///
+/// # Arguments
+/// * `family` - The family to revoke
+///
+/// # Returns
+/// `Result<(), DBTokenError>` - Nothing or an error
+pub async fn revoke_token_family(family: &str) -> Result<(), DBTokenError> {
+    let sql = "UPDATE RefreshToken SET revoked_at = time::now() WHERE family = $family AND revoked_at = NONE;";
+
+    let mut response = DB.query(sql)
+        .bind(("family", Value::from(family)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let _: Vec<RefreshToken> = response
+        .take(0)
+        .map_err(|e| {
+            DBTokenError::Other(e.to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Revoke every refresh token in the family of the given refresh token
+///
+/// # Arguments
+/// * `token` - The refresh token given by the client
+///
+/// # Returns
+/// `Result<(), DBTokenError>` - Nothing or an error
+pub async fn revoke_refresh_token(token: &str) -> Result<(), DBTokenError> {
+    let sql = "SELECT * FROM RefreshToken WHERE token_hash = $token_hash;";
+
+    let mut response = DB.query(sql)
+        .bind(("token_hash", Value::from(hash_token(token))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let result: Option<RefreshToken> = response
+        .take(0)
+        .map_err(|e| {
+            DBTokenError::Other(e.to_string())
+        })?;
+
+    let family = result
+        .and_then(|r| r.family)
+        .ok_or_else(|| DBTokenError::NotFound("Refresh token not found".to_string()))?;
+
+    revoke_token_family(&family).await
+}
+
+/// Read the revocation state of a user, from the cache if it is fresh enough
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+///
+/// # Returns
+/// `Result<Option<RevocationState>, DBReadError>` - The state, None if the user does not exist, or an error
+async fn get_revocation_state(user_id: &str) -> Result<Option<RevocationState>, DBReadError> {
+    // Use the cached state if it is fresh enough
+    // Its okay to unwrap the lock as it is only poisoned if a thread panicked while holding it
+    if let Some(state) = REVOCATION_CACHE.read().unwrap().get(user_id) {
+        match state {
+            Some(s) if s.fetched_at.elapsed() < REVOCATION_CACHE_TTL => return Ok(Some(s.clone())),
+            None => return Ok(None),
+            _ => {}
+        }
+    }
+
+    let sql = "
+    SELECT VALUE token_generation FROM ONLY $user;
+    SELECT VALUE jti FROM RevokedToken WHERE user = $user AND expires_at > time::now();
+    ";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let generation: Option<i64> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+    let revoked: Vec<String> = response
+        .take(1)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let state = generation.map(|generation| RevocationState {
+        generation,
+        revoked: revoked.into_iter().collect(),
+        fetched_at: Instant::now(),
+    });
+
+    REVOCATION_CACHE.write().unwrap().insert(user_id.to_string(), state.clone());
+
+    Ok(state)
+}
+
+/// Remove a user from the revocation cache so their state is read from the database next time
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+pub fn forget_cached_user(user_id: &str) {
+    REVOCATION_CACHE.write().unwrap().remove(user_id);
+}
+
+/// Get the current token generation of a user, which new access tokens must carry
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+///
+/// # Returns
+/// `Result<i64, DBReadError>` - The generation or an error
+pub async fn get_token_generation(user_id: &str) -> Result<i64, DBReadError> {
+    get_revocation_state(user_id)
+        .await?
+        .map(|s| s.generation)
+        .ok_or_else(|| DBReadError::NotFound("Failed to get user".to_string()))
+}
+
+/// Check if an access token has been revoked
+/// Tokens are revoked if their jti has been revoked, they are from an older generation or their user no longer exists
+///
+/// # Arguments
+/// * `user_id` - The id of the user the token was made for
+/// * `jti` - The id of the token
+/// * `generation` - The generation the token was made with
+///
+/// # Returns
+/// `Result<bool, DBReadError>` - True if the token has been revoked, or an error
+pub async fn is_token_revoked(user_id: &str, jti: &str, generation: i64) -> Result<bool, DBReadError> {
+    let state = get_revocation_state(user_id).await?;
+
+    Ok(match state {
+        Some(s) => generation < s.generation || s.revoked.contains(jti),
+        None => true,
+    })
+}
+
+/// Revoke a single access token
+///
+/// # Arguments
+/// * `user_id` - The id of the user the token was made for
+/// * `jti` - The id of the token
+/// * `expires_at` - When the token expires, after this it does not need to be remembered
+///
+/// # Returns
+/// `Result<(), DBCreateError>` - Nothing or an error
+pub async fn revoke_token(user_id: &str, jti: &str, expires_at: DateTime<Utc>) -> Result<(), DBCreateError> {
+    // Expired revocations are removed at the same time as they are no longer needed
+    let sql = "
+    DELETE RevokedToken WHERE expires_at < time::now();
+    CREATE RevokedToken SET jti = $jti, user = $user, expires_at = $expires_at;
+    ";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("jti", Value::from(jti)))
+        .bind(("user", user))
+        .bind(("expires_at", Value::Datetime(sdbDateTime::from(expires_at))))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let errors = response.take_errors();
+    if !errors.is_empty() {
+        return Err(DBCreateError::Other(format!("Failed to revoke token: {:?}", errors)));
+    }
+
+    // Update the cache so this instance rejects the token straight away
+    if let Some(Some(state)) = REVOCATION_CACHE.write().unwrap().get_mut(user_id) {
+        state.revoked.insert(jti.to_string());
+    }
+
+    Ok(())
+}
+
+/// Revoke every access token and refresh token of a user
+///
+/// # Arguments
+/// * `user_id` - The id of the user
+///
+/// # Returns
+/// `Result<(), DBEditError>` - Nothing or an error
+pub async fn revoke_all_tokens(user_id: &str) -> Result<(), DBEditError> {
+    let sql = "
+    UPDATE ONLY $user SET token_generation += 1 RETURN AFTER;
+    UPDATE RefreshToken SET revoked_at = time::now() WHERE user = $user AND revoked_at = NONE;
+    ";
+
+    let user: Value = Thing::from(("User", user_id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("user", user))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let errors = response.take_errors();
+    if !errors.is_empty() {
+        return Err(DBEditError::Other(format!("Failed to revoke tokens: {:?}", errors)));
+    }
+
+    forget_cached_user(user_id);
+
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use surrealdb::sql::{Value, Thing};
 
-use crate::model::users::User;
+use crate::model::users::{User, UserAvailability, UserProfile};
 
-use super::{DBCreateError, DBEditError, DBReadError, DB};
+use super::{password::{hash_password, needs_rehash, verify_dummy_password, verify_password}, tokens::forget_cached_user, DBCreateError, DBEditError, DBReadError, DB};
 
 
 /// Create a new user in the database
@@ -10,10 +10,10 @@ use super::{DBCreateError, DBEditError, DBReadError, DB};
 /// # Arguments
 /// * `username` - The username of the user
 /// * `email` - The email of the user
-/// * `password` - The password of the user
+/// * `password` - The password of the user, this is hashed before it is stored
 /// 
 /// # Returns
-/// `Result<User, DBCreateError>` - The created user or an error
+/// `Result<User, DBCreateError>` - The created user or an error, `AlreadyExists` with the name of the field if the username or email is taken
 pub async fn create_user(username: &str, email: &str, password: &str) -> Result<User, DBCreateError> {
     // Create the query
     let sql = "
@@ -23,6 +23,9 @@ pub async fn create_user(username: &str, email: &str, password: &str) -> Result<
     password = $password;
     ";
 
+    // Hash the password so it is never stored in plaintext
+    let password = hash_password(password).map_err(DBCreateError::Other)?;
+
     // Convert the inputs 
     let username = Value::from(username);
     let email = Value::from(email);
@@ -38,14 +41,9 @@ pub async fn create_user(username: &str, email: &str, password: &str) -> Result<
     // Take the first result and convert it to a User
     let result: Option<User> = response
         .take(0)
-        .map_err(|e| {
-            if e.to_string().contains("uniqueUsername") {
-                DBCreateError::BadData("Username already exists".to_string())
-            } else if e.to_string().contains("uniqueEmail") {
-                DBCreateError::BadData("Email already exists".to_string())
-            } else {
-                DBCreateError::Other(e.to_string())
-            }
+        .map_err(|e| match taken_field(&e.to_string()) {
+            Some(field) => DBCreateError::AlreadyExists(field.to_string()),
+            None => DBCreateError::Other(e.to_string()),
         })?;
 
     // Check if the result is None and return an error if it is
@@ -58,6 +56,147 @@ pub async fn create_user(username: &str, email: &str, password: &str) -> Result<
     Ok(result)
 }
 
+/// Find the field of a user which broke one of the unique indexes
+/// The database only reports the first index which was broken, so this is at most one field
+/// 
+/// # Arguments
+/// * `err` - The error from the database
+/// 
+/// # Returns
+/// `Option<&'static str>` - `username` or `email`, None if the error wasn't from a unique index
+fn taken_field(err: &str) -> Option<&'static str> {
+    if err.contains("`uniqueUsername`") {
+        Some("username")
+    } else if err.contains("`uniqueEmail`") {
+        Some("email")
+    } else {
+        None
+    }
+}
+
+/// Check if a username and email are free to sign up with
+/// 
+/// # Arguments
+/// * `username` - The username to check, None to not check it
+/// * `email` - The email to check, None to not check it
+/// 
+/// # Returns
+/// `Result<UserAvailability, DBReadError>` - Whether each of the given fields is free
+pub async fn check_availability(username: Option<&str>, email: Option<&str>) -> Result<UserAvailability, DBReadError> {
+    // Both lookups use the unique indexes so they are cheap
+    let sql = "
+    SELECT VALUE id FROM User WHERE username = $username LIMIT 1;
+    SELECT VALUE id FROM User WHERE email = $email LIMIT 1;
+    ";
+
+    let mut response = DB.query(sql)
+        .bind(("username", username.map(Value::from).unwrap_or(Value::None)))
+        .bind(("email", email.map(Value::from).unwrap_or(Value::None)))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    let username_taken: Vec<Thing> = response.take(0).map_err(|e| DBReadError::Other(e.to_string()))?;
+    let email_taken: Vec<Thing> = response.take(1).map_err(|e| DBReadError::Other(e.to_string()))?;
+
+    Ok(UserAvailability {
+        username: username.map(|_| username_taken.is_empty()),
+        email: email.map(|_| email_taken.is_empty()),
+    })
+}
+
+/// Get a user from the database by id
+/// 
+/// # Arguments
+/// * `id` - The id of the user
+/// 
+/// # Returns
+/// `Result<User, DBReadError>` - The user or an error
+pub async fn get_user_by_id(id: &str) -> Result<User, DBReadError> {
+    // Create the query
+    let sql = "SELECT * FROM $id;";
+
+    // Convert the id to a surrealdb::sql::value
+    let id: Value = Thing::from(("User", id)).into();
+
+    let mut response = DB.query(sql)
+        .bind(("id", id))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    // Take the first result and convert it to a User
+    let result: Option<User> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get user".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a user from the database by username
+/// 
+/// # Arguments
+/// * `username` - The username of the user
+/// 
+/// # Returns
+/// `Result<User, DBReadError>` - The user or an error
+pub async fn get_user_by_username(username: &str) -> Result<User, DBReadError> {
+    // Create the query
+    let sql = "SELECT * FROM User WHERE username = $username LIMIT 1;";
+
+    let mut response = DB.query(sql)
+        .bind(("username", username.to_string()))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    // Take the first result and convert it to a User
+    let result: Option<User> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get user".to_string())
+    })?;
+
+    Ok(result)
+}
+
+/// Get a user from the database by email
+/// 
+/// # Arguments
+/// * `email` - The email of the user
+/// 
+/// # Returns
+/// `Result<User, DBReadError>` - The user or an error
+pub async fn get_user_by_email(email: &str) -> Result<User, DBReadError> {
+    // Create the query
+    let sql = "SELECT * FROM User WHERE email = $email LIMIT 1;";
+
+    let mut response = DB.query(sql)
+        .bind(("email", email.to_string()))
+        .await
+        .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
+
+    // Take the first result and convert it to a User
+    let result: Option<User> = response
+        .take(0)
+        .map_err(|e| {
+            DBReadError::Other(e.to_string())
+        })?;
+
+    let result = result.ok_or_else(|| {
+        DBReadError::NotFound("Failed to get user".to_string())
+    })?;
+
+    Ok(result)
+}
+
 /// Test a username/password combination 
 /// 
 /// # Arguments
@@ -65,18 +204,17 @@ pub async fn create_user(username: &str, email: &str, password: &str) -> Result<
 /// * `password` - The password of the user
 /// 
 /// # Returns
-/// `Result<User, DBReadError>` - The user if the username and password are correct, or an error
-pub async fn compare_username_password(username: &str, password: &str) -> Result<User, DBReadError> {
+/// `Result<UserProfile, DBReadError>` - The user if the username and password are correct, or an error
+pub async fn compare_username_password(username: &str, password: &str) -> Result<UserProfile, DBReadError> {
     // Create the query
-    let sql = "SELECT * FROM User WHERE username = $username AND password = $password;";
+    // The password is checked in Rust against the stored hash, not in the query
+    let sql = "SELECT * FROM User WHERE username = $username;";
 
     // Convert the inputs 
     let username = Value::from(username);
-    let password = Value::from(password);
 
     let mut response = DB.query(sql)
         .bind(("username", username))
-        .bind(("password", password))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
@@ -88,34 +226,35 @@ pub async fn compare_username_password(username: &str, password: &str) -> Result
         })?;
 
     // Check if the result is None and return an error if it is
+    // A password is still checked so an unknown user takes as long as a wrong password
     if result.is_none() {
+        verify_dummy_password(password);
         return Err(DBReadError::NotFound("Failed to get user".to_string()));
     }
     let result = result.unwrap();
 
-    // Return the User struct
-    Ok(result)
+    // Check the password and return the public view of the user
+    check_password(result, password).await
 }
 
-/// Test a email/password combination returning the User if correct
+/// Test a email/password combination returning the user if correct
 /// 
 /// # Arguments
 /// * `email` - The email of the user
 /// * `password` - The password of the user
 /// 
 /// # Returns
-/// `Result<User, DBReadError>` - The user if the email and password are correct, or an error
-pub async fn compare_email_password(email: &str, password: &str) -> Result<User, DBReadError> {
+/// `Result<UserProfile, DBReadError>` - The user if the email and password are correct, or an error
+pub async fn compare_email_password(email: &str, password: &str) -> Result<UserProfile, DBReadError> {
     // Create the query
-    let sql = "SELECT * FROM User WHERE email = $email AND password = $password;";
+    // The password is checked in Rust against the stored hash, not in the query
+    let sql = "SELECT * FROM User WHERE email = $email;";
 
     // Convert the inputs 
     let email = Value::from(email);
-    let password = Value::from(password);
 
     let mut response = DB.query(sql)
         .bind(("email", email))
-        .bind(("password", password))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
@@ -127,27 +266,61 @@ pub async fn compare_email_password(email: &str, password: &str) -> Result<User,
         })?;
 
     // Check if the result is None and return an error if it is
+    // A password is still checked so an unknown user takes as long as a wrong password
     if result.is_none() {
+        verify_dummy_password(password);
         return Err(DBReadError::NotFound("Failed to get user".to_string()));
     }
     let result = result.unwrap();
 
-    // Return the User struct
-    Ok(result)
+    // Check the password and return the public view of the user
+    check_password(result, password).await
+}
+
+/// Check a password against the hash stored for a user
+/// If the hash was made with outdated parameters it is replaced with a new one
+/// 
+/// # Arguments
+/// * `user` - The user fetched from the database
+/// * `password` - The plaintext password to check
+/// 
+/// # Returns
+/// `Result<UserProfile, DBReadError>` - The user without the password hash if the password is correct, or an error
+async fn check_password(user: User, password: &str) -> Result<UserProfile, DBReadError> {
+    let stored = user.password.as_deref().unwrap_or_default();
+
+    // Use the same error as an unknown user so callers cant tell which part was wrong
+    if !verify_password(password, stored) {
+        return Err(DBReadError::NotFound("Failed to get user".to_string()));
+    }
+
+    // Upgrade the hash if the parameters have changed since it was created
+    // Failing to do this should not stop the user logging in, it will be tried again next time
+    if needs_rehash(stored)
+        && let (Some(id), Ok(new_hash)) = (user.id.clone(), hash_password(password))
+    {
+        let _ = DB.query("UPDATE $id SET password = $password;")
+            .bind(("id", Value::from(id)))
+            .bind(("password", Value::from(new_hash)))
+            .bind(("rehash", true)) // The password hasn't changed so it isn't put in the activity log
+            .await;
+    }
+
+    Ok(UserProfile::from(user))
 }
 
-#[allow(dead_code)]
 /// Edit a user from the database by id
 /// 
 /// # Arguments
 /// * `id` - The id of the user to edit
 /// * `username` - The new username of the user
 /// * `email` - The new email of the user
-/// * `password` - The new password of the user
+/// * `password` - The new password of the user, this is hashed before it is stored and revokes every existing token of the user
+/// * `version` - Only edit the user if they are still this version
 /// 
 /// # Returns 
-/// `Result<User, DBEditError>` - The edited user or an error
-pub async fn edit_existing_user(id: &str, username: Option<&str>, email: Option<&str>, password: Option<&str>) -> Result<User, DBEditError> {
+/// `Result<User, DBEditError>` - The edited user or an error, `NotFound` if the user has a different version and `AlreadyExists` with the name of the field if the new username or email is taken
+pub async fn edit_existing_user(id: &str, username: Option<&str>, email: Option<&str>, password: Option<&str>, version: Option<i64>) -> Result<User, DBEditError> {
 
     // Check not all inputs are NONE as this will create an invalid SQL statement
     if email.is_none() && username.is_none() && password.is_none() {
@@ -176,48 +349,66 @@ pub async fn edit_existing_user(id: &str, username: Option<&str>, email: Option<
         },
         None => Value::None,
     };
+    // Changing the password also moves the user to a new token generation, revoking every existing access token
     let password = match password {
         Some(p) => {
-            sql.push_str("password = $password, ");
-            Value::from(p)
+            sql.push_str("password = $password, token_generation += 1, ");
+            Value::from(hash_password(p).map_err(DBEditError::Other)?)
         },
         None => Value::None,
     };
+    let changes_password = password != Value::None;
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to case anything in the SQL
     // I dont have to explicitly do this but I prefer to
+    let user_id = id;
     let id: Value = Thing::from(("User", id)).into();
 
     // Remove the end space and end comma and add the return statement
     sql.pop();
     sql.pop();
+    if version.is_some() {
+        sql.push_str(" WHERE version = $version");
+    }
     sql.push_str(" RETURN AFTER;");
 
+    // Revoke the refresh tokens as well when the password changes
+    // The new hash is only stored if the version matched, so the tokens are left alone otherwise
+    if changes_password {
+        sql.push_str(" UPDATE RefreshToken SET revoked_at = time::now() WHERE user = $id AND revoked_at = NONE AND $id.password = $password;");
+    }
+
     // Send the query
     let mut response = DB.query(sql)
         .bind(("id", id))
         .bind(("email", email))
         .bind(("username", username))
         .bind(("password", password))
+        .bind(("version", version.map(Value::from).unwrap_or(Value::None)))
         .await
         .unwrap(); // This will only panic if the sql is malformed or there is a critical DB error
 
     let result: Option<User> = response
     .take(0)
-    .map_err(|e| {
-        DBEditError::Other(e.to_string())
+    .map_err(|e| match taken_field(&e.to_string()) {
+        Some(field) => DBEditError::AlreadyExists(field.to_string()),
+        None => DBEditError::Other(e.to_string()),
     })?;
 
     let result = result.ok_or_else(|| {
         DBEditError::NotFound("Failed to get task".to_string())
     })?;
+
+    // Make sure this instance sees the new token generation straight away
+    if changes_password {
+        forget_cached_user(user_id);
+    }
     
     Ok(result)
 }
 
-#[allow(dead_code)]
-/// Delete a user from the database
+/// Delete a user and all of their tasks from the database
 /// 
 /// # Arguments
 /// * `id` - The id of the user to delete
@@ -226,15 +417,35 @@ pub async fn edit_existing_user(id: &str, username: Option<&str>, email: Option<
 /// `Result<User, DBEditError>` - The deleted user or an error
 pub async fn delete_user(id: &str) -> Result<User, DBEditError> {
     // Create the query
-    let sql = "DELETE ONLY $id RETURN BEFORE;";
+    // The users tasks and tokens are removed in the same transaction, access tokens are rejected once the user no longer exists
+    // The user is the actor of every change in the activity log, including to the tasks assigned to them
+    let sql = "
+    BEGIN TRANSACTION;
+    DELETE ONLY $id RETURN BEFORE;
+    DELETE TaskCompletion WHERE task.owner = $id;
+    DELETE CommentEdit WHERE comment.task.owner = $id OR comment.author = $id;
+    DELETE Comment WHERE task.owner = $id OR author = $id;
+    DELETE ToDoTask WHERE owner = $id;
+    DELETE TaskTombstone WHERE owner = $id;
+    DELETE Project WHERE owner = $id;
+    DELETE Tag WHERE owner = $id;
+    DELETE Notification WHERE user = $id;
+    UPDATE ToDoTask SET assignee = NONE WHERE assignee = $id;
+    DELETE TaskRemoval WHERE user = $id;
+    DELETE RefreshToken WHERE user = $id;
+    DELETE RevokedToken WHERE user = $id;
+    COMMIT TRANSACTION;
+    ";
 
     // Convert the id to a surrealdb::sql::value
     // This means I dont have to case anything in the SQL
     // I dont have to explicitly do this but I prefer to
+    let user_id = id;
     let id: Value = Thing::from(("User", id)).into();
 
     let mut response = DB.query(sql)
-        .bind(("id", id))
+        .bind(("id", id.clone()))
+        .bind(("actor", id))
         .await
         .unwrap(); // Its okay if this panics because it will only panic if the database is not connected or the query is malformed
 
@@ -254,6 +465,9 @@ pub async fn delete_user(id: &str) -> Result<User, DBEditError> {
     }
     let result = result.unwrap();
 
+    // Make sure this instance rejects the users tokens straight away
+    forget_cached_user(user_id);
+
     // Return the User struct
     Ok(result)
 
@@ -1,5 +1,6 @@
-use api::{todotask::{create_task_handler, delete_task_handler, get_task_handler, update_task_handler, get_tasks_by_user_handler}, user::{create_user_handler, sign_in_user_handler}};
-use rocket::routes;
+use api::{activity::{get_my_activity_handler, get_task_history_handler}, auth::{logout_all_handler, logout_handler, refresh_token_handler, unauthorized_catcher}, comment::{create_comment_handler, delete_comment_handler, get_comment_history_handler, get_comments_handler, update_comment_handler}, notification::{get_notifications_handler, read_all_notifications_handler, read_notification_handler}, problem::{default_catcher, not_found_catcher, request_id_fairing, unprocessable_catcher}, project::{create_project_handler, delete_project_handler, get_project_handler, get_project_tasks_handler, get_projects_handler, update_project_handler}, rate_limit::{too_many_requests_catcher, RateLimitConfig, RateLimitStore}, share::{get_project_shares_handler, get_task_shares_handler, share_project_handler, share_task_handler, unshare_project_handler, unshare_task_handler}, sync::{get_sync_handler, post_sync_handler}, tag::{create_tag_handler, delete_tag_handler, get_tags_handler, get_task_tags_handler, merge_tags_handler, rename_tag_handler, tag_task_handler, untag_task_handler}, todotask::{create_task_handler, delete_task_handler, get_task_handler, update_task_handler, get_tasks_by_user_handler, get_overdue_tasks_handler, get_upcoming_tasks_handler, get_task_completions_handler, get_subtasks_handler, get_task_tree_handler, get_shared_tasks_handler, get_assigned_tasks_handler, assign_task_handler, unassign_task_handler, get_trash_handler, restore_task_handler, purge_task_handler}, user::{create_user_handler, delete_me_handler, get_availability_handler, get_me_handler, sign_in_user_handler, update_me_handler}};
+use database::config::DatabaseConfig;
+use rocket::{catchers, fairing::AdHoc, routes};
 
 mod api;
 mod database;
@@ -16,13 +17,83 @@ async fn main() {
             routes![
                 create_user_handler,
                 sign_in_user_handler,
+                get_availability_handler,
+                get_me_handler,
+                update_me_handler,
+                delete_me_handler,
+                refresh_token_handler,
+                logout_handler,
+                logout_all_handler,
                 create_task_handler,
                 get_task_handler,
                 get_tasks_by_user_handler,
+                get_overdue_tasks_handler,
+                get_upcoming_tasks_handler,
+                get_task_completions_handler,
+                get_subtasks_handler,
+                get_task_tree_handler,
                 update_task_handler,
-                delete_task_handler
+                delete_task_handler,
+                create_project_handler,
+                get_projects_handler,
+                get_project_handler,
+                get_project_tasks_handler,
+                update_project_handler,
+                delete_project_handler,
+                create_tag_handler,
+                get_tags_handler,
+                rename_tag_handler,
+                merge_tags_handler,
+                delete_tag_handler,
+                get_task_tags_handler,
+                tag_task_handler,
+                untag_task_handler,
+                get_shared_tasks_handler,
+                get_task_shares_handler,
+                share_task_handler,
+                unshare_task_handler,
+                get_project_shares_handler,
+                share_project_handler,
+                unshare_project_handler,
+                get_assigned_tasks_handler,
+                assign_task_handler,
+                unassign_task_handler,
+                get_trash_handler,
+                restore_task_handler,
+                purge_task_handler,
+                get_sync_handler,
+                post_sync_handler,
+                get_notifications_handler,
+                read_notification_handler,
+                read_all_notifications_handler,
+                get_comments_handler,
+                create_comment_handler,
+                update_comment_handler,
+                get_comment_history_handler,
+                delete_comment_handler,
+                get_task_history_handler,
+                get_my_activity_handler
             ],
         )
+        .register("/", catchers![unauthorized_catcher, too_many_requests_catcher, not_found_catcher, unprocessable_catcher, default_catcher])
+        .attach(request_id_fairing())
+        .attach(AdHoc::on_liftoff("Trash purge", |_| Box::pin(async {
+            // Old tasks are purged from the trash in the background, a retention of 0 keeps them forever
+            let config = DatabaseConfig::get();
+            if config.trash_retention_days > 0 {
+                let retention = chrono::Duration::days(config.trash_retention_days.into());
+                let every = std::time::Duration::from_secs(config.trash_purge_interval_minutes.max(1) * 60);
+                tokio::spawn(database::todotask::purge_trash_periodically(retention, every));
+            }
+        })))
+        .attach(AdHoc::on_liftoff("Rate limit purge", |_| Box::pin(async {
+            // Only the database store needs cleaning up, the memory store forgets old limits itself
+            let config = RateLimitConfig::get();
+            if config.enabled && config.store == RateLimitStore::Database {
+                let every = std::time::Duration::from_secs(config.purge_interval_minutes.max(1) * 60);
+                tokio::spawn(database::rate_limit::purge_rate_limits_periodically(every));
+            }
+        })))
         .launch()
         .await
         .expect("Error launching rocket instance");
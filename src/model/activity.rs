@@ -0,0 +1,76 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What was done to a task or user
+/// 
+/// # Variants
+/// * `Create` - It was created
+/// * `Update` - Some of its fields were changed
+/// * `Delete` - It was deleted, a task is moved to the trash
+/// * `Restore` - A task was restored from the trash
+/// * `Purge` - A task was deleted permanently
+pub enum ActivityAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+/// A field which was changed
+/// 
+/// # Fields
+/// * `field` - The name of the field
+/// * `old` - The value before the change, null if it wasn't set
+/// * `new` - The value after the change, null if it was removed
+pub struct FieldChange {
+    pub field: String,
+    #[serde(default)]
+    pub old: serde_json::Value,
+    #[serde(default)]
+    pub new: serde_json::Value,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents an entry in the activity log
+/// 
+/// # Fields
+/// * `id` - The ID of the activity
+/// * `actor` - The user who made the change
+/// * `action` - What was done
+/// * `target` - The task or user which was changed, this may no longer exist
+/// * `changes` - The fields which were changed with their old and new values
+/// * `created_at` - The date and time when the change was made
+pub struct Activity {
+    pub id: Option<Thing>,
+    pub actor: Option<Thing>,
+    pub action: ActivityAction,
+    pub target: Option<Thing>,
+    #[serde(default)]
+    pub changes: Vec<FieldChange>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, rocket::FromForm)]
+/// The query parameters for listing activity
+/// 
+/// # Fields
+/// * `cursor` - The `next_cursor` of the previous page, to get the page after it
+/// * `limit` - The maximum number of activities to return
+pub struct ActivityQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A page of activity
+/// 
+/// # Fields
+/// * `activities` - The activities in this page, newest first
+/// * `next_cursor` - The cursor to pass to get the next page, `None` if this is the last page
+pub struct ActivityPage {
+    pub activities: Vec<Activity>,
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,66 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a comment on a task in the database
+/// 
+/// # Fields
+/// * `id` - The ID of the comment
+/// * `task` - The task the comment is on
+/// * `author` - The user who wrote the comment
+/// * `body` - The text of the comment
+/// * `created_at` - The date and time when the comment was written
+/// * `edited_at` - The date and time when the comment was last edited, if is None then it has not been edited
+pub struct Comment {
+    pub id: Option<Thing>,
+    pub task: Option<Thing>,
+    pub author: Option<Thing>,
+    pub body: Option<String>,
+    pub created_at: Option<String>,
+    pub edited_at: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A previous version of an edited comment
+/// 
+/// # Fields
+/// * `id` - The ID of the edit
+/// * `comment` - The comment which was edited
+/// * `body` - The text of the comment before the edit
+/// * `edited_at` - The date and time when the comment was edited
+pub struct CommentEdit {
+    pub id: Option<Thing>,
+    pub comment: Option<Thing>,
+    pub body: Option<String>,
+    pub edited_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to write or edit a comment
+/// 
+/// # Fields
+/// * `body` - The text of the comment
+pub struct CommentRequest {
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, rocket::FromForm)]
+/// The query parameters for listing comments
+/// 
+/// # Fields
+/// * `cursor` - The `next_cursor` of the previous page, to get the page after it
+/// * `limit` - The maximum number of comments to return
+pub struct CommentQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A page of comments
+/// 
+/// # Fields
+/// * `comments` - The comments in this page, oldest first
+/// * `next_cursor` - The cursor to pass to get the next page, `None` if this is the last page
+pub struct CommentPage {
+    pub comments: Vec<Comment>,
+    pub next_cursor: Option<String>,
+}
@@ -1,2 +1,11 @@
+pub mod activity;
+pub mod comment;
+pub mod notification;
+pub mod project;
+pub mod rate_limit;
+pub mod share;
+pub mod sync;
+pub mod tag;
 pub mod todotask;
+pub mod tokens;
 pub mod users;
\ No newline at end of file
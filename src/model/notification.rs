@@ -0,0 +1,47 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What a notification is about
+/// 
+/// # Variants
+/// * `Assigned` - A task was assigned to the user
+/// * `Completed` - A task the user owns or is assigned was completed
+/// * `Commented` - Someone commented on a task the user owns or is assigned
+pub enum NotificationKind {
+    Assigned,
+    Completed,
+    Commented,
+}
+
+impl NotificationKind {
+    /// The name of the kind in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Assigned => "assigned",
+            NotificationKind::Completed => "completed",
+            NotificationKind::Commented => "commented",
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents an in-app notification in the database
+/// 
+/// # Fields
+/// * `id` - The ID of the notification
+/// * `user` - The user the notification is for
+/// * `kind` - What the notification is about
+/// * `task` - The task the notification is about
+/// * `actor` - The user who did the thing the notification is about
+/// * `read_at` - The date and time when the notification was read, if is None then it is unread
+/// * `created_at` - The date and time when the notification was created
+pub struct Notification {
+    pub id: Option<Thing>,
+    pub user: Option<Thing>,
+    pub kind: NotificationKind,
+    pub task: Option<Thing>,
+    pub actor: Option<Thing>,
+    pub read_at: Option<String>,
+    pub created_at: Option<String>,
+}
@@ -0,0 +1,33 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a project in the database, a list which groups a users tasks
+/// 
+/// # Fields
+/// * `id` - The ID of the project
+/// * `name` - The name of the project
+/// * `description` - The description of the project
+/// * `owner` - The owner of the project
+/// * `archived_at` - The date and time when the project was archived, if is None then the project is active
+/// * `created_at` - The date and time when the project was created
+pub struct Project {
+    pub id: Option<Thing>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub owner: Option<Thing>,
+    pub archived_at: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to create or update a project
+/// 
+/// # Fields
+/// * `name` - The name of the project, required when creating a project
+/// * `description` - The description of the project
+/// * `archived` - Archive or restore the project, archived projects are hidden along with their tasks
+pub struct ProjectRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub archived: Option<bool>,
+}
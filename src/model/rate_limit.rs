@@ -0,0 +1,16 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents the failed log ins for an account in the database
+///
+/// # Fields
+/// * `id` - The ID of the record, made from the username or email which was used
+/// * `failures` - How many log ins have failed in a row
+/// * `retry_at` - The date and time when the account can be tried again, if is None it can be tried straight away
+/// * `updated_at` - The date and time of the last failed log in
+pub struct LoginAttempt {
+    pub id: Option<Thing>,
+    pub failures: i64,
+    pub retry_at: Option<String>,
+    pub updated_at: Option<String>,
+}
@@ -0,0 +1,71 @@
+use surrealdb::sql::Thing;
+
+use super::todotask::ToDoTask;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// The role a user is given on a task or project shared with them, each role can do everything the roles before it can
+/// 
+/// # Variants
+/// * `Viewer` - Can see the task
+/// * `Editor` - Can also edit the task
+/// * `CoOwner` - Can also delete the task and share it with other users
+pub enum ShareRole {
+    #[default]
+    Viewer,
+    Editor,
+    CoOwner,
+}
+
+impl ShareRole {
+    /// The name of the role in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShareRole::Viewer => "viewer",
+            ShareRole::Editor => "editor",
+            ShareRole::CoOwner => "co-owner",
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a task or project being shared with a user, a `shared` edge in the database
+/// 
+/// # Fields
+/// * `id` - The ID of the share
+/// * `user` - The user it is shared with
+/// * `target` - The task or project which is shared
+/// * `role` - What the user can do
+/// * `created_at` - The date and time when it was shared
+pub struct Share {
+    pub id: Option<Thing>,
+    #[serde(alias = "in")]
+    pub user: Option<Thing>,
+    #[serde(alias = "out")]
+    pub target: Option<Thing>,
+    pub role: ShareRole,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to share a task or project
+/// 
+/// # Fields
+/// * `username` - The username of the user to share with
+/// * `role` - What the user can do, defaults to `viewer`. Sharing again with the same user changes their role
+pub struct ShareRequest {
+    pub username: Option<String>,
+    pub role: Option<ShareRole>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A task shared with a user
+/// 
+/// # Fields
+/// * `task` - The task, its fields are flattened into this when serialised
+/// * `role` - The highest role the user has on the task, from the task itself or its project
+pub struct SharedTask {
+    #[serde(flatten)]
+    pub task: ToDoTask,
+    pub role: ShareRole,
+}
@@ -0,0 +1,120 @@
+use surrealdb::sql::Thing;
+
+use crate::api::problem::Problem;
+use super::todotask::{TaskRequest, ToDoTask};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a task which was deleted permanently in the database
+///
+/// # Fields
+/// * `id` - The ID of the tombstone
+/// * `task` - The task which was deleted
+/// * `owner` - The owner of the task
+/// * `deleted_at` - The date and time when the task was deleted
+pub struct TaskTombstone {
+    pub id: Option<Thing>,
+    pub task: Thing,
+    pub owner: Option<Thing>,
+    pub deleted_at: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a task a user could see and no longer can, because it was purged, unshared, unassigned or moved out of a shared project
+///
+/// # Fields
+/// * `id` - The ID of the removal
+/// * `task` - The task which was removed
+/// * `user` - The user who can no longer see it
+/// * `removed_at` - The date and time when they lost it
+pub struct TaskRemoval {
+    pub id: Option<Thing>,
+    pub task: Thing,
+    pub user: Option<Thing>,
+    pub removed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A task which changed since the last sync
+///
+/// # Fields
+/// * `id` - The ID of the task
+/// * `deleted` - True if the task is in the trash, was deleted permanently or the user can no longer see it, the client should remove it
+/// * `task` - The task as it is now, None if it was deleted permanently
+/// * `changed_at` - The date and time of the change
+pub struct SyncChange {
+    pub id: Thing,
+    pub deleted: bool,
+    pub task: Option<ToDoTask>,
+    pub changed_at: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A page of the changes since the last sync
+///
+/// # Fields
+/// * `changes` - The changed tasks, oldest change first
+/// * `cursor` - The cursor to send as `since` for the next page, or on the next sync
+/// * `has_more` - True if there are more changes, the client should ask for the next page straight away
+pub struct SyncPage {
+    pub changes: Vec<SyncChange>,
+    pub cursor: String,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Default, rocket::FromForm)]
+/// The query parameters for getting changes
+///
+/// # Fields
+/// * `since` - The `cursor` of the last page, leave it out to get every task
+/// * `limit` - The maximum number of changes to return
+pub struct SyncQuery {
+    pub since: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+/// A change made on a client while it was offline
+///
+/// # Variants
+/// * `Create` - Create a task, `client_id` is the id the client gave it and is sent back with the result
+/// * `Update` - Update a task by id, if `version` is given the change fails with 412 when the task has changed since
+/// * `Delete` - Move a task to the trash by id
+pub enum SyncMutation {
+    Create {
+        client_id: Option<String>,
+        task: TaskRequest,
+    },
+    Update {
+        id: String,
+        task: TaskRequest,
+        version: Option<i64>,
+    },
+    Delete {
+        id: String,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// The body of a request to upload changes made on a client
+///
+/// # Fields
+/// * `mutations` - The changes, applied in order
+pub struct SyncRequest {
+    pub mutations: Vec<SyncMutation>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// The result of one change uploaded by a client
+///
+/// # Fields
+/// * `status` - The HTTP status the change would have had on its own route, e.g. 201 for a created task
+/// * `client_id` - The `client_id` of a created task
+/// * `task` - The task after the change, None if it failed
+/// * `error` - Why the change failed, the same problem the route would have sent
+pub struct SyncResult {
+    pub status: u16,
+    pub client_id: Option<String>,
+    pub task: Option<ToDoTask>,
+    pub error: Option<Problem>,
+}
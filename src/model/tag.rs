@@ -0,0 +1,34 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a tag in the database, a label a user can put on any of their tasks
+/// 
+/// # Fields
+/// * `id` - The ID of the tag
+/// * `name` - The name of the tag, unique for each user
+/// * `owner` - The owner of the tag
+/// * `created_at` - The date and time when the tag was created
+pub struct Tag {
+    pub id: Option<Thing>,
+    pub name: Option<String>,
+    pub owner: Option<Thing>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to create or rename a tag
+/// 
+/// # Fields
+/// * `name` - The name of the tag
+pub struct TagRequest {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to merge a tag into another
+/// 
+/// # Fields
+/// * `into` - The id of the tag which is kept, the tasks of the merged tag are moved to it
+pub struct TagMergeRequest {
+    pub into: String,
+}
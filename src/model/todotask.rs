@@ -10,6 +10,16 @@ use surrealdb::sql::Thing;
 /// * `owner` - The owner of the task
 /// * `completed_at` - The date and time when the task was completed
 /// * `created_at` - The date and time when the task was created
+/// * `due_at` - The date and time when the task is due
+/// * `remind_at` - The date and time to remind the owner about the task, this can't be after `due_at`
+/// * `recurrence` - The recurrence rule of the task, a subset of an iCalendar RRULE e.g. `FREQ=WEEKLY;BYDAY=MO,TH`
+/// * `completed_occurrences` - How many occurrences of a recurring task have been completed
+/// * `parent` - The task this is a subtask of
+/// * `project` - The project the task is in, if is None then the task is in the inbox
+/// * `assignee` - The user the task is assigned to
+/// * `deleted_at` - The date and time when the task was moved to the trash, if is None then it is not in the trash
+/// * `updated_at` - The date and time of the last change to the task, set by the database
+/// * `version` - Increases on every change to the task, set by the database and used for the `ETag`
 pub struct ToDoTask {
     pub id: Option<Thing>,
     pub title: Option<String>,
@@ -17,14 +27,200 @@ pub struct ToDoTask {
     pub owner: Option<Thing>,
     pub completed_at: Option<String>,
     pub created_at: Option<String>,
+    pub due_at: Option<String>,
+    pub remind_at: Option<String>,
+    pub recurrence: Option<String>,
+    pub completed_occurrences: Option<i64>,
+    pub parent: Option<Thing>,
+    pub project: Option<Thing>,
+    pub assignee: Option<Thing>,
+    pub deleted_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub version: Option<i64>,
 }
 
 impl std::fmt::Display for ToDoTask {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "ToDoTask {{ id: {:?}, title: {:?}, description: {:?}, completed_at: {:?}, created_at: {:?} }}",
-            self.id, self.title, self.description, self.completed_at, self.created_at
+            "ToDoTask {{ id: {:?}, title: {:?}, description: {:?}, completed_at: {:?}, created_at: {:?}, due_at: {:?}, remind_at: {:?}, recurrence: {:?}, parent: {:?}, project: {:?}, assignee: {:?}, deleted_at: {:?}, updated_at: {:?}, version: {:?} }}",
+            self.id, self.title, self.description, self.completed_at, self.created_at, self.due_at, self.remind_at, self.recurrence, self.parent, self.project, self.assignee, self.deleted_at, self.updated_at, self.version
         )
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to create or update a task
+/// 
+/// # Fields
+/// * `title` - The title of the task, required when creating a task
+/// * `description` - The description of the task
+/// * `completed_at` - The date and time when the task was completed, completing a recurring task moves it on to its next occurrence
+/// * `created_at` - The date and time when the task was created, only used when 'uploading' a task created earlier offline
+/// * `due_at` - The date and time when the task is due
+/// * `remind_at` - The date and time to remind the owner about the task
+/// * `recurrence` - The recurrence rule of the task, an empty rule removes the recurrence when updating
+/// * `parent` - The id of the task this is a subtask of, an empty id makes it a top level task when updating
+/// * `project` - The id of the project the task is in, `inbox` or an empty id moves it to the inbox when updating
+pub struct TaskRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub completed_at: Option<String>,
+    pub created_at: Option<String>,
+    pub due_at: Option<String>,
+    pub remind_at: Option<String>,
+    pub recurrence: Option<String>,
+    pub parent: Option<String>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+/// The body of a request to assign a task
+/// 
+/// # Fields
+/// * `username` - The username of the user to assign the task to
+pub struct AssignRequest {
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A completed occurrence of a recurring task
+/// 
+/// # Fields
+/// * `id` - The ID of the completion
+/// * `task` - The task which was completed
+/// * `due_at` - When the occurrence was due
+/// * `completed_at` - When the occurrence was completed
+pub struct TaskCompletion {
+    pub id: Option<Thing>,
+    pub task: Option<Thing>,
+    pub due_at: Option<String>,
+    pub completed_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, rocket::FromFormField, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// The field to sort a list of tasks by
+/// 
+/// # Variants
+/// * `CreatedAt` - Sort by the time the task was created
+/// * `CompletedAt` - Sort by the time the task was completed, tasks which are not completed come first when ascending
+/// * `Title` - Sort by the title of the task
+/// * `DueAt` - Sort by the time the task is due, tasks without a due date come first when ascending
+pub enum TaskSort {
+    #[default]
+    #[field(value = "created_at")]
+    CreatedAt,
+    #[field(value = "completed_at")]
+    CompletedAt,
+    #[field(value = "title")]
+    Title,
+    #[field(value = "due_at")]
+    DueAt,
+}
+
+impl TaskSort {
+    /// The name of the field in the database
+    pub fn field(&self) -> &'static str {
+        match self {
+            TaskSort::CreatedAt => "created_at",
+            TaskSort::CompletedAt => "completed_at",
+            TaskSort::Title => "title",
+            TaskSort::DueAt => "due_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, rocket::FromFormField, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// The direction to sort a list of tasks in
+/// 
+/// # Variants
+/// * `Asc` - Smallest first
+/// * `Desc` - Largest first
+pub enum SortDirection {
+    #[default]
+    #[field(value = "asc")]
+    Asc,
+    #[field(value = "desc")]
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, rocket::FromFormField, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// How tasks are matched against the tags in a query
+/// 
+/// # Variants
+/// * `Any` - Tasks with at least one of the tags
+/// * `All` - Tasks with every one of the tags
+pub enum TagMatch {
+    #[default]
+    #[field(value = "any")]
+    Any,
+    #[field(value = "all")]
+    All,
+}
+
+#[derive(Debug, Clone, Default, rocket::FromForm)]
+/// The query parameters for listing tasks
+/// 
+/// # Fields
+/// * `completed` - Only return tasks which are (or are not) completed
+/// * `created_after` - Only return tasks created after this RFC 3339 time
+/// * `created_before` - Only return tasks created before this RFC 3339 time
+/// * `search` - Only return tasks whose title or description contains this text, ignoring case
+/// * `project` - Only return tasks in this project, or `inbox` for tasks without a project. Without this tasks in archived projects are left out
+/// * `tag` - Only return tasks with these tag ids, repeated for each tag e.g. `tag=a&tag=b`
+/// * `tag_match` - Whether tasks need `any` (default) or `all` of the tags
+/// * `sort` - The field to sort by, defaults to `created_at`
+/// * `direction` - The direction to sort in, defaults to `asc`
+/// * `cursor` - The `next_cursor` of the previous page, to get the page after it
+/// * `limit` - The maximum number of tasks to return
+pub struct TaskQuery {
+    pub completed: Option<bool>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub search: Option<String>,
+    pub project: Option<String>,
+    pub tag: Vec<String>,
+    pub tag_match: Option<TagMatch>,
+    pub sort: Option<TaskSort>,
+    pub direction: Option<SortDirection>,
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A page of tasks
+/// 
+/// # Fields
+/// * `tasks` - The tasks in this page
+/// * `next_cursor` - The cursor to pass to get the next page, `None` if this is the last page
+pub struct TaskPage {
+    pub tasks: Vec<ToDoTask>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+/// How many of the direct subtasks of a task are completed
+/// 
+/// # Fields
+/// * `completed` - The number of completed subtasks
+/// * `total` - The number of subtasks
+pub struct TaskProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// A task with all of its subtasks
+/// 
+/// # Fields
+/// * `task` - The task, its fields are flattened into this when serialised
+/// * `progress` - How many of its direct subtasks are completed
+/// * `subtasks` - The subtasks of the task, each with their own subtasks
+pub struct TaskTree {
+    #[serde(flatten)]
+    pub task: ToDoTask,
+    pub progress: TaskProgress,
+    pub subtasks: Vec<TaskTree>,
+}
@@ -0,0 +1,47 @@
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Represents a refresh token in the database
+/// Only the hash of the token is stored, the token itself is only ever given to the client
+/// 
+/// # Fields
+/// * `id` - The ID of the refresh token
+/// * `user` - The user the token belongs to
+/// * `family` - The family of the token, shared by every token rotated from the same log in
+/// * `token_hash` - The SHA-256 hash of the token
+/// * `expires_at` - The date and time when the token expires
+/// * `used_at` - The date and time when the token was rotated, if is None then the token is unused
+/// * `revoked_at` - The date and time when the token was revoked, if is None then the token has not been revoked
+/// * `created_at` - The date and time when the token was created
+pub struct RefreshToken {
+    pub id: Option<Thing>,
+    pub user: Option<Thing>,
+    pub family: Option<String>,
+    pub token_hash: Option<String>,
+    pub expires_at: Option<String>,
+    pub used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// The tokens given to a user when they sign up, log in or refresh
+/// 
+/// # Fields
+/// * `access_token` - The short lived JWT sent in the `Authorization` header
+/// * `refresh_token` - The long lived, single use token used to get a new pair
+/// * `expires_in` - The number of seconds until the access token expires
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// The body of a request to refresh a token pair
+/// 
+/// # Fields
+/// * `refresh_token` - The refresh token to rotate
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
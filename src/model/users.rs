@@ -1,18 +1,155 @@
 use surrealdb::sql::Thing;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 /// This represents a user of the application / an account of the app
+/// This is only ever read from the database and deliberately does not implement `Serialize`, use `UserProfile` for responses
 /// 
 /// # Fields
 /// * `id` - The ID of the user
 /// * `username` - The username of the user
 /// * `email` - The email of the user
-/// * `password` - The password of the user
+/// * `password` - The password hash of the user
 /// * `created_at` - The date and time when the user was created
+/// * `version` - Increases on every change to the user, set by the database and used for the `ETag`
 pub struct User {
     pub id: Option<Thing>,
     pub username: Option<String>,
     pub email: Option<String>,
     pub password: Option<String>,
     pub created_at: Option<String>,
-}
\ No newline at end of file
+    pub version: Option<i64>,
+}
+
+// Written by hand so the password hash does not end up in logs
+impl std::fmt::Debug for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("User")
+            .field("id", &self.id)
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("created_at", &self.created_at)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// The public view of a user, which is safe to return from the API
+/// This deliberately has no password field
+/// 
+/// # Fields
+/// * `id` - The ID of the user
+/// * `username` - The username of the user
+/// * `email` - The email of the user
+/// * `created_at` - The date and time when the user was created
+/// * `version` - Increases on every change to the user
+pub struct UserProfile {
+    pub id: Option<Thing>,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub created_at: Option<String>,
+    pub version: Option<i64>,
+}
+
+impl From<User> for UserProfile {
+    fn from(user: User) -> Self {
+        UserProfile {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            created_at: user.created_at,
+            version: user.version,
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+/// The body of a request to update the authenticated user
+/// This is only ever read from requests and holds the plaintext password, so it is only `Serialize` for the tests to send it
+/// 
+/// # Fields
+/// * `username` - The new username of the user
+/// * `email` - The new email of the user
+/// * `password` - The new password of the user
+/// * `current_password` - The current password of the user, required when changing the password
+pub struct UpdateUserRequest {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub current_password: Option<String>,
+}
+
+// Written by hand, like `User`, so the plaintext passwords do not end up in logs
+impl std::fmt::Debug for UpdateUserRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdateUserRequest")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("current_password", &self.current_password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+/// The body of a request to sign up a new user
+/// The password is plaintext, so `Debug` redacts it and the tests are the only place it is serialised
+/// 
+/// # Fields
+/// * `username` - The username of the new user
+/// * `email` - The email of the new user
+/// * `password` - The password of the new user
+pub struct SignUpRequest {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+}
+
+impl std::fmt::Debug for SignUpRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignUpRequest")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+/// The body of a request to log in, either the username or the email is needed
+/// Like `SignUpRequest` it is only `Serialize` in the tests, as the password is plaintext
+/// 
+/// # Fields
+/// * `username` - The username of the user
+/// * `email` - The email of the user
+/// * `password` - The password of the user
+pub struct LogInRequest {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+}
+
+impl std::fmt::Debug for LogInRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogInRequest")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Whether a username and email can still be used to sign up
+/// 
+/// # Fields
+/// * `username` - True if the username is free, None if it wasn't asked about
+/// * `email` - True if the email is free, None if it wasn't asked about
+pub struct UserAvailability {
+    pub username: Option<bool>,
+    pub email: Option<bool>,
+}
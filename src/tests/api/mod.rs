@@ -1,17 +1,79 @@
+mod auth;
+mod rate_limit;
 mod todotasks;
 mod users;
+mod validation;
 
-use rocket::{build, routes};
+use rocket::{build, catchers, routes};
 
 pub fn rocket_test_launch() -> rocket::Rocket<rocket::Build> {
     build()
-        .mount("/tasks", routes![
+        .mount("/", routes![
             crate::api::todotask::create_task_handler,
+            crate::api::todotask::get_task_handler,
+            crate::api::todotask::get_tasks_by_user_handler,
             crate::api::todotask::update_task_handler,
             crate::api::todotask::delete_task_handler,
-        ])
-        .mount("/users", routes![
+            crate::api::todotask::get_overdue_tasks_handler,
+            crate::api::todotask::get_upcoming_tasks_handler,
+            crate::api::todotask::get_task_completions_handler,
+            crate::api::todotask::get_subtasks_handler,
+            crate::api::todotask::get_task_tree_handler,
+            crate::api::project::create_project_handler,
+            crate::api::project::get_projects_handler,
+            crate::api::project::get_project_handler,
+            crate::api::project::get_project_tasks_handler,
+            crate::api::project::update_project_handler,
+            crate::api::project::delete_project_handler,
+            crate::api::tag::create_tag_handler,
+            crate::api::tag::get_tags_handler,
+            crate::api::tag::rename_tag_handler,
+            crate::api::tag::merge_tags_handler,
+            crate::api::tag::delete_tag_handler,
+            crate::api::tag::get_task_tags_handler,
+            crate::api::tag::tag_task_handler,
+            crate::api::tag::untag_task_handler,
+            crate::api::todotask::get_shared_tasks_handler,
+            crate::api::share::get_task_shares_handler,
+            crate::api::share::share_task_handler,
+            crate::api::share::unshare_task_handler,
+            crate::api::share::get_project_shares_handler,
+            crate::api::share::share_project_handler,
+            crate::api::share::unshare_project_handler,
+            crate::api::todotask::get_assigned_tasks_handler,
+            crate::api::todotask::assign_task_handler,
+            crate::api::todotask::unassign_task_handler,
+            crate::api::todotask::get_trash_handler,
+            crate::api::todotask::restore_task_handler,
+            crate::api::todotask::purge_task_handler,
+            crate::api::sync::get_sync_handler,
+            crate::api::sync::post_sync_handler,
+            crate::api::notification::get_notifications_handler,
+            crate::api::notification::read_notification_handler,
+            crate::api::notification::read_all_notifications_handler,
+            crate::api::comment::get_comments_handler,
+            crate::api::comment::create_comment_handler,
+            crate::api::comment::update_comment_handler,
+            crate::api::comment::get_comment_history_handler,
+            crate::api::comment::delete_comment_handler,
+            crate::api::activity::get_task_history_handler,
+            crate::api::activity::get_my_activity_handler,
             crate::api::user::create_user_handler,
             crate::api::user::sign_in_user_handler,
+            crate::api::user::get_availability_handler,
+            crate::api::auth::refresh_token_handler,
+            crate::api::auth::logout_handler,
+            crate::api::auth::logout_all_handler,
+            crate::api::user::get_me_handler,
+            crate::api::user::update_me_handler,
+            crate::api::user::delete_me_handler,
+        ])
+        .register("/", catchers![
+            crate::api::auth::unauthorized_catcher,
+            crate::api::rate_limit::too_many_requests_catcher,
+            crate::api::problem::not_found_catcher,
+            crate::api::problem::unprocessable_catcher,
+            crate::api::problem::default_catcher,
         ])
+        .attach(crate::api::problem::request_id_fairing())
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+use rocket::local::asynchronous::Client;
+use rocket::http::{Header, Status};
+use crate::api::problem::Problem;
+use crate::model::users::{LogInRequest, SignUpRequest};
+use crate::database::{connect, clear_all_test};
+use super::rocket_test_launch;
+
+#[cfg(test)]
+mod delays {
+    use std::time::Duration;
+    use crate::api::problem::RequestId;
+    use crate::api::rate_limit::{account_key, check_account, failure_delay, record_failure, record_success, Limited, RateLimitConfig};
+
+    #[test]
+    /// Test the wait doubles after each failure until the account is locked
+    fn failure_delay_doubles() {
+        let config = RateLimitConfig::default();
+        assert_eq!(failure_delay(&config, 2), None);
+        assert_eq!(failure_delay(&config, 3), Some(Duration::from_secs(1)));
+        assert_eq!(failure_delay(&config, 4), Some(Duration::from_secs(2)));
+        assert_eq!(failure_delay(&config, 5), Some(Duration::from_secs(4)));
+        assert_eq!(failure_delay(&config, 9), Some(Duration::from_secs(30)));
+        assert_eq!(failure_delay(&config, 10), Some(Duration::from_secs(15 * 60)));
+    }
+
+    #[rocket::async_test]
+    /// Test an account is locked after too many failures and unlocked by logging in
+    async fn lockout_after_failures() {
+        let account = account_key(Some("TEST_lockout"));
+        let request_id = RequestId("TEST".to_string());
+        for _ in 0..10 {
+            record_failure(&account, &request_id).await;
+        }
+        assert!(matches!(check_account(&account, &request_id).await, Err(Limited::AccountLocked(_))));
+
+        record_success(&account, &request_id).await;
+        assert!(check_account(&account, &request_id).await.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod memory_store {
+    use std::time::{Duration, Instant};
+    use crate::api::rate_limit::MemoryStore;
+
+    #[test]
+    /// Test expired entries are forgotten and are not returned before then
+    fn forget_expired() {
+        let mut store = MemoryStore::new(10);
+        let now = Instant::now();
+        store.insert("a", 1, now + Duration::from_secs(1), now);
+        assert_eq!(store.get("a", now), Some(&1));
+        assert_eq!(store.get("a", now + Duration::from_secs(1)), None);
+
+        // Replacing an entry changes when it expires
+        store.insert("a", 2, now + Duration::from_secs(5), now);
+        assert_eq!(store.get("a", now + Duration::from_secs(2)), Some(&2));
+        store.remove("a");
+        assert_eq!(store.get("a", now), None);
+    }
+
+    #[test]
+    /// Test a full store forgets the entry which expires soonest, whenever it was added
+    fn full_forgets_soonest() {
+        let mut store = MemoryStore::new(2);
+        let now = Instant::now();
+        // A slow bucket takes longer to fill up than a fast one added after it
+        store.insert("slow", 1, now + Duration::from_secs(60), now);
+        store.insert("fast", 2, now + Duration::from_secs(2), now);
+        store.insert("new", 3, now + Duration::from_secs(30), now);
+
+        assert_eq!(store.get("slow", now), Some(&1));
+        assert_eq!(store.get("fast", now), None);
+        assert_eq!(store.get("new", now), Some(&3));
+    }
+}
+
+#[cfg(test)]
+mod request_tests {
+    use super::*;
+
+    #[rocket::async_test]
+    /// Test signing up too often from one IP is rejected
+    /// This test ensures the sign up route returns 429 Too Many Requests with Retry-After once the bucket is empty.
+    async fn test_sign_up_rate_limited() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Invalid sign ups still use up the bucket
+        let user = SignUpRequest {
+            username: Some("TESTlimited".to_string()),
+            email: Some("not an email".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        for _ in 0..5 {
+            let response = client.post("/users/sign-up").remote("1.2.3.4:5000".parse().unwrap()).json(&user).dispatch().await;
+            assert_eq!(response.status(), Status::BadRequest);
+        }
+
+        // The next one is rejected with how long to wait
+        let response = client.post("/users/sign-up").remote("1.2.3.4:5000".parse().unwrap()).json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let retry_after: u64 = response.headers().get_one("Retry-After").expect("Expected Retry-After").parse().unwrap();
+        assert!(retry_after > 0);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "too_many_requests");
+        assert_eq!(problem.retry_after, Some(retry_after));
+
+        // Setting X-Real-IP doesn't get a new bucket
+        let response = client.post("/users/sign-up").remote("1.2.3.4:5000".parse().unwrap()).header(Header::new("X-Real-IP", "9.9.9.9")).json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+
+        // Other IPs have their own bucket
+        let response = client.post("/users/sign-up").remote("5.6.7.8:5000".parse().unwrap()).json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[rocket::async_test]
+    /// Test failed log ins make the account wait
+    /// This test ensures the log in route returns 429 Too Many Requests after several wrong passwords, even with the right one.
+    async fn test_log_in_delayed() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Sign up
+        let user = SignUpRequest {
+            username: Some("TESTdelayed".to_string()),
+            email: Some("TESTdelayed@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        // Log in with the wrong password
+        let wrong = LogInRequest {
+            username: Some("TESTdelayed".to_string()),
+            email: None,
+            password: Some("TESTwrong123".to_string()),
+        };
+        for _ in 0..3 {
+            let response = client.post("/users/log-in").json(&wrong).dispatch().await;
+            assert_eq!(response.status(), Status::BadRequest);
+        }
+
+        // The account has to wait, even with the right password
+        let right = LogInRequest { password: Some("TESTpassword123".to_string()), ..wrong };
+        let response = client.post("/users/log-in").json(&right).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert!(response.headers().get_one("Retry-After").is_some());
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "too_many_attempts");
+    }
+
+    #[rocket::async_test]
+    /// Test failed log ins with the username and with the email count towards the same wait
+    /// This test ensures switching between them doesn't give a client more tries at the password.
+    async fn test_log_in_delayed_username_and_email() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Sign up
+        let user = SignUpRequest {
+            username: Some("TESTalternating".to_string()),
+            email: Some("TESTalternating@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        // Log in with the wrong password, switching between the username and email
+        let by_username = LogInRequest {
+            username: Some("TESTalternating".to_string()),
+            email: None,
+            password: Some("TESTwrong123".to_string()),
+        };
+        let by_email = LogInRequest {
+            username: None,
+            email: Some("TESTalternating@example.com".to_string()),
+            password: Some("TESTwrong123".to_string()),
+        };
+        for wrong in [&by_username, &by_email, &by_username] {
+            let response = client.post("/users/log-in").json(wrong).dispatch().await;
+            assert_eq!(response.status(), Status::BadRequest);
+        }
+
+        // The account has to wait, whichever is used next
+        let right = LogInRequest { password: Some("TESTpassword123".to_string()), ..by_email };
+        let response = client.post("/users/log-in").json(&right).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "too_many_attempts");
+    }
+}
@@ -1,7 +1,8 @@
 use rocket::local::asynchronous::Client;
 use rocket::http::{Status, Header};
-use crate::model::users::User;
-use crate::model::todotask::ToDoTask;
+use crate::model::users::SignUpRequest;
+use crate::model::todotask::{TaskPage, TaskRequest, ToDoTask};
+use crate::model::tokens::TokenPair;
 use crate::database::{connect, clear_all_test};
 use super::rocket_test_launch;
 
@@ -22,12 +23,10 @@ mod creating {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Define a user and sign up to get a token
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         // Send a POST request to sign up the user
@@ -41,22 +40,21 @@ mod creating {
         assert_eq!(sign_up_response.status(), Status::Created);
 
         // Extract the token from the response
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
 
         // Define a task to create
-        let task = ToDoTask {
+        let task = TaskRequest {
             title: Some("Test Task".to_string()),
             description: Some("A task for testing".to_string()),
             completed_at: None,
             created_at: None,
-            id: None,
-            owner: None,
+            ..Default::default()
         };
 
         // Send a POST request to create the task
         let response = client
             .post("/tasks")
-            .header(Header::new("Authorization", token))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .json(&task)
             .dispatch()
             .await;
@@ -78,12 +76,10 @@ mod creating {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Define a user and sign up to get a token
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         // Send a POST request to sign up the user
@@ -97,28 +93,40 @@ mod creating {
         assert_eq!(sign_up_response.status(), Status::Created);
 
         // Extract the token from the response
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
 
         // Define a task with invalid data (missing title)
-        let task = ToDoTask {
+        let task = TaskRequest {
             title: None, // Invalid data: missing title
             description: Some("A task for testing".to_string()),
             completed_at: None,
             created_at: None,
-            id: None,
-            owner: None,
+            ..Default::default()
         };
 
         // Send a POST request to create the task
         let response = client
             .post("/tasks")
-            .header(Header::new("Authorization", token))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .json(&task)
             .dispatch()
             .await;
 
         // Assert that the response status is BadRequest (400)
         assert_eq!(response.status(), Status::BadRequest);
+
+        // The database says what was wrong, the same as when updating a task
+        let task = TaskRequest { title: Some("TESTmissing project".to_string()), project: Some("missing".to_string()), ..Default::default() };
+        let response = client
+            .post("/tasks")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .json(&task)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let problem: crate::api::problem::Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "invalid_data");
+        assert_eq!(problem.detail, "Project not found");
     }
 }
 
@@ -136,12 +144,10 @@ mod getting {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Create a user
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         let sign_up_response = client
@@ -152,31 +158,32 @@ mod getting {
 
         assert_eq!(sign_up_response.status(), Status::Created);
 
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
 
         // Create a task
-        let task = ToDoTask {
+        let task = TaskRequest {
             title: Some("Test Task".to_string()),
             description: Some("A task for testing".to_string()),
             completed_at: None,
             created_at: None,
-            id: None,
-            owner: None,
+            ..Default::default()
         };
 
         let create_task_response = client
             .post("/tasks")
-            .header(Header::new("Authorization", token.clone()))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .json(&task)
             .dispatch()
             .await;
 
         assert_eq!(create_task_response.status(), Status::Created);
+        let created_task: ToDoTask = create_task_response.into_json().await.unwrap();
+        let task_id = created_task.id.unwrap().id.to_raw();
 
         // Fetch the task by ID
         let get_task_response = client
-            .get("/tasks/1")
-            .header(Header::new("Authorization", token.clone()))
+            .get(format!("/tasks/{}", task_id))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .dispatch()
             .await;
 
@@ -199,12 +206,10 @@ mod getting {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Create a user
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         let sign_up_response = client
@@ -215,40 +220,37 @@ mod getting {
 
         assert_eq!(sign_up_response.status(), Status::Created);
 
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
 
         // Create multiple tasks
         let tasks = vec![
-            ToDoTask {
+            TaskRequest {
                 title: Some("Task 1".to_string()),
                 description: Some("First task".to_string()),
                 completed_at: None,
                 created_at: None,
-                id: None,
-                owner: None,
+                ..Default::default()
             },
-            ToDoTask {
+            TaskRequest {
                 title: Some("Task 2".to_string()),
                 description: Some("Second task".to_string()),
                 completed_at: None,
                 created_at: None,
-                id: None,
-                owner: None,
+                ..Default::default()
             },
-            ToDoTask {
+            TaskRequest {
                 title: Some("Task 3".to_string()),
                 description: Some("Third task".to_string()),
                 completed_at: None,
                 created_at: None,
-                id: None,
-                owner: None,
+                ..Default::default()
             },
         ];
 
         for task in &tasks {
             let response = client
                 .post("/tasks")
-                .header(Header::new("Authorization", token.clone()))
+                .header(Header::new("Authorization", format!("Bearer {}", token)))
                 .json(task)
                 .dispatch()
                 .await;
@@ -259,7 +261,7 @@ mod getting {
         // Fetch tasks by user ID
         let get_tasks_response = client
             .get("/tasks")
-            .header(Header::new("Authorization", token.clone()))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .dispatch()
             .await;
 
@@ -267,7 +269,7 @@ mod getting {
         assert_eq!(get_tasks_response.status(), Status::Ok);
 
         // Assert that the tasks data matches the created tasks
-        let fetched_tasks: Vec<ToDoTask> = get_tasks_response.into_json().await.unwrap();
+        let fetched_tasks = get_tasks_response.into_json::<TaskPage>().await.unwrap().tasks;
         assert_eq!(fetched_tasks.len(), tasks.len());
 
         for (fetched_task, created_task) in fetched_tasks.iter().zip(tasks.iter()) {
@@ -289,12 +291,10 @@ mod editing {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Create a user and get the token
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         let sign_up_response = client
@@ -305,20 +305,29 @@ mod editing {
 
         assert_eq!(sign_up_response.status(), Status::Created);
 
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
 
-        let updated_task = ToDoTask {
+        let updated_task = TaskRequest {
             title: Some("Updated Task".to_string()),
             description: Some("Updated description".to_string()),
             completed_at: None,
             created_at: None,
-            id: None,
-            owner: None,
+            ..Default::default()
         };
 
+        let create_task_response = client
+            .post("/tasks")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(create_task_response.status(), Status::Created);
+        let created_task: ToDoTask = create_task_response.into_json().await.unwrap();
+        let task_id = created_task.id.unwrap().id.to_raw();
+
         let response = client
-            .patch("/tasks/1")
-            .header(Header::new("Authorization", token))
+            .patch(format!("/tasks/{}", task_id))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
             .json(&updated_task)
             .dispatch()
             .await;
@@ -339,12 +348,10 @@ mod deleting {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Create a user and get the token
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         let sign_up_response = client
@@ -355,14 +362,491 @@ mod deleting {
 
         assert_eq!(sign_up_response.status(), Status::Created);
 
-        let token = sign_up_response.into_string().await.unwrap(); // Assuming token is returned as plain text
+        let token = sign_up_response.into_json::<TokenPair>().await.unwrap().access_token;
+
+        let create_task_response = client
+            .post("/tasks")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(create_task_response.status(), Status::Created);
+        let created_task: ToDoTask = create_task_response.into_json().await.unwrap();
+        let task_id = created_task.id.unwrap().id.to_raw();
 
         let response = client
-            .delete("/tasks/1")
-            .header(Header::new("Authorization", token))
+            .delete(format!("/tasks/{}", task_id))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
+#[cfg(test)]
+mod sharing {
+    use super::*;
+    use crate::model::notification::{Notification, NotificationKind};
+    use crate::model::project::{Project, ProjectRequest};
+    use crate::model::share::{ShareRequest, ShareRole, SharedTask};
+    use crate::model::todotask::AssignRequest;
+
+    /// Sign up a user and get their access token
+    async fn sign_up(client: &Client, username: &str) -> String {
+        let user = SignUpRequest {
+            username: Some(username.to_string()),
+            email: Some(format!("{}@example.com", username)),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+        response.into_json::<TokenPair>().await.unwrap().access_token
+    }
+
+    #[rocket::async_test]
+    /// Test a viewer can see a shared task but not edit or delete it
+    async fn test_shared_task_roles() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let owner = sign_up(&client, "test_owner").await;
+        let viewer = sign_up(&client, "test_viewer").await;
+        let auth = |token: &str| Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth(&owner))
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+
+        // Nobody else can see the task until it is shared
+        let response = client.get(format!("/tasks/{}", task_id)).header(auth(&viewer)).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let share = ShareRequest { username: Some("test_viewer".to_string()), role: Some(ShareRole::Viewer) };
+        let response = client.post(format!("/tasks/{}/shares", task_id)).header(auth(&owner)).json(&share).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        // A viewer can't share the task any further
+        let response = client.post(format!("/tasks/{}/shares", task_id)).header(auth(&viewer)).json(&share).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client.get(format!("/tasks/{}", task_id)).header(auth(&viewer)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.patch(format!("/tasks/{}", task_id))
+            .header(auth(&viewer))
+            .json(&TaskRequest { title: Some("Renamed".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client.delete(format!("/tasks/{}", task_id)).header(auth(&viewer)).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client.get("/tasks/shared-with-me").header(auth(&viewer)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let shared: Vec<SharedTask> = response.into_json().await.unwrap();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].role, ShareRole::Viewer);
+    }
+
+    #[rocket::async_test]
+    /// Test only the owner or a co-owner can assign a task, so an editor can't give someone else access
+    async fn test_editor_cannot_assign() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let owner = sign_up(&client, "TESTassign_owner").await;
+        let editor = sign_up(&client, "TESTassign_editor").await;
+        let _ = sign_up(&client, "TESTassign_other").await;
+        let auth = |token: &str| Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth(&owner))
+            .json(&TaskRequest { title: Some("TESTassigned".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+
+        let share = ShareRequest { username: Some("TESTassign_editor".to_string()), role: Some(ShareRole::Editor) };
+        let response = client.post(format!("/tasks/{}/shares", task_id)).header(auth(&owner)).json(&share).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        // The editor can't assign the task to a third user
+        let assign = AssignRequest { username: Some("TESTassign_other".to_string()) };
+        let response = client.put(format!("/tasks/{}/assignee", task_id)).header(auth(&editor)).json(&assign).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client.get(format!("/tasks/{}", task_id)).header(auth(&owner)).dispatch().await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        assert!(task.assignee.is_none(), "Expected the task to stay unassigned");
+
+        // An assignee can't pass the task on either
+        let assign = AssignRequest { username: Some("TESTassign_editor".to_string()) };
+        let response = client.put(format!("/tasks/{}/assignee", task_id)).header(auth(&owner)).json(&assign).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let assign = AssignRequest { username: Some("TESTassign_other".to_string()) };
+        let response = client.put(format!("/tasks/{}/assignee", task_id)).header(auth(&editor)).json(&assign).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+
+    #[rocket::async_test]
+    /// Test the owner is only told a task was completed once, even if the completed task is sent back again
+    async fn test_completed_notified_once() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let owner = sign_up(&client, "TESTcomplete_owner").await;
+        let editor = sign_up(&client, "TESTcomplete_editor").await;
+        let auth = |token: &str| Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth(&owner))
+            .json(&TaskRequest { title: Some("TESTcompleted".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+
+        let share = ShareRequest { username: Some("TESTcomplete_editor".to_string()), role: Some(ShareRole::Editor) };
+        let response = client.post(format!("/tasks/{}/shares", task_id)).header(auth(&owner)).json(&share).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        // Complete the task, then send the whole completed task back with a new title
+        let completed_at = chrono::Utc::now().to_rfc3339();
+        for title in ["TESTcompleted", "TESTrenamed"] {
+            let edit = TaskRequest { title: Some(title.to_string()), completed_at: Some(completed_at.clone()), ..Default::default() };
+            let response = client.patch(format!("/tasks/{}", task_id)).header(auth(&editor)).json(&edit).dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        let response = client.get("/notifications").header(auth(&owner)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let notifications: Vec<Notification> = response.into_json().await.unwrap();
+        let completed = notifications.iter().filter(|n| n.kind == NotificationKind::Completed).count();
+        assert_eq!(completed, 1);
+    }
+
+    #[rocket::async_test]
+    /// Test a member of a shared project can see it and its tasks, and an editor can add tasks to it
+    async fn test_shared_project_members() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let owner = sign_up(&client, "TESTlist_owner").await;
+        let member = sign_up(&client, "TESTlist_member").await;
+        let auth = |token: &str| Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/projects")
+            .header(auth(&owner))
+            .json(&ProjectRequest { name: Some("TESTshared list".to_string()), ..Default::default() })
             .dispatch()
             .await;
+        assert_eq!(response.status(), Status::Created);
+        let project: Project = response.into_json().await.unwrap();
+        let project_id = project.id.unwrap().id.to_raw();
+        let response = client.post("/tasks")
+            .header(auth(&owner))
+            .json(&TaskRequest { title: Some("TESTowner item".to_string()), project: Some(project_id.clone()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Created);
+
+        let response = client.get(format!("/projects/{}", project_id)).header(auth(&member)).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let share = ShareRequest { username: Some("TESTlist_member".to_string()), role: Some(ShareRole::Editor) };
+        let response = client.post(format!("/projects/{}/shares", project_id)).header(auth(&owner)).json(&share).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+
+        let response = client.get(format!("/projects/{}", project_id)).header(auth(&member)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get("/projects").header(auth(&member)).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let projects: Vec<Project> = response.into_json().await.unwrap();
+        assert_eq!(projects.len(), 1);
+
+        let response = client.post("/tasks")
+            .header(auth(&member))
+            .json(&TaskRequest { title: Some("TESTmember item".to_string()), project: Some(project_id.clone()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Created);
+
+        // Both users see every task in the list
+        for token in [&owner, &member] {
+            let response = client.get(format!("/projects/{}/tasks", project_id)).header(auth(token)).dispatch().await;
+            assert_eq!(response.status(), Status::Ok);
+            let page: TaskPage = response.into_json().await.unwrap();
+            assert_eq!(page.tasks.len(), 2);
+        }
+
+        // The member can't delete a list they don't own
+        let response = client.delete(format!("/projects/{}", project_id)).header(auth(&member)).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+    }
+}
+
+#[cfg(test)]
+mod history {
+    use super::*;
+    use crate::model::activity::{ActivityAction, ActivityPage};
+
+    #[rocket::async_test]
+    /// Test the changes to a task are recorded with their old and new values
+    async fn test_task_history() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let user = SignUpRequest {
+            username: Some("test_historian".to_string()),
+            email: Some("test_historian@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        let token = response.into_json::<TokenPair>().await.unwrap().access_token;
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth.clone())
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+
+        let response = client.patch(format!("/tasks/{}", task_id))
+            .header(auth.clone())
+            .json(&TaskRequest { title: Some("Renamed".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // Newest first, only the title changed
+        let response = client.get(format!("/tasks/{}/history", task_id)).header(auth.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let history: ActivityPage = response.into_json().await.unwrap();
+        assert_eq!(history.activities.len(), 2);
+        assert_eq!(history.activities[0].action, ActivityAction::Update);
+        assert_eq!(history.activities[0].changes.len(), 1);
+        assert_eq!(history.activities[0].changes[0].field, "title");
+        assert_eq!(history.activities[0].changes[0].old, "Test Task");
+        assert_eq!(history.activities[0].changes[0].new, "Renamed");
+        assert_eq!(history.activities[1].action, ActivityAction::Create);
+
+        // The feed of the user has their sign up too, one page at a time
+        let response = client.get("/users/me/activity?limit=2").header(auth.clone()).dispatch().await;
+        let feed: ActivityPage = response.into_json().await.unwrap();
+        assert_eq!(feed.activities.len(), 2);
+        let response = client.get(format!("/users/me/activity?limit=2&cursor={}", feed.next_cursor.unwrap())).header(auth).dispatch().await;
+        let feed: ActivityPage = response.into_json().await.unwrap();
+        assert_eq!(feed.activities.len(), 1);
+        assert_eq!(feed.activities[0].action, ActivityAction::Create);
+        assert!(feed.next_cursor.is_none());
+    }
+}
+
+#[cfg(test)]
+mod sync {
+    use super::*;
+    use crate::model::sync::{SyncMutation, SyncPage, SyncRequest, SyncResult};
+
+    #[rocket::async_test]
+    /// Test uploading changes made offline and getting the changes since the last sync
+    async fn test_sync() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let user = SignUpRequest {
+            username: Some("test_syncer".to_string()),
+            email: Some("test_syncer@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        let token = response.into_json::<TokenPair>().await.unwrap().access_token;
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth.clone())
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+
+        // The first sync gets every task
+        let response = client.get("/sync").header(auth.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let page: SyncPage = response.into_json().await.unwrap();
+        assert_eq!(page.changes.len(), 1);
+        assert!(!page.changes[0].deleted);
+
+        // Each change gets its own result, a failing change doesn't stop the rest
+        let batch = SyncRequest { mutations: vec![
+            SyncMutation::Create { client_id: Some("local-1".to_string()), task: TaskRequest { title: Some("Test Offline".to_string()), ..Default::default() } },
+            SyncMutation::Create { client_id: Some("local-2".to_string()), task: TaskRequest::default() },
+            SyncMutation::Delete { id: task_id.clone() },
+        ] };
+        let response = client.post("/sync").header(auth.clone()).json(&batch).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let results: Vec<SyncResult> = response.into_json().await.unwrap();
+        assert_eq!(results.iter().map(|r| r.status).collect::<Vec<_>>(), vec![201, 400, 200]);
+        assert_eq!(results[0].client_id.as_deref(), Some("local-1"));
+        assert!(results[1].error.is_some());
+
+        // Only the created task and the deleted task have changed since the cursor
+        let response = client.get(format!("/sync?since={}", page.cursor)).header(auth.clone()).dispatch().await;
+        let changes: SyncPage = response.into_json().await.unwrap();
+        assert_eq!(changes.changes.len(), 2);
+        assert!(!changes.changes[0].deleted);
+        assert_eq!(changes.changes[1].id.id.to_raw(), task_id);
+        assert!(changes.changes[1].deleted, "The deleted task should be a tombstone");
+
+        // The changes were just made, so they are sent again in case another one committed late
+        let response = client.get(format!("/sync?since={}", changes.cursor)).header(auth.clone()).dispatch().await;
+        let again: SyncPage = response.into_json().await.unwrap();
+        let ids = |page: &SyncPage| page.changes.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&again), ids(&changes));
+        assert!(!again.has_more);
+
+        let response = client.get("/sync?since=yesterday").header(auth).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+}
+
+#[cfg(test)]
+mod conditional {
+    use super::*;
+    use crate::api::conditional::Preconditions;
+
+    #[test]
+    /// Test `If-Match` only matches strong tags while `If-None-Match` also matches weak ones
+    fn test_weak_tags() {
+        let conditions = |header: &str| Preconditions { if_match: Some(header.to_string()), if_none_match: Some(header.to_string()) };
+
+        assert!(conditions("\"3\"").if_match(Some(3)));
+        assert!(conditions("\"3\"").not_modified(Some(3)));
+        assert!(!conditions("W/\"3\"").if_match(Some(3)));
+        assert!(conditions("W/\"3\"").not_modified(Some(3)));
+        assert!(conditions("W/\"2\", \"3\"").if_match(Some(3)));
+        assert!(conditions("*").if_match(Some(3)));
+        assert!(!conditions("\"2\"").if_match(Some(3)));
+        assert!(!conditions("\"2\"").not_modified(Some(3)));
+    }
+
+    #[rocket::async_test]
+    /// Test the version of a task is checked with `If-Match` and `If-None-Match`
+    async fn test_task_etag() {
+        connect().await;
+        clear_all_test().await;
+
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+        let user = SignUpRequest {
+            username: Some("test_etagger".to_string()),
+            email: Some("test_etagger@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        let token = response.into_json::<TokenPair>().await.unwrap().access_token;
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        let response = client.post("/tasks")
+            .header(auth.clone())
+            .json(&TaskRequest { title: Some("Test Task".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        let task_id = task.id.unwrap().id.to_raw();
+        assert_eq!(task.version, Some(1));
+
+        let response = client.get(format!("/tasks/{}", task_id)).header(auth.clone()).dispatch().await;
+        let etag = response.headers().get_one("ETag").expect("The task should have an ETag").to_string();
+        assert_eq!(etag, "\"1\"");
+
+        // The client already has this version
+        let response = client.get(format!("/tasks/{}", task_id))
+            .header(auth.clone())
+            .header(Header::new("If-None-Match", etag.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotModified);
 
+        // The first update matches and gets a new tag
+        let response = client.patch(format!("/tasks/{}", task_id))
+            .header(auth.clone())
+            .header(Header::new("If-Match", etag.clone()))
+            .json(&TaskRequest { title: Some("First".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
         assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some("\"2\""));
+
+        // The second update with the old tag is rejected and changes nothing
+        let response = client.patch(format!("/tasks/{}", task_id))
+            .header(auth.clone())
+            .header(Header::new("If-Match", etag))
+            .json(&TaskRequest { title: Some("Second".to_string()), ..Default::default() })
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PreconditionFailed);
+        let response = client.get(format!("/tasks/{}", task_id)).header(auth).dispatch().await;
+        let task: ToDoTask = response.into_json().await.unwrap();
+        assert_eq!(task.title.as_deref(), Some("First"));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod due_dates {
+    use crate::api::todotask::parse_within;
+
+    #[test]
+    /// Test parsing the `within` parameter of the upcoming tasks route
+    fn test_parse_within() {
+        assert_eq!(parse_within("30m"), Some(chrono::Duration::minutes(30)));
+        assert_eq!(parse_within("12h"), Some(chrono::Duration::hours(12)));
+        assert_eq!(parse_within("3d"), Some(chrono::Duration::days(3)));
+        assert_eq!(parse_within("2w"), Some(chrono::Duration::weeks(2)));
+        assert_eq!(parse_within("3"), None);
+        assert_eq!(parse_within("d"), None);
+        assert_eq!(parse_within("-3d"), None);
+        assert_eq!(parse_within("3y"), None);
+        assert_eq!(parse_within(""), None);
+    }
+}
+
+#[cfg(test)]
+mod errors {
+    use crate::api::{Resource, Response};
+    use crate::database::{DBCreateError, DBEditError, DBReadError};
+
+    /// Get the status and code of a response
+    fn status_code(response: Response<()>) -> (u16, String) {
+        match response {
+            Response::BadRequest(problem) => (400, problem.code),
+            Response::NotFound(problem) => (404, problem.code),
+            Response::Conflict(problem) => (409, problem.code),
+            Response::InternalServerError(problem) => (500, problem.code),
+            _ => panic!("Expected an error response"),
+        }
+    }
+
+    #[test]
+    /// Test every database error gets the same status and code whichever handler it came from
+    fn database_errors_map_the_same_way() {
+        assert_eq!(status_code(Response::from(DBEditError::NotFound("gone".to_string())).about(Resource::Task)), (404, "task_not_found".to_string()));
+        assert_eq!(status_code(Response::from(DBReadError::NotFound("gone".to_string())).about(Resource::Project)), (404, "project_not_found".to_string()));
+        assert_eq!(status_code(Response::from(DBReadError::NotFound("gone".to_string())).about(Resource::TrashedTask)), (404, "task_not_in_trash".to_string()));
+        assert_eq!(status_code(Response::from(DBCreateError::AlreadyExists("taken".to_string())).about(Resource::Tag)), (409, "already_exists".to_string()));
+        assert_eq!(status_code(Response::from(DBEditError::AlreadyExists("taken".to_string())).about(Resource::Project)), (409, "already_exists".to_string()));
+        assert_eq!(status_code(Response::from(DBCreateError::BadData("Project not found".to_string()))), (400, "invalid_data".to_string()));
+        assert_eq!(status_code(Response::from(DBReadError::BadData("Invalid cursor".to_string())).about(Resource::Comment)), (400, "invalid_query".to_string()));
+        assert_eq!(status_code(Response::from(DBReadError::Other("broken".to_string())).about(Resource::Task)), (500, "internal_error".to_string()));
+    }
+}
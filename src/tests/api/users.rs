@@ -1,5 +1,5 @@
 use rocket::local::asynchronous::Client;
-use crate::model::users::User;
+use crate::model::users::{LogInRequest, SignUpRequest};
 use crate::database::{clear_all_test, connect};
 use super::rocket_test_launch;
 
@@ -22,12 +22,10 @@ mod user_tests {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Define a user to create
-        let user = User {
-            username: Some("test_user".to_string()),
-            email: Some("test_user@example.com".to_string()),
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+        let user = SignUpRequest {
+            username: Some("TEST_user".to_string()),
+            email: Some("TEST_user@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
         };
 
         // Send a POST request to create the user
@@ -53,13 +51,14 @@ mod user_tests {
         // Create a client for sending requests
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
+        // Create the user to sign in as
+        let _ = crate::database::users::create_user("TEST_user", "TEST_user@example.com", "TESTpassword123").await.expect("Failed to create user: ");
+
         // Define a user to sign in
-        let user = User {
-            username: Some("test_user".to_string()),
+        let user = LogInRequest {
+            username: Some("TEST_user".to_string()),
             email: None,
-            password: Some("password123".to_string()),
-            created_at: None,
-            id: None,
+            password: Some("TESTpassword123".to_string()),
         };
 
         // Send a POST request to log in the user
@@ -86,12 +85,10 @@ mod user_tests {
         let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
 
         // Define a user with invalid credentials
-        let user = User {
+        let user = LogInRequest {
             username: Some("nonexistent_user".to_string()), // Invalid username
             email: None,
             password: Some("wrongpassword".to_string()), // Invalid password
-            created_at: None,
-            id: None,
         };
 
         // Send a POST request to log in the user
@@ -104,4 +101,280 @@ mod user_tests {
         // Assert that the response status is BadRequest (400)
         assert_eq!(response.status(), Status::BadRequest);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod me_tests {
+    use rocket::http::{Header, Status};
+    use crate::api::auth::generate_token;
+    use crate::database::{todotask::{create_task, get_task_by_id}, users::create_user};
+    use crate::model::todotask::TaskRequest;
+    use crate::model::users::{UpdateUserRequest, UserProfile};
+
+    use super::*;
+
+    #[rocket::async_test]
+    /// Test getting, updating and deleting the authenticated user
+    /// This test ensures the profile never contains the password and deleting the user removes their tasks.
+    async fn test_get_update_delete_me() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Create a user with a task and get a token for them
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let task = create_task(&user_id, &TaskRequest { title: Some("TESTtask".to_string()), ..Default::default() }).await.expect("Failed to create task: ");
+        let token = generate_token(&user_id, chrono::Duration::minutes(5)).await.expect("Failed to generate token: ");
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        // Get the profile
+        let response = client.get("/users/me").header(auth.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert!(!body.contains("password"), "Profile contains the password: {}", body);
+        let profile: UserProfile = serde_json::from_str(&body).unwrap();
+        assert_eq!(profile.username, Some("TESTuser".to_string()));
+
+        // Changing the password without the current password is rejected
+        let update = UpdateUserRequest {
+            username: None,
+            email: None,
+            password: Some("TESTnewpassword".to_string()),
+            current_password: None,
+        };
+        let response = client.patch("/users/me").header(auth.clone()).json(&update).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // Changing the email works
+        let update = UpdateUserRequest {
+            username: None,
+            email: Some("TESTnew@example.com".to_string()),
+            password: None,
+            current_password: None,
+        };
+        let response = client.patch("/users/me").header(auth.clone()).json(&update).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let profile: UserProfile = response.into_json().await.unwrap();
+        assert_eq!(profile.email, Some("TESTnew@example.com".to_string()));
+
+        // Delete the user
+        let response = client.delete("/users/me").header(auth.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // The task should be gone and the token no longer valid
+        let task = get_task_by_id(&task.id.unwrap().id.to_string()).await;
+        assert!(task.is_err(), "Expected the task to be deleted with the user");
+        let response = client.get("/users/me").header(auth).dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+}
+
+#[cfg(test)]
+mod password_tests {
+    use rocket::http::{Header, Status};
+    use crate::api::{auth::generate_token, problem::Problem};
+    use crate::database::users::create_user;
+    use crate::model::tokens::TokenPair;
+    use crate::model::users::UpdateUserRequest;
+
+    use super::*;
+
+    /// Assert a response body has no password field anywhere in it
+    fn assert_no_password(body: &str) {
+        assert!(!body.to_lowercase().contains("password"), "Response contains a password field: {}", body);
+    }
+
+    #[rocket::async_test]
+    /// Test no user route returns the password
+    /// This test goes through every route that returns user data and checks the body never contains a password field.
+    async fn test_no_route_emits_password() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Sign up
+        let sign_up = SignUpRequest {
+            username: Some("TESTuser".to_string()),
+            email: Some("TEST@example.com".to_string()),
+            password: Some("TESTpassword".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&sign_up).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+        assert_no_password(&response.into_string().await.unwrap());
+
+        // Log in
+        let log_in = LogInRequest {
+            username: Some("TESTuser".to_string()),
+            email: None,
+            password: Some("TESTpassword".to_string()),
+        };
+        let response = client.post("/users/log-in").json(&log_in).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.into_string().await.unwrap();
+        assert_no_password(&body);
+        let token = serde_json::from_str::<TokenPair>(&body).unwrap().access_token;
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        // Get, update and delete the profile
+        let response = client.get("/users/me").header(auth.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_no_password(&response.into_string().await.unwrap());
+
+        let update = UpdateUserRequest {
+            username: Some("TESTuser2".to_string()),
+            email: None,
+            password: None,
+            current_password: None,
+        };
+        let response = client.patch("/users/me").header(auth.clone()).json(&update).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_no_password(&response.into_string().await.unwrap());
+
+        let response = client.delete("/users/me").header(auth).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_no_password(&response.into_string().await.unwrap());
+    }
+
+    #[rocket::async_test]
+    /// Test guessing the current password is limited like logging in
+    /// This test ensures wrong current passwords make the account wait, even when the right one is sent next.
+    async fn test_current_password_delayed() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        let user = create_user("TESTcurrent", "TESTcurrent@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let token = generate_token(&user_id, chrono::Duration::minutes(5)).await.expect("Failed to generate token: ");
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        // Change the password with the wrong current password
+        let wrong = UpdateUserRequest {
+            username: None,
+            email: None,
+            password: Some("TESTnewpassword123".to_string()),
+            current_password: Some("TESTwrong123".to_string()),
+        };
+        for _ in 0..3 {
+            let response = client.patch("/users/me").header(auth.clone()).json(&wrong).dispatch().await;
+            assert_eq!(response.status(), Status::Forbidden);
+        }
+
+        // The account has to wait, even with the right password
+        let right = UpdateUserRequest { current_password: Some("TESTpassword".to_string()), ..wrong };
+        let response = client.patch("/users/me").header(auth).json(&right).dispatch().await;
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "too_many_attempts");
+    }
+
+    #[rocket::async_test]
+    /// Test the password hash is redacted when a user is debug printed
+    async fn test_debug_redacts_password() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let debug = format!("{:?}", user);
+        assert!(!debug.contains("$argon2id$"), "Debug output contains the password hash: {}", debug);
+        assert!(debug.contains("<redacted>"));
+
+        // The requests hold the plaintext password
+        let update = UpdateUserRequest {
+            username: None,
+            email: None,
+            password: Some("TESTplaintext1".to_string()),
+            current_password: Some("TESTplaintext2".to_string()),
+        };
+        let log_in = LogInRequest { username: Some("TESTuser".to_string()), email: None, password: Some("TESTplaintext3".to_string()) };
+        let sign_up = SignUpRequest { username: Some("TESTuser".to_string()), email: None, password: Some("TESTplaintext4".to_string()) };
+        let debug = format!("{:?} {:?} {:?}", update, log_in, sign_up);
+        assert!(!debug.contains("TESTplaintext"), "Debug output contains a password: {}", debug);
+        assert!(debug.contains("TESTuser"));
+    }
+}
+
+#[cfg(test)]
+mod availability_tests {
+    use rocket::http::{Header, Status};
+    use crate::api::auth::generate_token;
+    use crate::api::problem::Problem;
+    use crate::database::users::create_user;
+    use crate::model::users::{UpdateUserRequest, UserAvailability};
+
+    use super::*;
+
+    #[rocket::async_test]
+    /// Test checking if a username and email are free and using taken ones
+    /// This test ensures a taken username or email gives 409 Conflict with the field, when signing up and when updating.
+    async fn test_taken_username_and_email() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // Create a user whose username and email are taken
+        let _ = create_user("TESTuser1", "TEST1@example.com", "TESTpassword").await.expect("Failed to create user: ");
+
+        // Check availability
+        let response = client.get("/users/availability?username=TESTuser1&email=TESTfree@example.com").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let availability: UserAvailability = response.into_json().await.unwrap();
+        assert_eq!(availability.username, Some(false));
+        assert_eq!(availability.email, Some(true));
+
+        // Only the fields asked about are checked
+        let response = client.get("/users/availability?email=TEST1@example.com").dispatch().await;
+        let availability: UserAvailability = response.into_json().await.unwrap();
+        assert_eq!(availability.username, None);
+        assert_eq!(availability.email, Some(false));
+
+        // Nothing to check is rejected
+        let response = client.get("/users/availability").dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // Signing up with the taken email gives 409 with the field
+        let sign_up = SignUpRequest {
+            username: Some("TESTuser2".to_string()),
+            email: Some("TEST1@example.com".to_string()),
+            password: Some("TESTpassword".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&sign_up).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "user_exists");
+        assert_eq!(problem.errors[0].field, "email");
+        assert_eq!(problem.errors[0].code, "taken");
+
+        // Changing the username to the taken one gives 409 with the field
+        let user = create_user("TESTuser2", "TEST2@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let token = generate_token(&user.id.unwrap().id.to_string(), chrono::Duration::minutes(5)).await.expect("Failed to generate token: ");
+        let update = UpdateUserRequest {
+            username: Some("TESTuser1".to_string()),
+            email: None,
+            password: None,
+            current_password: None,
+        };
+        let response = client.patch("/users/me").header(Header::new("Authorization", format!("Bearer {}", token))).json(&update).dispatch().await;
+        assert_eq!(response.status(), Status::Conflict);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.errors[0].field, "username");
+    }
+}
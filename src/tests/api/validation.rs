@@ -0,0 +1,166 @@
+use rocket::local::asynchronous::Client;
+use rocket::http::{Header, Status};
+use crate::api::problem::Problem;
+use crate::model::users::SignUpRequest;
+use crate::model::todotask::TaskRequest;
+use crate::database::{connect, clear_all_test};
+use super::rocket_test_launch;
+
+#[cfg(test)]
+mod rules {
+    use crate::api::problem::FieldError;
+    use crate::api::validation::{check_password, sign_up, task, Validator, ValidationConfig, EMAIL, USERNAME};
+
+    use super::*;
+
+    /// Get the codes of the fields which broke a rule
+    fn codes(result: Result<(), Vec<FieldError>>) -> Vec<(String, String)> {
+        result.err().unwrap_or_default().into_iter().map(|e| (e.field, e.code)).collect()
+    }
+
+    #[test]
+    /// Test emails are checked
+    fn check_email() {
+        for email in ["TEST@example.com", "first.last+tag@sub.example.co.uk"] {
+            assert!(Validator::new().check("email", Some(email), EMAIL).finish().is_ok(), "Expected {} to be valid", email);
+        }
+        for email in ["TESTexample.com", "TEST@example", "TEST@@example.com", ".TEST@example.com", "TEST@-example.com", "TE ST@example.com", "TEST@example.c0m"] {
+            assert!(Validator::new().check("email", Some(email), EMAIL).finish().is_err(), "Expected {} to be invalid", email);
+        }
+    }
+
+    #[test]
+    /// Test usernames are checked
+    fn check_username() {
+        assert!(Validator::new().check("username", Some("TEST_user-1.a"), USERNAME).finish().is_ok());
+        assert_eq!(codes(Validator::new().check("username", Some("ab"), USERNAME).finish()), vec![("username".to_string(), "too_short".to_string())]);
+        assert_eq!(codes(Validator::new().check("username", Some(&"a".repeat(33)), USERNAME).finish()), vec![("username".to_string(), "too_long".to_string())]);
+        assert_eq!(codes(Validator::new().check("username", Some("TEST user"), USERNAME).finish()), vec![("username".to_string(), "invalid_characters".to_string())]);
+    }
+
+    #[test]
+    /// Test the password policy and its configuration
+    fn check_password_policy() {
+        let config = ValidationConfig::default();
+        assert!(check_password("TESTpassword", &config).is_ok());
+        assert_eq!(check_password("TESTpa1", &config).unwrap_err().0, "too_short");
+        assert_eq!(check_password("testpassword", &config).unwrap_err().0, "too_weak");
+        assert_eq!(check_password("Password123", &config).unwrap_err().0, "common_password");
+
+        // The minimums can be changed
+        let config = ValidationConfig { password_min_length: 16, password_min_classes: 3, reject_common_passwords: false };
+        assert_eq!(check_password("TESTpassword", &config).unwrap_err().0, "too_short");
+        assert_eq!(check_password("TESTpasswordTEST", &config).unwrap_err().0, "too_weak");
+        assert!(check_password("TESTpassword1234", &config).is_ok());
+        assert!(check_password("Password12345678", &config).is_ok());
+    }
+
+    #[test]
+    /// Test every broken field of a sign up is reported
+    fn check_sign_up() {
+        let request = SignUpRequest {
+            username: Some("a b".to_string()),
+            email: None,
+            password: Some("password".to_string()),
+        };
+        assert_eq!(codes(sign_up(&request)), vec![
+            ("email".to_string(), "required".to_string()),
+            ("username".to_string(), "invalid_characters".to_string()),
+            ("password".to_string(), "too_weak".to_string()),
+        ]);
+    }
+
+    #[test]
+    /// Test the fields of a task are checked
+    fn check_task() {
+        // The title is only required when creating
+        let request = TaskRequest { description: Some(String::new()), ..Default::default() };
+        assert_eq!(codes(task(&request, true, None)), vec![("title".to_string(), "required".to_string())]);
+        assert!(task(&request, false, None).is_ok());
+
+        // Titles can't be blank or too long
+        let request = TaskRequest { title: Some("  ".to_string()), ..Default::default() };
+        assert_eq!(codes(task(&request, true, None)), vec![("title".to_string(), "blank".to_string())]);
+        let request = TaskRequest { title: Some("a".repeat(201)), ..Default::default() };
+        assert_eq!(codes(task(&request, true, None)), vec![("title".to_string(), "too_long".to_string())]);
+
+        // Datetimes have to be RFC 3339
+        let request = TaskRequest { title: Some("TESTtask".to_string()), due_at: Some("tomorrow".to_string()), ..Default::default() };
+        assert_eq!(codes(task(&request, true, None)), vec![("due_at".to_string(), "invalid_datetime".to_string())]);
+
+        // A task can't be completed before it was created, from the request or the existing task
+        let request = TaskRequest {
+            title: Some("TESTtask".to_string()),
+            created_at: Some("2024-01-02T00:00:00Z".to_string()),
+            completed_at: Some("2024-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(codes(task(&request, true, None)), vec![("completed_at".to_string(), "before_created_at".to_string())]);
+        let request = TaskRequest { completed_at: Some("2024-01-01T00:00:00Z".to_string()), ..Default::default() };
+        assert_eq!(codes(task(&request, false, Some("2024-01-02T00:00:00Z"))), vec![("completed_at".to_string(), "before_created_at".to_string())]);
+        assert!(task(&request, false, Some("2023-12-31T00:00:00Z")).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod request_tests {
+    use crate::model::tokens::TokenPair;
+
+    use super::*;
+
+    #[rocket::async_test]
+    /// Test invalid sign ups and tasks are rejected with the fields
+    /// This test ensures the validation is applied to the routes and nothing is created.
+    async fn test_invalid_requests() {
+        // Connect to the database
+        connect().await;
+        // Clear all test data
+        clear_all_test().await;
+
+        // Create a client for sending requests
+        let client = Client::tracked(rocket_test_launch()).await.expect("valid rocket instance");
+
+        // A bad email and a common password are both reported
+        let user = SignUpRequest {
+            username: Some("test_validator".to_string()),
+            email: Some("not an email".to_string()),
+            password: Some("password123".to_string()),
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.code, "validation_failed");
+        let fields: Vec<&str> = problem.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["email", "password"]);
+
+        // A valid user can sign up
+        let user = SignUpRequest {
+            email: Some("test_validator@example.com".to_string()),
+            password: Some("TESTpassword123".to_string()),
+            ..user
+        };
+        let response = client.post("/users/sign-up").json(&user).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+        let token = response.into_json::<TokenPair>().await.unwrap().access_token;
+        let auth = Header::new("Authorization", format!("Bearer {}", token));
+
+        // An empty title is rejected
+        let task = TaskRequest { title: Some(String::new()), ..Default::default() };
+        let response = client.post("/tasks").header(auth.clone()).json(&task).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.errors[0].field, "title");
+        assert_eq!(problem.errors[0].code, "blank");
+
+        // Completing a task before it was created is rejected
+        let task = TaskRequest { title: Some("TESTtask".to_string()), ..Default::default() };
+        let response = client.post("/tasks").header(auth.clone()).json(&task).dispatch().await;
+        assert_eq!(response.status(), Status::Created);
+        let task: crate::model::todotask::ToDoTask = response.into_json().await.unwrap();
+        let update = TaskRequest { completed_at: Some("2000-01-01T00:00:00Z".to_string()), ..Default::default() };
+        let response = client.patch(format!("/tasks/{}", task.id.unwrap().id.to_raw())).header(auth).json(&update).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let problem: Problem = response.into_json().await.unwrap();
+        assert_eq!(problem.errors[0].code, "before_created_at");
+    }
+}
@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod events {
+    use surrealdb::sql::Thing;
+    use crate::database::{activity::{get_activity, ActivityTarget}, config::SubtaskDeletePolicy, connect, clear_all_test, DB, project::{create_project, delete_project_by_id}, todotask::{create_task, delete_task_by_id, edit_task_by_id, restore_task_by_id}, users::{create_user, delete_user, edit_existing_user}};
+    use crate::model::{activity::{Activity, ActivityAction, ActivityQuery}, project::ProjectRequest, todotask::TaskRequest};
+
+    /// Get the whole activity log of a task or user, newest first
+    async fn activity(target: ActivityTarget<'_>) -> Vec<Activity> {
+        get_activity(target, &ActivityQuery { limit: Some(200), ..Default::default() }).await.expect("Failed to get activity: ").activities
+    }
+
+    #[tokio::test]
+    /// Test every change to a task is recorded by the database, with only the fields which changed
+    async fn record_task_changes() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTactivity", "TESTactivity@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_raw();
+
+        let task = create_task(&user_id, &TaskRequest { title: Some("TESTbefore".to_string()), description: Some("Same".to_string()), ..Default::default() })
+            .await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_raw();
+
+        // An edit records the old and new values of the fields which changed
+        edit_task_by_id(&task_id, &TaskRequest { title: Some("TESTafter".to_string()), description: Some("Same".to_string()), ..Default::default() }, None, None, &user_id)
+            .await.expect("Failed to edit task: ");
+        // An edit which doesn't change anything isn't recorded
+        edit_task_by_id(&task_id, &TaskRequest { title: Some("TESTafter".to_string()), ..Default::default() }, None, None, &user_id)
+            .await.expect("Failed to edit task: ");
+
+        delete_task_by_id(&task_id, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        restore_task_by_id(&task_id, &user_id).await.expect("Failed to restore task: ");
+
+        let history = activity(ActivityTarget::Task(&task_id)).await;
+        let actions: Vec<ActivityAction> = history.iter().map(|a| a.action).collect();
+        assert_eq!(actions, vec![ActivityAction::Restore, ActivityAction::Delete, ActivityAction::Update, ActivityAction::Create]);
+        assert!(history.iter().all(|a| a.actor.as_ref().is_some_and(|actor| actor.id.to_raw() == user_id)));
+
+        let update = &history[2];
+        assert_eq!(update.changes.len(), 1);
+        assert_eq!(update.changes[0].field, "title");
+        assert_eq!(update.changes[0].old, "TESTbefore");
+        assert_eq!(update.changes[0].new, "TESTafter");
+
+        let created = &history[3];
+        assert!(created.changes.iter().all(|c| c.old.is_null()));
+        assert!(created.changes.iter().any(|c| c.field == "title" && c.new == "TESTbefore"));
+        assert!(history[1].changes.iter().any(|c| c.field == "deleted_at" && c.old.is_null() && c.new.is_string()));
+    }
+
+    #[tokio::test]
+    /// Test changes made by deleting a project or a user are recorded in the same transaction
+    async fn record_cascades() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTcascade", "TESTcascade@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_raw();
+
+        let project = create_project(&user_id, &ProjectRequest { name: Some("TESTcascade".to_string()), ..Default::default() })
+            .await.expect("Failed to create project: ");
+        let project_id = project.id.unwrap().id.to_raw();
+        let task = create_task(&user_id, &TaskRequest { title: Some("TESTcascade".to_string()), project: Some(project_id.clone()), ..Default::default() })
+            .await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_raw();
+
+        // Deleting the project moves the task to the inbox
+        delete_project_by_id(&project_id, &user_id).await.expect("Failed to delete project: ");
+        let history = activity(ActivityTarget::Task(&task_id)).await;
+        assert_eq!(history[0].action, ActivityAction::Update);
+        assert_eq!(history[0].actor.as_ref().map(|a| a.id.to_raw()), Some(user_id.clone()));
+        assert_eq!(history[0].changes.len(), 1);
+        assert_eq!(history[0].changes[0].field, "project");
+        assert_eq!(history[0].changes[0].old, format!("Project:{}", project_id));
+        assert!(history[0].changes[0].new.is_null());
+
+        // Changing the password only records that it changed
+        edit_existing_user(&user_id, None, None, Some("TESTnewpassword"), None).await.expect("Failed to edit user: ");
+        let account = activity(ActivityTarget::User(&user_id)).await;
+        let update = account.iter().find(|a| a.action == ActivityAction::Update && a.target.as_ref().is_some_and(|t| t.tb == "User")).expect("Expected the password change");
+        assert_eq!(update.changes.len(), 1);
+        assert_eq!(update.changes[0].field, "password");
+        assert_eq!(update.changes[0].new, "<redacted>");
+
+        // Deleting the user deletes their tasks
+        delete_user(&user_id).await.expect("Failed to delete user: ");
+        let history = activity(ActivityTarget::Task(&task_id)).await;
+        assert_eq!(history[0].action, ActivityAction::Purge);
+        assert_eq!(history[0].actor.as_ref().map(|a| a.id.to_raw()), Some(user_id.clone()));
+        let account = activity(ActivityTarget::User(&user_id)).await;
+        assert!(account.iter().any(|a| a.action == ActivityAction::Delete && a.target.as_ref().is_some_and(|t| t.tb == "User")));
+    }
+
+    #[tokio::test]
+    /// Test activity can't be changed or deleted, even by the system user the backend signs in as
+    async fn append_only() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTappend", "TESTappend@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_raw();
+        let target = Thing::from(("User", user_id.as_str()));
+
+        for sql in ["UPDATE Activity SET action = \"update\" WHERE target = $target", "DELETE Activity WHERE target = $target"] {
+            let mut response = DB.query(sql).bind(("target", target.clone())).await.expect("Failed to run query: ");
+            let result: Result<Vec<Activity>, _> = response.take(0);
+            assert!(result.is_err(), "Expected {} to be refused", sql);
+        }
+
+        let account = activity(ActivityTarget::User(&user_id)).await;
+        assert_eq!(account.len(), 1);
+        assert_eq!(account[0].action, ActivityAction::Create);
+    }
+}
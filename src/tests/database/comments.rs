@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod thread {
+    use crate::database::{connect, clear_all_test, config::SubtaskDeletePolicy, comment::{create_comment, delete_comment_by_id, edit_comment, get_comment_by_id, get_comment_history, get_comments}, todotask::{create_task, delete_task_by_id, purge_task_by_id}, users::create_user, DBCreateError, DBReadError};
+    use crate::model::{comment::CommentQuery, todotask::TaskRequest};
+
+    #[tokio::test]
+    /// Test the comments on a task
+    /// This test writes comments, pages through them, edits one and checks they are deleted when the task is deleted permanently.
+    async fn comment_on_task() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTcommenter", "TESTcommenter@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let task = create_task(&user_id, &TaskRequest { title: Some("TESTdiscussed".to_string()), ..Default::default() })
+            .await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_raw();
+
+        let result = create_comment(&task_id, &user_id, "   ").await;
+        assert!(matches!(result, Err(DBCreateError::BadData(_))), "Expected bad data error for an empty comment");
+
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let comment = create_comment(&task_id, &user_id, &format!("Comment {}", i)).await.expect("Failed to create comment: ");
+            ids.push(comment.id.unwrap().id.to_raw());
+        }
+
+        // Two pages, oldest first
+        let first = get_comments(&task_id, &CommentQuery { limit: Some(2), ..Default::default() }).await.expect("Failed to get comments: ");
+        assert_eq!(first.comments.len(), 2);
+        assert_eq!(first.comments[0].body.as_deref(), Some("Comment 0"));
+        let second = get_comments(&task_id, &CommentQuery { limit: Some(2), cursor: first.next_cursor }).await.expect("Failed to get comments: ");
+        assert_eq!(second.comments.len(), 1);
+        assert_eq!(second.comments[0].body.as_deref(), Some("Comment 2"));
+        assert!(second.next_cursor.is_none());
+        let result = get_comments(&task_id, &CommentQuery { cursor: Some("missing".to_string()), ..Default::default() }).await;
+        assert!(matches!(result, Err(DBReadError::BadData(_))), "Expected bad data error for an invalid cursor");
+
+        // Editing keeps the previous body, an unchanged body isn't added to the history
+        let edited = edit_comment(&ids[0], "Edited").await.expect("Failed to edit comment: ");
+        assert_eq!(edited.body.as_deref(), Some("Edited"));
+        assert!(edited.edited_at.is_some());
+        edit_comment(&ids[0], "Edited").await.expect("Failed to edit comment: ");
+        let history = get_comment_history(&ids[0]).await.expect("Failed to get history: ");
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].body.as_deref(), Some("Comment 0"));
+
+        delete_comment_by_id(&ids[1]).await.expect("Failed to delete comment: ");
+        assert!(matches!(get_comment_by_id(&ids[1]).await, Err(DBReadError::NotFound(_))));
+
+        // Moving the task to the trash keeps its comments, deleting it permanently deletes them and their history
+        delete_task_by_id(&task_id, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        assert!(get_comment_by_id(&ids[0]).await.is_ok(), "The comments should be kept while the task is in the trash");
+        purge_task_by_id(&task_id, &user_id).await.expect("Failed to purge task: ");
+        assert!(matches!(get_comment_by_id(&ids[0]).await, Err(DBReadError::NotFound(_))));
+        assert!(get_comment_history(&ids[0]).await.unwrap().is_empty());
+    }
+}
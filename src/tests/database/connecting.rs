@@ -17,4 +17,49 @@ mod connecting {
         let _ = clear_all_test().await;
     }
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod config {
+    use rocket::figment::{providers::{Format, Toml}, Figment};
+    use crate::database::config::{AuthLevel, DatabaseConfig};
+
+    #[test]
+    /// Test the database section is read correctly, including a database level user
+    fn read_database_config() {
+        let figment = Figment::from(Toml::string("
+        [database]
+        endpoint = \"mem://\"
+        namespace = \"TESTns\"
+        database = \"TESTdb\"
+        auth = \"database\"
+        username = \"TESTuser\"
+        password = \"TESTpassword\"
+        "));
+
+        let config: DatabaseConfig = figment.extract_inner("database").expect("Failed to read config: ");
+
+        // Check each of the fields match up
+        assert_eq!(config.endpoint, "mem://", "Endpoint mismatch");
+        assert_eq!(config.namespace, "TESTns", "Namespace mismatch");
+        assert_eq!(config.database, "TESTdb", "Database mismatch");
+        assert_eq!(config.auth, AuthLevel::Database, "Auth level mismatch");
+        assert_eq!(config.username, Some("TESTuser".to_string()), "Username mismatch");
+        assert_eq!(config.password, Some("TESTpassword".to_string()), "Password mismatch");
+    }
+
+    #[test]
+    /// Test missing fields fall back to the defaults
+    fn read_database_config_defaults() {
+        let figment = Figment::from(Toml::string("
+        [database]
+        endpoint = \"mem://\"
+        auth = \"none\"
+        "));
+
+        let config: DatabaseConfig = figment.extract_inner("database").expect("Failed to read config: ");
+
+        assert_eq!(config.endpoint, "mem://", "Endpoint mismatch");
+        assert_eq!(config.namespace, "Dev", "Namespace should default to Dev");
+        assert_eq!(config.auth, AuthLevel::None, "Auth level mismatch");
+    }
+}
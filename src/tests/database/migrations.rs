@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod embedded {
+    use crate::database::migrations::MIGRATIONS;
+
+    #[test]
+    /// Test the migrations are in order with no repeated versions
+    fn migrations_in_order() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version, "Migration {} is out of order", pair[1].version);
+        }
+    }
+
+    #[test]
+    /// Test the checksum is a SHA-256 hex string and ignores line endings
+    fn migration_checksum() {
+        let migration = MIGRATIONS[0].clone();
+        let checksum = migration.checksum();
+        assert_eq!(checksum.len(), 64, "Checksum is not SHA-256 hex: {}", checksum);
+
+        // The same migration checked out with CRLF line endings should have the same checksum
+        let crlf = crate::database::migrations::Migration {
+            sql: Box::leak(migration.sql.replace('\n', "\r\n").into_boxed_str()),
+            ..migration
+        };
+        assert_eq!(crlf.checksum(), checksum, "Checksum changed with line endings");
+    }
+}
+
+#[cfg(test)]
+mod applying {
+    use crate::database::{connect, DBMigrationError, DB};
+    use crate::database::config::MigrationMode;
+    use crate::database::migrations::{migrate, status, MigrationState};
+
+    #[tokio::test]
+    /// Test connecting applies every migration and running them again does nothing
+    async fn migrate_all_applied() {
+        // Connecting applies the migrations
+        let _ = connect().await;
+
+        // Check every migration is applied
+        let statuses = status().await.expect("Failed to get migration status: ");
+        assert!(statuses.iter().all(|s| s.state == MigrationState::Applied), "Not all migrations applied: {:?}", statuses);
+
+        // Check applying again does not fail
+        let result = migrate(MigrationMode::Apply).await;
+        assert!(result.is_ok(), "Failed to re-run migrations: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    /// Test the status refuses a database with a migration this binary does not know about
+    async fn migrate_database_ahead() {
+        let _ = connect().await;
+
+        // Record a migration from the future
+        DB.query("CREATE _migrations SET version = 999999, name = 'TEST', checksum = '';")
+            .await
+            .expect("Failed to add test migration: ");
+
+        let result = status().await;
+
+        // Remove it again before asserting so other tests are not affected
+        DB.query("DELETE _migrations WHERE version = 999999;")
+            .await
+            .expect("Failed to remove test migration: ");
+
+        assert!(matches!(result, Err(DBMigrationError::DatabaseAhead(_))), "Expected database ahead error: {:?}", result);
+    }
+}
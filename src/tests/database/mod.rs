@@ -3,4 +3,28 @@ mod connecting;
 #[cfg(test)]
 mod todotasks;
 #[cfg(test)]
-mod users;
\ No newline at end of file
+mod users;
+#[cfg(test)]
+mod password;
+#[cfg(test)]
+mod migrations;
+#[cfg(test)]
+mod tokens;
+#[cfg(test)]
+mod recurrence;
+#[cfg(test)]
+mod projects;
+#[cfg(test)]
+mod tags;
+#[cfg(test)]
+mod sharing;
+#[cfg(test)]
+mod notifications;
+#[cfg(test)]
+mod comments;
+#[cfg(test)]
+mod activity;
+#[cfg(test)]
+mod rate_limit;
+#[cfg(test)]
+mod sync;
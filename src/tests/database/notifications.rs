@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod assigning {
+    use crate::database::{connect, clear_all_test, notification::{create_notification, get_notifications, mark_all_notifications_read, mark_notification_read, notify_task_users}, share::{check_permission, ShareTarget}, todotask::{create_task, get_tasks_assigned_to, set_assignee}, users::create_user};
+    use crate::model::{notification::NotificationKind, share::ShareRole, todotask::TaskRequest};
+
+    #[tokio::test]
+    /// Test assigning tasks and the notifications feed
+    /// This test assigns a task, checks the assignee can edit it and sees it as assigned, then reads the notifications.
+    async fn assign_and_notify() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let owner = create_user("TESTowner", "TESTowner@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let owner_id = owner.id.unwrap().id.to_string();
+        let other = create_user("TESTother", "TESTother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let other_id = other.id.unwrap().id.to_string();
+
+        let task = create_task(&owner_id, &TaskRequest { title: Some("TESTassigned".to_string()), ..Default::default() })
+            .await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_raw();
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Viewer).await.unwrap());
+
+        // The assignee can edit the task but not delete it
+        let task = set_assignee(&task_id, Some(&other_id), &owner_id).await.expect("Failed to assign task: ");
+        assert_eq!(task.assignee.as_ref().map(|a| a.id.to_raw()), Some(other_id.clone()));
+        assert!(check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Editor).await.unwrap());
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::CoOwner).await.unwrap());
+        let assigned = get_tasks_assigned_to(&other_id).await.expect("Failed to get assigned tasks: ");
+        assert_eq!(assigned.len(), 1);
+
+        // The owner and assignee are notified, except whoever did it
+        create_notification(&other_id, NotificationKind::Assigned, &task_id, &owner_id).await.expect("Failed to create notification: ");
+        notify_task_users(&task, NotificationKind::Completed, &other_id).await.expect("Failed to notify users: ");
+        let owner_notifications = get_notifications(&owner_id, false).await.unwrap();
+        assert_eq!(owner_notifications.len(), 1);
+        assert_eq!(owner_notifications[0].kind, NotificationKind::Completed);
+        let other_notifications = get_notifications(&other_id, true).await.unwrap();
+        assert_eq!(other_notifications.len(), 1);
+        assert_eq!(other_notifications[0].kind, NotificationKind::Assigned);
+
+        // Users can only read their own notifications
+        let notification_id = other_notifications[0].id.as_ref().unwrap().id.to_raw();
+        assert!(mark_notification_read(&notification_id, &owner_id).await.is_err());
+        let read = mark_notification_read(&notification_id, &other_id).await.expect("Failed to read notification: ");
+        assert!(read.read_at.is_some());
+        assert!(get_notifications(&other_id, true).await.unwrap().is_empty());
+        assert_eq!(mark_all_notifications_read(&owner_id).await.unwrap(), 1);
+        assert!(get_notifications(&owner_id, true).await.unwrap().is_empty());
+
+        // Unassigning takes the task away from the assignee
+        set_assignee(&task_id, None, &owner_id).await.expect("Failed to unassign task: ");
+        assert!(get_tasks_assigned_to(&other_id).await.unwrap().is_empty());
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Editor).await.unwrap());
+    }
+}
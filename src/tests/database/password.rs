@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod hashing {
+    use std::time::Instant;
+    use crate::database::password::{hash_password, needs_rehash, verify_dummy_password, verify_password};
+
+    #[test]
+    /// Test a hashed password can be verified with the original password
+    fn hash_and_verify_password() {
+        // Hash a password
+        let hash = hash_password("TESTpassword").expect("Failed to hash password: ");
+
+        // Check the hash is an Argon2id PHC string and not the password itself
+        assert!(hash.starts_with("$argon2id$"), "Hash is not an Argon2id PHC string: {}", hash);
+        assert_ne!(hash, "TESTpassword", "Password was not hashed");
+
+        // Check the password verifies against the hash
+        assert!(verify_password("TESTpassword", &hash), "Failed to verify correct password");
+    }
+
+    #[test]
+    /// Test a wrong password is rejected
+    fn verify_wrong_password() {
+        let hash = hash_password("TESTpassword").expect("Failed to hash password: ");
+
+        assert!(!verify_password("WRONGpassword", &hash), "Expected wrong password to be rejected");
+    }
+
+    #[test]
+    /// Test a missing or unreadable hash still costs a full check, so unknown users can't be told apart by timing
+    fn verify_missing_hash_timing() {
+        let hash = hash_password("TESTpassword").expect("Failed to hash password: ");
+        verify_dummy_password("TESTpassword");
+
+        let start = Instant::now();
+        assert!(!verify_password("WRONGpassword", &hash));
+        let known = start.elapsed();
+
+        let start = Instant::now();
+        assert!(!verify_password("WRONGpassword", ""));
+        let unknown = start.elapsed();
+
+        assert!(unknown * 2 > known, "Expected an unreadable hash to take as long as a real one: {:?} vs {:?}", unknown, known);
+    }
+
+    #[test]
+    /// Test hashing the same password twice gives different hashes because of the salt
+    fn hash_password_salted() {
+        let hash1 = hash_password("TESTpassword").expect("Failed to hash password: ");
+        let hash2 = hash_password("TESTpassword").expect("Failed to hash password: ");
+
+        assert_ne!(hash1, hash2, "Expected different salts to give different hashes");
+    }
+
+    #[test]
+    /// Test only hashes with outdated parameters need rehashing
+    fn needs_rehash_outdated() {
+        // A hash with the current parameters should not need rehashing
+        let hash = hash_password("TESTpassword").expect("Failed to hash password: ");
+        assert!(!needs_rehash(&hash), "Fresh hash should not need rehashing");
+
+        // A hash with weaker parameters should need rehashing
+        let old_hash = "$argon2id$v=19$m=4096,t=3,p=1$c29tZXNhbHQ$1h+LwR5g7rhAqkpQZWX+s7O8NuV1ZtVSKrk0f8Qkq6o";
+        assert!(needs_rehash(old_hash), "Outdated hash should need rehashing");
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod managing {
+    use crate::database::{connect, clear_all_test, project::{create_project, delete_project_by_id, edit_project_by_id, get_projects_by_user}, todotask::{create_task, get_task_by_id, get_tasks_by_user}, share::{check_permission, ShareTarget}, users::create_user, DBCreateError};
+    use crate::model::{project::ProjectRequest, share::ShareRole, todotask::{TaskQuery, TaskRequest}};
+
+    /// Create a task in a project, or the inbox if there is no project
+    async fn task_in(owner: &str, title: &str, project: Option<&str>) -> Result<String, DBCreateError> {
+        let task = create_task(owner, &TaskRequest {
+            title: Some(title.to_string()),
+            project: project.map(|p| p.to_string()),
+            ..Default::default()
+        }).await?;
+        Ok(task.id.unwrap().id.to_raw())
+    }
+
+    #[tokio::test]
+    /// Test grouping tasks into projects
+    /// This test lists the tasks of a project and the inbox, then archives and deletes the project.
+    async fn group_tasks_by_project() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let project = create_project(&user_id, &ProjectRequest { name: Some("TESTproject".to_string()), ..Default::default() })
+            .await.expect("Failed to create project: ");
+        let project_id = project.id.unwrap().id.to_raw();
+        assert!(check_permission(&user_id, ShareTarget::Project(&project_id), ShareRole::CoOwner).await.expect("Failed to check owner: "));
+
+        let in_project = task_in(&user_id, "TESTin project", Some(&project_id)).await.expect("Failed to create task: ");
+        let _in_inbox = task_in(&user_id, "TESTin inbox", None).await.expect("Failed to create task: ");
+
+        let project_query = TaskQuery { project: Some(project_id.clone()), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &project_query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 1);
+        assert_eq!(page.tasks[0].title.as_deref(), Some("TESTin project"));
+
+        let inbox_query = TaskQuery { project: Some("inbox".to_string()), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &inbox_query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 1);
+        assert_eq!(page.tasks[0].title.as_deref(), Some("TESTin inbox"));
+
+        // Archiving hides the project and its tasks, and no more tasks can be added to it
+        let archive = ProjectRequest { archived: Some(true), ..Default::default() };
+        let project = edit_project_by_id(&project_id, &archive).await.expect("Failed to archive project: ");
+        assert!(project.archived_at.is_some(), "The project should be archived");
+        assert!(get_projects_by_user(&user_id, false).await.expect("Failed to get projects: ").is_empty());
+        assert_eq!(get_projects_by_user(&user_id, true).await.expect("Failed to get projects: ").len(), 1);
+
+        let page = get_tasks_by_user(&user_id, &TaskQuery::default()).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 1, "Tasks in archived projects should be hidden");
+        let page = get_tasks_by_user(&user_id, &project_query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 1, "Tasks in an archived project are listed when it is asked for");
+
+        let result = task_in(&user_id, "TESTarchived", Some(&project_id)).await;
+        assert!(matches!(result, Err(DBCreateError::BadData(_))), "Expected bad data error for an archived project");
+
+        // Another user can't put tasks in the project
+        let other = create_user("TESTother", "TESTother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let other_id = other.id.unwrap().id.to_string();
+        assert!(!check_permission(&other_id, ShareTarget::Project(&project_id), ShareRole::Viewer).await.expect("Failed to check owner: "));
+        let restore = ProjectRequest { archived: Some(false), ..Default::default() };
+        edit_project_by_id(&project_id, &restore).await.expect("Failed to restore project: ");
+        let result = task_in(&other_id, "TESTforeign", Some(&project_id)).await;
+        assert!(matches!(result, Err(DBCreateError::BadData(_))), "Expected bad data error for another users project");
+
+        // Deleting the project moves its tasks to the inbox
+        delete_project_by_id(&project_id, &user_id).await.expect("Failed to delete project: ");
+        let task = get_task_by_id(&in_project).await.expect("The task should still exist: ");
+        assert!(task.project.is_none(), "The task should be in the inbox");
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod buckets {
+    use crate::database::connect;
+    use crate::database::rate_limit::{purge_rate_limits, take_token};
+
+    #[tokio::test]
+    /// Test tokens are taken from a bucket until it is empty
+    async fn take_token_until_empty() {
+        // Connect to the database
+        let _ = connect().await;
+
+        // A new bucket starts full
+        let tokens = take_token("TEST:take_token", 2, 1).await.expect("Failed to take token: ");
+        assert_eq!(tokens, 2.0);
+        let tokens = take_token("TEST:take_token", 2, 1).await.expect("Failed to take token: ");
+        assert!((1.0..2.0).contains(&tokens), "Expected one token left: {}", tokens);
+
+        // Once it is empty no more are taken
+        let tokens = take_token("TEST:take_token", 2, 1).await.expect("Failed to take token: ");
+        assert!(tokens < 1.0, "Expected the bucket to be empty: {}", tokens);
+        let again = take_token("TEST:take_token", 2, 1).await.expect("Failed to take token: ");
+        assert!(again < 1.0 && again >= tokens, "Expected the bucket to stay empty: {}", again);
+
+        // Buckets which haven't filled up yet aren't purged
+        let _ = purge_rate_limits().await.expect("Failed to purge rate limits: ");
+        let tokens = take_token("TEST:take_token", 2, 1).await.expect("Failed to take token: ");
+        assert!(tokens < 1.0, "Expected the bucket to be kept: {}", tokens);
+    }
+}
+
+#[cfg(test)]
+mod login_attempts {
+    use chrono::{Duration, Utc};
+    use crate::database::connect;
+    use crate::database::rate_limit::{clear_login_attempts, get_login_attempt, purge_rate_limits, record_login_failure, set_login_retry_at};
+
+    #[tokio::test]
+    /// Test failed log ins are counted, delayed and cleared
+    async fn record_and_clear_failures() {
+        // Connect to the database
+        let _ = connect().await;
+        let _ = clear_login_attempts("username:TEST_failures").await;
+
+        // Failures are counted in a row
+        for expected in 1..=3 {
+            let failures = record_login_failure("username:TEST_failures", Duration::minutes(15)).await.expect("Failed to record failure: ");
+            assert_eq!(failures, expected);
+        }
+
+        // The account can be delayed
+        let retry_at = Utc::now() + Duration::seconds(30);
+        set_login_retry_at("username:TEST_failures", retry_at).await.expect("Failed to set retry at: ");
+        let attempt = get_login_attempt("username:TEST_failures").await.expect("Failed to get attempt: ").expect("Expected an attempt");
+        assert_eq!(attempt.failures, 3);
+        assert!(attempt.retry_at.is_some(), "Expected a retry at");
+
+        // Logging in clears the failures
+        clear_login_attempts("username:TEST_failures").await.expect("Failed to clear attempts: ");
+        let attempt = get_login_attempt("username:TEST_failures").await.expect("Failed to get attempt: ");
+        assert!(attempt.is_none(), "Expected the attempts to be cleared: {:?}", attempt);
+    }
+
+    #[tokio::test]
+    /// Test old failures are forgotten and purged
+    async fn forget_old_failures() {
+        // Connect to the database
+        let _ = connect().await;
+        let _ = clear_login_attempts("username:TEST_forget").await;
+
+        // A failure forgotten straight away starts the count again
+        let _ = record_login_failure("username:TEST_forget", Duration::zero()).await.expect("Failed to record failure: ");
+        let failures = record_login_failure("username:TEST_forget", Duration::zero()).await.expect("Failed to record failure: ");
+        assert_eq!(failures, 1);
+
+        // And is purged
+        let purged = purge_rate_limits().await.expect("Failed to purge rate limits: ");
+        assert!(purged >= 1, "Expected the attempt to be purged");
+        let attempt = get_login_attempt("username:TEST_forget").await.expect("Failed to get attempt: ");
+        assert!(attempt.is_none(), "Expected the attempt to be purged: {:?}", attempt);
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod parsing {
+    use chrono::Weekday;
+    use crate::database::recurrence::{Frequency, RecurrenceRule};
+
+    #[test]
+    /// Test parsing a rule and writing it back out in the normalised form
+    fn parse_and_normalise() {
+        let rule = RecurrenceRule::parse("RRULE:freq=weekly;byday=TH,mo;interval=2;count=10").expect("Failed to parse rule: ");
+        assert_eq!(rule.frequency, Frequency::Weekly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.by_day, vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(rule.count, Some(10));
+        assert_eq!(rule.to_string(), "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10");
+
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20240131").expect("Failed to parse rule: ");
+        assert_eq!(rule.to_string(), "FREQ=DAILY;INTERVAL=1;UNTIL=20240131T235959Z");
+    }
+
+    #[test]
+    /// Test invalid and unsupported rules are rejected
+    fn parse_invalid() {
+        assert!(RecurrenceRule::parse("").is_err(), "FREQ should be required");
+        assert!(RecurrenceRule::parse("FREQ=YEARLY").is_err(), "YEARLY is not supported");
+        assert!(RecurrenceRule::parse("FREQ=DAILY;INTERVAL=0").is_err(), "INTERVAL must be positive");
+        assert!(RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=XX").is_err(), "Unknown weekday");
+        assert!(RecurrenceRule::parse("FREQ=DAILY;COUNT=2;UNTIL=20240101").is_err(), "COUNT and UNTIL can't both be used");
+        assert!(RecurrenceRule::parse("FREQ=MONTHLY;BYDAY=MO").is_err(), "BYDAY is not supported for MONTHLY");
+        assert!(RecurrenceRule::parse("FREQ=DAILY;BYHOUR=9").is_err(), "BYHOUR is not supported");
+    }
+}
+
+#[cfg(test)]
+mod occurrences {
+    use chrono::{DateTime, Utc};
+    use crate::database::recurrence::RecurrenceRule;
+
+    fn time(t: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(t).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    /// Test daily rules with an interval and weekdays
+    fn daily() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY;INTERVAL=3").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-01-30T09:00:00Z"), 1), Some(time("2024-02-02T09:00:00Z")));
+
+        // Weekdays only, 2024-01-05 is a Friday
+        let rule = RecurrenceRule::parse("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-01-05T09:00:00Z"), 1), Some(time("2024-01-08T09:00:00Z")));
+    }
+
+    #[test]
+    /// Test weekly rules with an interval and weekdays
+    fn weekly() {
+        let rule = RecurrenceRule::parse("FREQ=WEEKLY").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-01-01T09:00:00Z"), 1), Some(time("2024-01-08T09:00:00Z")));
+
+        // Every other week on Monday and Thursday, 2024-01-01 is a Monday
+        let rule = RecurrenceRule::parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-01-01T09:00:00Z"), 1), Some(time("2024-01-04T09:00:00Z")));
+        assert_eq!(rule.next_occurrence(time("2024-01-04T09:00:00Z"), 2), Some(time("2024-01-15T09:00:00Z")));
+    }
+
+    #[test]
+    /// Test monthly rules skip months which don't have the day
+    fn monthly() {
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-01-31T09:00:00Z"), 1), Some(time("2024-03-31T09:00:00Z")));
+
+        let rule = RecurrenceRule::parse("FREQ=MONTHLY;INTERVAL=6").unwrap();
+        assert_eq!(rule.next_occurrence(time("2024-09-15T09:00:00Z"), 1), Some(time("2025-03-15T09:00:00Z")));
+    }
+
+    #[test]
+    /// Test rules finish after COUNT occurrences or after UNTIL
+    fn finishing() {
+        let rule = RecurrenceRule::parse("FREQ=DAILY;COUNT=2").unwrap();
+        assert!(rule.next_occurrence(time("2024-01-01T09:00:00Z"), 1).is_some());
+        assert_eq!(rule.next_occurrence(time("2024-01-02T09:00:00Z"), 2), None);
+
+        let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20240102T090000Z").unwrap();
+        assert!(rule.next_occurrence(time("2024-01-01T09:00:00Z"), 1).is_some());
+        assert_eq!(rule.next_occurrence(time("2024-01-02T09:00:00Z"), 2), None);
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod roles {
+    use crate::database::{connect, clear_all_test, project::create_project, share::{check_permission, get_shares, get_tasks_shared_with, share, unshare, ShareTarget}, todotask::create_task, users::create_user, DBCreateError};
+    use crate::model::{project::ProjectRequest, share::ShareRole, todotask::TaskRequest};
+
+    #[tokio::test]
+    /// Test sharing tasks and projects with roles
+    /// This test shares a task directly and through its project, checking the highest role is used.
+    async fn share_with_roles() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let owner = create_user("TESTowner", "TESTowner@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let owner_id = owner.id.unwrap().id.to_string();
+        let other = create_user("TESTother", "TESTother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let other_id = other.id.unwrap().id.to_string();
+
+        let project = create_project(&owner_id, &ProjectRequest { name: Some("TESTproject".to_string()), ..Default::default() })
+            .await.expect("Failed to create project: ");
+        let project_id = project.id.unwrap().id.to_raw();
+        let task = create_task(&owner_id, &TaskRequest {
+            title: Some("TESTshared".to_string()),
+            project: Some(project_id.clone()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_raw();
+
+        // The owner can do everything, nobody else can do anything until it is shared
+        assert!(check_permission(&owner_id, ShareTarget::Task(&task_id), ShareRole::CoOwner).await.unwrap());
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Viewer).await.unwrap());
+        assert!(get_tasks_shared_with(&other_id).await.unwrap().is_empty());
+
+        let result = share(ShareTarget::Task(&task_id), &owner_id, ShareRole::Editor).await;
+        assert!(matches!(result, Err(DBCreateError::BadData(_))), "Expected bad data error sharing with the owner");
+
+        // A viewer can see the task but not edit it
+        share(ShareTarget::Task(&task_id), &other_id, ShareRole::Viewer).await.expect("Failed to share task: ");
+        assert!(check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Viewer).await.unwrap());
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Editor).await.unwrap());
+
+        // A role on the project applies to its tasks, the highest role is used
+        share(ShareTarget::Project(&project_id), &other_id, ShareRole::Editor).await.expect("Failed to share project: ");
+        assert!(check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Editor).await.unwrap());
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::CoOwner).await.unwrap());
+        let shared = get_tasks_shared_with(&other_id).await.expect("Failed to get shared tasks: ");
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].role, ShareRole::Editor);
+
+        // Sharing again changes the role
+        share(ShareTarget::Task(&task_id), &other_id, ShareRole::CoOwner).await.expect("Failed to share task: ");
+        assert!(check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::CoOwner).await.unwrap());
+        assert_eq!(get_shares(ShareTarget::Task(&task_id)).await.unwrap().len(), 1);
+
+        unshare(ShareTarget::Task(&task_id), &other_id).await.expect("Failed to unshare task: ");
+        unshare(ShareTarget::Project(&project_id), &other_id).await.expect("Failed to unshare project: ");
+        assert!(!check_permission(&other_id, ShareTarget::Task(&task_id), ShareRole::Viewer).await.unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod changes {
+    use std::collections::HashSet;
+    use crate::database::{connect, clear_all_test, config::SubtaskDeletePolicy, project::create_project, share::{share, unshare, ShareTarget}, sync::get_changes, todotask::{create_task, delete_task_by_id, edit_task_by_id, purge_task_by_id, set_assignee}, users::create_user, DBReadError};
+    use crate::model::{project::ProjectRequest, share::ShareRole, sync::SyncQuery, todotask::TaskRequest};
+
+    /// Create a task and get its id
+    async fn task(owner: &str, title: &str) -> String {
+        let task = create_task(owner, &TaskRequest { title: Some(title.to_string()), ..Default::default() })
+            .await.expect("Failed to create task: ");
+        task.id.unwrap().id.to_raw()
+    }
+
+    #[tokio::test]
+    /// Test the changes are paged without skipping any, and recent changes are sent again on the next sync
+    async fn page_through_changes() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTsyncpages", "TESTsyncpages@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_raw();
+        let mut ids = HashSet::new();
+        for i in 0..3 {
+            ids.insert(task(&user_id, &format!("TESTsync {}", i)).await);
+        }
+
+        // One change at a time, each page carries on from the last
+        let mut seen = HashSet::new();
+        let mut since = None;
+        loop {
+            let page = get_changes(&user_id, &SyncQuery { since: since.clone(), limit: Some(1) }).await.expect("Failed to get changes: ");
+            assert!(page.changes.len() <= 1);
+            for change in &page.changes {
+                assert!(seen.insert(change.id.id.to_raw()), "A change was sent twice in one sync");
+            }
+            since = Some(page.cursor);
+            if !page.has_more {
+                break;
+            }
+        }
+        assert_eq!(seen, ids);
+
+        // The changes were just made, so they are sent again next time in case one committed late
+        let page = get_changes(&user_id, &SyncQuery { since: since.clone(), limit: None }).await.expect("Failed to get changes: ");
+        assert_eq!(page.changes.len(), 3);
+        assert!(!page.has_more);
+
+        let result = get_changes(&user_id, &SyncQuery { since: Some("not a cursor".to_string()), limit: None }).await;
+        assert!(matches!(result, Err(DBReadError::BadData(_))));
+    }
+
+    #[tokio::test]
+    /// Test tasks shared with or assigned to a user are synced to them
+    async fn sync_shared_and_assigned_tasks() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let owner = create_user("TESTsyncowner", "TESTsyncowner@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let owner_id = owner.id.unwrap().id.to_raw();
+        let other = create_user("TESTsyncother", "TESTsyncother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let other_id = other.id.unwrap().id.to_raw();
+
+        let shared = task(&owner_id, "TESTsync shared").await;
+        let assigned = task(&owner_id, "TESTsync assigned").await;
+        let _private = task(&owner_id, "TESTsync private").await;
+        share(ShareTarget::Task(&shared), &other_id, ShareRole::Viewer).await.expect("Failed to share task: ");
+        set_assignee(&assigned, Some(&other_id), &owner_id).await.expect("Failed to assign task: ");
+
+        let page = get_changes(&other_id, &SyncQuery::default()).await.expect("Failed to get changes: ");
+        let synced: HashSet<String> = page.changes.iter().map(|c| c.id.id.to_raw()).collect();
+        assert_eq!(synced, HashSet::from([shared.clone(), assigned]));
+
+        // A change by the owner is sent to them
+        let edit = TaskRequest { title: Some("TESTsync renamed".to_string()), ..Default::default() };
+        edit_task_by_id(&shared, &edit, None, None, &owner_id).await.expect("Failed to edit task: ");
+        let page = get_changes(&other_id, &SyncQuery { since: Some(page.cursor), limit: None }).await.expect("Failed to get changes: ");
+        let renamed = page.changes.iter().find(|c| c.id.id.to_raw() == shared).expect("Expected the shared task");
+        assert_eq!(renamed.task.as_ref().and_then(|t| t.title.as_deref()), Some("TESTsync renamed"));
+    }
+
+    #[tokio::test]
+    /// Test a collaborator is told to remove tasks which were purged, unshared, unassigned or moved out of a shared project
+    async fn sync_removed_tasks() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let owner = create_user("TESTremoveowner", "TESTremoveowner@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let owner_id = owner.id.unwrap().id.to_raw();
+        let other = create_user("TESTremoveother", "TESTremoveother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let other_id = other.id.unwrap().id.to_raw();
+
+        let purged = task(&owner_id, "TESTremove purged").await;
+        let unshared = task(&owner_id, "TESTremove unshared").await;
+        let unassigned = task(&owner_id, "TESTremove unassigned").await;
+        let still_shared = task(&owner_id, "TESTremove still shared").await;
+        let project = create_project(&owner_id, &ProjectRequest { name: Some("TESTremove project".to_string()), ..Default::default() })
+            .await.expect("Failed to create project: ");
+        let project_id = project.id.unwrap().id.to_raw();
+        let moved = create_task(&owner_id, &TaskRequest { title: Some("TESTremove moved".to_string()), project: Some(project_id.clone()), ..Default::default() })
+            .await.expect("Failed to create task: ").id.unwrap().id.to_raw();
+
+        for id in [&purged, &unshared, &still_shared] {
+            share(ShareTarget::Task(id), &other_id, ShareRole::Viewer).await.expect("Failed to share task: ");
+        }
+        share(ShareTarget::Project(&project_id), &other_id, ShareRole::Viewer).await.expect("Failed to share project: ");
+        for id in [&unassigned, &still_shared] {
+            set_assignee(id, Some(&other_id), &owner_id).await.expect("Failed to assign task: ");
+        }
+        let page = get_changes(&other_id, &SyncQuery::default()).await.expect("Failed to get changes: ");
+        assert_eq!(page.changes.len(), 5);
+
+        delete_task_by_id(&purged, SubtaskDeletePolicy::Cascade, &owner_id).await.expect("Failed to delete task: ");
+        purge_task_by_id(&purged, &owner_id).await.expect("Failed to purge task: ");
+        unshare(ShareTarget::Task(&unshared), &other_id).await.expect("Failed to unshare task: ");
+        set_assignee(&unassigned, None, &owner_id).await.expect("Failed to unassign task: ");
+        set_assignee(&still_shared, None, &owner_id).await.expect("Failed to unassign task: ");
+        let inbox = TaskRequest { project: Some("inbox".to_string()), ..Default::default() };
+        edit_task_by_id(&moved, &inbox, None, None, &owner_id).await.expect("Failed to move task: ");
+
+        // Every task they lost is sent as deleted, the task still shared with them is sent as it is
+        let page = get_changes(&other_id, &SyncQuery { since: Some(page.cursor), limit: None }).await.expect("Failed to get changes: ");
+        let removed: HashSet<String> = page.changes.iter().filter(|c| c.deleted && c.task.is_none()).map(|c| c.id.id.to_raw()).collect();
+        assert_eq!(removed, HashSet::from([purged, unshared, unassigned, moved]));
+        let kept = page.changes.iter().find(|c| c.id.id.to_raw() == still_shared).expect("Expected the task still shared");
+        assert!(!kept.deleted);
+    }
+}
@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod tagging {
+    use crate::database::{connect, clear_all_test, tag::{create_tag, delete_tag_by_id, get_task_tags, merge_tags, rename_tag, tag_task, untag_task}, todotask::{create_task, get_tasks_by_user}, users::create_user, DBCreateError, DBEditError};
+    use crate::model::todotask::{TagMatch, TaskQuery, TaskRequest};
+
+    /// Get the titles of the tasks with the tags
+    async fn titles_with(user_id: &str, tags: &[&str], tag_match: TagMatch) -> Vec<String> {
+        let query = TaskQuery {
+            tag: tags.iter().map(|t| t.to_string()).collect(),
+            tag_match: Some(tag_match),
+            sort: Some(crate::model::todotask::TaskSort::Title),
+            ..Default::default()
+        };
+        let page = get_tasks_by_user(user_id, &query).await.expect("Failed to get tasks: ");
+        page.tasks.into_iter().filter_map(|t| t.title).collect()
+    }
+
+    #[tokio::test]
+    /// Test tagging tasks and filtering by tags
+    /// This test filters with any and all of the tags, then renames, merges and deletes tags.
+    async fn tag_and_filter_tasks() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let work = create_tag(&user_id, "TESTwork").await.expect("Failed to create tag: ").id.unwrap().id.to_raw();
+        let urgent = create_tag(&user_id, "TESTurgent").await.expect("Failed to create tag: ").id.unwrap().id.to_raw();
+        let result = create_tag(&user_id, "TESTwork").await;
+        assert!(matches!(result, Err(DBCreateError::AlreadyExists(_))), "Expected already exists error for a repeated name");
+
+        let mut ids = Vec::new();
+        for title in ["TESTa", "TESTb", "TESTc"] {
+            let task = create_task(&user_id, &TaskRequest { title: Some(title.to_string()), ..Default::default() })
+                .await.expect("Failed to create task: ");
+            ids.push(task.id.unwrap().id.to_raw());
+        }
+        tag_task(&ids[0], &work).await.expect("Failed to tag task: ");
+        tag_task(&ids[0], &urgent).await.expect("Failed to tag task: ");
+        tag_task(&ids[1], &work).await.expect("Failed to tag task: ");
+        // Tagging twice changes nothing
+        let tags = tag_task(&ids[1], &work).await.expect("Failed to tag task: ");
+        assert_eq!(tags.len(), 1);
+
+        assert_eq!(titles_with(&user_id, &[&work, &urgent], TagMatch::Any).await, vec!["TESTa", "TESTb"]);
+        assert_eq!(titles_with(&user_id, &[&work, &urgent], TagMatch::All).await, vec!["TESTa"]);
+
+        // Renaming can't take the name of another tag
+        let result = rename_tag(&urgent, "TESTwork").await;
+        assert!(matches!(result, Err(DBEditError::AlreadyExists(_))), "Expected already exists error for a taken name");
+        let tag = rename_tag(&urgent, "TESTsoon").await.expect("Failed to rename tag: ");
+        assert_eq!(tag.name.as_deref(), Some("TESTsoon"));
+
+        // Merging moves the tasks of the merged tag without repeating tags
+        merge_tags(&work, &urgent).await.expect("Failed to merge tags: ");
+        assert_eq!(titles_with(&user_id, &[&urgent], TagMatch::Any).await, vec!["TESTa", "TESTb"]);
+        assert_eq!(get_task_tags(&ids[0]).await.expect("Failed to get tags: ").len(), 1);
+
+        let tags = untag_task(&ids[1], &urgent).await.expect("Failed to untag task: ");
+        assert!(tags.is_empty());
+        let result = untag_task(&ids[1], &urgent).await;
+        assert!(matches!(result, Err(DBEditError::NotFound(_))), "Expected not found error for a missing tag");
+
+        // Deleting the tag removes it from its tasks
+        delete_tag_by_id(&urgent).await.expect("Failed to delete tag: ");
+        assert!(get_task_tags(&ids[0]).await.expect("Failed to get tags: ").is_empty());
+    }
+}
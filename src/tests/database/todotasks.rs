@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod creating {
     use crate::database::{connect, clear_all_test, todotask::create_task, users::create_user};
+    use crate::model::todotask::TaskRequest;
 
     #[tokio::test]
     #[allow(dead_code)]
@@ -17,7 +18,11 @@ mod creating {
         let user_id = user.id.unwrap().id.to_string();
 
         // Create a todo task
-        let task = create_task(&user_id, "TESTtask", Some("TESTdescription"), None, None).await;
+        let task = create_task(&user_id, &TaskRequest {
+            title: Some("TESTtask".to_string()),
+            description: Some("TESTdescription".to_string()),
+            ..Default::default()
+        }).await;
 
         // Assert that the todo task was created successfully
         assert!(task.is_ok(), "Failed to create todo task: {:?}", task.err());
@@ -30,14 +35,398 @@ mod creating {
     }
 }
 
+#[cfg(test)]
 mod reading {
-    
+    use crate::database::{connect, clear_all_test, todotask::{create_task, get_tasks_by_user}, users::create_user, DBReadError};
+    use crate::model::todotask::{SortDirection, TaskQuery, TaskRequest, TaskSort};
+
+    /// Create a user with five tasks, the odd ones are completed
+    async fn create_user_with_tasks() -> String {
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        for i in 1..=5 {
+            let completed_at = if i % 2 == 1 { Some(format!("2024-01-0{}T12:00:00Z", i)) } else { None };
+            create_task(&user_id, &TaskRequest {
+                title: Some(format!("TESTtask {}", i)),
+                description: Some(if i == 3 { "Buy Milk" } else { "TESTdescription" }.to_string()),
+                completed_at,
+                created_at: Some(format!("2024-01-0{}T00:00:00Z", i)),
+                ..Default::default()
+            }).await.expect("Failed to create task: ");
+        }
+
+        user_id
+    }
+
+    #[tokio::test]
+    /// Test filtering a users tasks
+    /// This test checks the completed, created_after and search filters each only return the matching tasks.
+    async fn filter_tasks() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+        let user_id = create_user_with_tasks().await;
+
+        // Completed
+        let query = TaskQuery { completed: Some(true), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 3, "Expected the 3 completed tasks");
+
+        // Created after
+        let query = TaskQuery { created_after: Some("2024-01-03T00:00:00Z".to_string()), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 2, "Expected the 2 tasks created after the 3rd");
+
+        // Search the description ignoring case
+        let query = TaskQuery { search: Some("milk".to_string()), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &query).await.expect("Failed to get tasks: ");
+        assert_eq!(page.tasks.len(), 1, "Expected the 1 task mentioning milk");
+        assert_eq!(page.tasks[0].title, Some("TESTtask 3".to_string()));
+
+        // A bad time is reported as bad data
+        let query = TaskQuery { created_before: Some("yesterday".to_string()), ..Default::default() };
+        let page = get_tasks_by_user(&user_id, &query).await;
+        assert!(matches!(page, Err(DBReadError::BadData(_))), "Expected bad data error for an invalid time");
+    }
+
+    #[tokio::test]
+    /// Test sorting and paging through a users tasks
+    /// This test pages through the tasks two at a time and checks every task is returned once in order.
+    async fn page_through_tasks() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+        let user_id = create_user_with_tasks().await;
+
+        let mut query = TaskQuery {
+            sort: Some(TaskSort::Title),
+            direction: Some(SortDirection::Desc),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut titles = Vec::new();
+        loop {
+            let page = get_tasks_by_user(&user_id, &query).await.expect("Failed to get tasks: ");
+            assert!(page.tasks.len() <= 2, "Page is larger than the limit");
+            titles.extend(page.tasks.into_iter().map(|t| t.title.unwrap()));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(titles, vec!["TESTtask 5", "TESTtask 4", "TESTtask 3", "TESTtask 2", "TESTtask 1"]);
+
+        // Tasks which are not completed have no completed_at, they should still be paged through
+        let mut query = TaskQuery { sort: Some(TaskSort::CompletedAt), limit: Some(2), ..Default::default() };
+        let mut count = 0;
+        loop {
+            let page = get_tasks_by_user(&user_id, &query).await.expect("Failed to get tasks: ");
+            count += page.tasks.len();
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(count, 5, "Expected every task once when sorting by completed_at");
+
+        // A cursor cant be used with a different sort
+        query.sort = Some(TaskSort::Title);
+        query.cursor = get_tasks_by_user(&user_id, &TaskQuery { limit: Some(1), ..Default::default() }).await.unwrap().next_cursor;
+        let page = get_tasks_by_user(&user_id, &query).await;
+        assert!(matches!(page, Err(DBReadError::BadData(_))), "Expected bad data error for a cursor from another sort");
+    }
 }
 
 mod editing {
 
 }
 
+#[cfg(test)]
+mod due_dates {
+    use chrono::{Duration, Utc};
+    use crate::database::{connect, clear_all_test, todotask::{create_task, edit_task_by_id, get_overdue_tasks, get_upcoming_tasks}, users::create_user, DBCreateError, DBEditError};
+    use crate::model::todotask::TaskRequest;
+
+    /// Make the request for a task with the given times
+    fn task_request(title: &str, completed_at: Option<&str>, due_at: Option<&str>, remind_at: Option<&str>) -> TaskRequest {
+        TaskRequest {
+            title: Some(title.to_string()),
+            completed_at: completed_at.map(str::to_string),
+            due_at: due_at.map(str::to_string),
+            remind_at: remind_at.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    /// Test the overdue and upcoming views
+    /// This test creates tasks due at different times and checks each view only returns the incomplete tasks due in its window.
+    async fn overdue_and_upcoming() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let yesterday = (Utc::now() - Duration::days(1)).to_rfc3339();
+        let in_two_days = (Utc::now() + Duration::days(2)).to_rfc3339();
+        let in_a_month = (Utc::now() + Duration::days(30)).to_rfc3339();
+
+        create_task(&user_id, &task_request("TESToverdue", None, Some(&yesterday), None)).await.expect("Failed to create task: ");
+        create_task(&user_id, &task_request("TESTdone", Some(&yesterday), Some(&yesterday), None)).await.expect("Failed to create task: ");
+        create_task(&user_id, &task_request("TESTsoon", None, Some(&in_two_days), Some(&yesterday))).await.expect("Failed to create task: ");
+        create_task(&user_id, &task_request("TESTlater", None, Some(&in_a_month), None)).await.expect("Failed to create task: ");
+        create_task(&user_id, &task_request("TESTundated", None, None, None)).await.expect("Failed to create task: ");
+
+        let overdue = get_overdue_tasks(&user_id).await.expect("Failed to get overdue tasks: ");
+        let titles: Vec<_> = overdue.into_iter().map(|t| t.title.unwrap()).collect();
+        assert_eq!(titles, vec!["TESToverdue"]);
+
+        let upcoming = get_upcoming_tasks(&user_id, Duration::days(7)).await.expect("Failed to get upcoming tasks: ");
+        let titles: Vec<_> = upcoming.into_iter().map(|t| t.title.unwrap()).collect();
+        assert_eq!(titles, vec!["TESTsoon"]);
+    }
+
+    #[tokio::test]
+    /// Test a reminder can't be after the due date
+    /// This test checks both creating and editing a task reject a reminder after the due date.
+    async fn reminder_after_due_date() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let task = create_task(&user_id, &task_request("TESTtask", None, Some("2024-01-02T00:00:00Z"), Some("2024-01-03T00:00:00Z"))).await;
+        assert!(matches!(task, Err(DBCreateError::BadData(_))), "Expected bad data error for a reminder after the due date");
+
+        let task = create_task(&user_id, &task_request("TESTtask", None, Some("2024-01-02T00:00:00Z"), Some("2024-01-01T00:00:00Z"))).await.expect("Failed to create task: ");
+        let task_id = task.id.unwrap().id.to_string();
+        let edited = edit_task_by_id(&task_id, &TaskRequest { remind_at: Some("2024-01-05T00:00:00Z".to_string()), ..Default::default() }, None, None, &user_id).await;
+        assert!(matches!(edited, Err(DBEditError::BadData(_))), "Expected bad data error for a reminder after the due date");
+
+        let edited = edit_task_by_id(&task_id, &TaskRequest { due_at: Some("2024-01-10T00:00:00Z".to_string()), remind_at: Some("2024-01-05T00:00:00Z".to_string()), ..Default::default() }, None, None, &user_id).await.expect("Failed to edit task: ");
+        assert!(edited.remind_at.is_some() && edited.due_at.is_some());
+    }
+}
+
+#[cfg(test)]
+mod recurring {
+    use crate::database::{connect, clear_all_test, todotask::{create_task, edit_task_by_id, get_task_completions}, users::create_user, DBCreateError};
+    use crate::model::todotask::TaskRequest;
+
+    #[tokio::test]
+    /// Test completing a recurring task
+    /// This test completes a task which repeats twice, checking the first completion moves it on and the second completes it.
+    async fn complete_recurring_task() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let task = create_task(&user_id, &TaskRequest {
+            title: Some("TESTchore".to_string()),
+            due_at: Some("2024-01-01T09:00:00Z".to_string()),
+            remind_at: Some("2024-01-01T08:00:00Z".to_string()),
+            recurrence: Some("freq=weekly;count=2".to_string()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ");
+        assert_eq!(task.recurrence, Some("FREQ=WEEKLY;INTERVAL=1;COUNT=2".to_string()), "The rule should be normalised");
+        let task_id = task.id.unwrap().id.to_string();
+
+        // Completing the first occurrence moves the task on a week, keeping the reminder an hour before
+        let complete = TaskRequest { completed_at: Some("2024-01-01T10:00:00Z".to_string()), ..Default::default() };
+        let task = edit_task_by_id(&task_id, &complete, None, None, &user_id).await.expect("Failed to complete task: ");
+        assert_eq!(task.completed_at, None, "The task should not be completed yet");
+        assert!(task.due_at.as_deref().unwrap().starts_with("2024-01-08T09:00:00"), "Unexpected due_at {:?}", task.due_at);
+        assert!(task.remind_at.as_deref().unwrap().starts_with("2024-01-08T08:00:00"), "Unexpected remind_at {:?}", task.remind_at);
+        assert_eq!(task.completed_occurrences, Some(1));
+
+        // Completing the last occurrence completes the task
+        let task = edit_task_by_id(&task_id, &complete, None, None, &user_id).await.expect("Failed to complete task: ");
+        assert!(task.completed_at.is_some(), "The task should be completed");
+        assert_eq!(task.completed_occurrences, Some(2));
+
+        // Both completions are in the history
+        let completions = get_task_completions(&task_id).await.expect("Failed to get completions: ");
+        assert_eq!(completions.len(), 2);
+
+        // An invalid rule is rejected
+        let task = create_task(&user_id, &TaskRequest {
+            title: Some("TESTchore".to_string()),
+            recurrence: Some("FREQ=HOURLY".to_string()),
+            ..Default::default()
+        }).await;
+        assert!(matches!(task, Err(DBCreateError::BadData(_))), "Expected bad data error for an invalid rule");
+    }
+}
+
+#[cfg(test)]
+mod subtasks {
+    use crate::database::{config::SubtaskDeletePolicy, connect, clear_all_test, todotask::{create_task, delete_task_by_id, edit_task_by_id, get_subtasks, get_task_by_id, get_task_tree}, users::create_user, DBCreateError, DBEditError, DBReadError};
+    use crate::model::todotask::TaskRequest;
+
+    /// Create a task with an optional parent, returning its id
+    async fn subtask(owner: &str, title: &str, parent: Option<&str>) -> String {
+        let task = create_task(owner, &TaskRequest {
+            title: Some(title.to_string()),
+            parent: parent.map(|p| p.to_string()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ");
+        task.id.unwrap().id.to_raw()
+    }
+
+    #[tokio::test]
+    /// Test building the tree of a task
+    /// This test nests subtasks two deep and checks the tree and the completed count of each level.
+    async fn task_tree_and_progress() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let root = subtask(&user_id, "TESTroot", None).await;
+        let first = subtask(&user_id, "TESTfirst", Some(&root)).await;
+        let _second = subtask(&user_id, "TESTsecond", Some(&root)).await;
+        let nested = subtask(&user_id, "TESTnested", Some(&first)).await;
+
+        let complete = TaskRequest { completed_at: Some("2024-01-01T10:00:00Z".to_string()), ..Default::default() };
+        edit_task_by_id(&first, &complete, None, None, &user_id).await.expect("Failed to complete task: ");
+        edit_task_by_id(&nested, &complete, None, None, &user_id).await.expect("Failed to complete task: ");
+
+        let subtasks = get_subtasks(&root).await.expect("Failed to get subtasks: ");
+        assert_eq!(subtasks.len(), 2, "Only the direct subtasks should be returned");
+
+        let tree = get_task_tree(&root).await.expect("Failed to get tree: ");
+        assert_eq!((tree.progress.completed, tree.progress.total), (1, 2));
+        assert_eq!(tree.subtasks[0].task.title.as_deref(), Some("TESTfirst"));
+        assert_eq!((tree.subtasks[0].progress.completed, tree.subtasks[0].progress.total), (1, 1));
+        assert_eq!(tree.subtasks[0].subtasks[0].task.title.as_deref(), Some("TESTnested"));
+        assert_eq!(tree.subtasks[1].progress.total, 0);
+
+        // A task can't be moved below one of its own subtasks
+        let cycle = TaskRequest { parent: Some(nested.clone()), ..Default::default() };
+        let result = edit_task_by_id(&root, &cycle, None, None, &user_id).await;
+        assert!(matches!(result, Err(DBEditError::BadData(_))), "Expected bad data error for a cycle");
+
+        // A task can't be its own parent
+        let cycle = TaskRequest { parent: Some(root.clone()), ..Default::default() };
+        let result = edit_task_by_id(&root, &cycle, None, None, &user_id).await;
+        assert!(matches!(result, Err(DBEditError::BadData(_))), "Expected bad data error for a cycle");
+
+        // The parent must belong to the same user
+        let other = create_user("TESTother", "TESTother@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let result = create_task(&other.id.unwrap().id.to_string(), &TaskRequest {
+            title: Some("TESTforeign".to_string()),
+            parent: Some(root.clone()),
+            ..Default::default()
+        }).await;
+        assert!(matches!(result, Err(DBCreateError::BadData(_))), "Expected bad data error for another users parent");
+
+        // An empty parent makes the task top level again
+        let detach = TaskRequest { parent: Some(String::new()), ..Default::default() };
+        let task = edit_task_by_id(&nested, &detach, None, None, &user_id).await.expect("Failed to detach task: ");
+        assert!(task.parent.is_none(), "The task should be top level");
+    }
+
+    #[tokio::test]
+    /// Test each policy for the subtasks of a deleted task
+    async fn delete_policies() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TESTemail@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+
+        let root = subtask(&user_id, "TESTroot", None).await;
+        let middle = subtask(&user_id, "TESTmiddle", Some(&root)).await;
+        let leaf = subtask(&user_id, "TESTleaf", Some(&middle)).await;
+
+        // Restrict refuses to delete a task with subtasks
+        let result = delete_task_by_id(&middle, SubtaskDeletePolicy::Restrict, &user_id).await;
+        assert!(matches!(result, Err(DBReadError::BadData(_))), "Expected bad data error with subtasks");
+
+        // Promote moves the subtasks up to the parent of the deleted task
+        delete_task_by_id(&middle, SubtaskDeletePolicy::Promote, &user_id).await.expect("Failed to delete task: ");
+        let leaf_task = get_task_by_id(&leaf).await.expect("The subtask should still exist: ");
+        assert_eq!(leaf_task.parent.map(|p| p.id.to_raw()), Some(root.clone()));
+
+        // Cascade deletes every level of subtasks
+        let nested = subtask(&user_id, "TESTnested", Some(&leaf)).await;
+        delete_task_by_id(&root, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        assert!(matches!(get_task_by_id(&leaf).await, Err(DBReadError::NotFound(_))), "The subtask should be deleted");
+        assert!(matches!(get_task_by_id(&nested).await, Err(DBReadError::NotFound(_))), "The nested subtask should be deleted");
+    }
+}
+
+#[cfg(test)]
 mod deleting {
+    use crate::database::{config::SubtaskDeletePolicy, connect, clear_all_test, todotask::{create_task, delete_task_by_id, get_task_by_id, get_tasks_by_user, get_trash, purge_task_by_id, purge_trash, restore_task_by_id}, users::create_user, DBReadError};
+    use crate::model::todotask::{TaskQuery, TaskRequest};
+
+    #[tokio::test]
+    /// Test the trash
+    /// This test deletes a task with a subtask, checks they are hidden and in the trash, restores them and then deletes them permanently.
+    async fn trash_restore_and_purge() {
+        // Connect to the database and clear everything related to previous tests
+        let _ = connect().await;
+        let _ = clear_all_test().await;
 
+        let user = create_user("TESTtrashuser", "TESTtrash@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_raw();
+
+        let parent = create_task(&user_id, &TaskRequest {
+            title: Some("TESTtrash parent".to_string()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ").id.unwrap().id.to_raw();
+        let child = create_task(&user_id, &TaskRequest {
+            title: Some("TESTtrash child".to_string()),
+            parent: Some(parent.clone()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ").id.unwrap().id.to_raw();
+
+        // Deleting moves both tasks to the trash, only the parent is listed
+        let deleted = delete_task_by_id(&parent, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        assert!(deleted.deleted_at.is_some(), "The task should be marked as deleted");
+        assert!(matches!(get_task_by_id(&parent).await, Err(DBReadError::NotFound(_))), "The task should be hidden");
+        assert!(matches!(get_task_by_id(&child).await, Err(DBReadError::NotFound(_))), "The subtask should be hidden");
+        assert!(get_tasks_by_user(&user_id, &TaskQuery::default()).await.unwrap().tasks.is_empty(), "Deleted tasks should not be listed");
+        let trash = get_trash(&user_id).await.unwrap();
+        assert_eq!(trash.len(), 1, "Only the deleted task should be in the trash");
+        assert_eq!(trash[0].id.as_ref().unwrap().id.to_raw(), parent);
+
+        // Restoring brings the subtask back with it
+        let restored = restore_task_by_id(&parent, &user_id).await.expect("Failed to restore task: ");
+        assert!(restored.deleted_at.is_none(), "The task should not be marked as deleted");
+        let child_task = get_task_by_id(&child).await.expect("The subtask should be restored: ");
+        assert_eq!(child_task.parent.unwrap().id.to_raw(), parent, "The subtask should keep its parent");
+        assert!(get_trash(&user_id).await.unwrap().is_empty());
+
+        // A task which isn't in the trash can't be deleted permanently
+        assert!(matches!(purge_task_by_id(&parent, &user_id).await, Err(DBReadError::NotFound(_))));
+
+        // Deleting permanently removes the task and its subtask
+        delete_task_by_id(&parent, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        purge_task_by_id(&parent, &user_id).await.expect("Failed to purge task: ");
+        assert!(get_trash(&user_id).await.unwrap().is_empty());
+        assert!(matches!(restore_task_by_id(&child, &user_id).await, Err(crate::database::DBEditError::NotFound(_))), "The subtask should be gone");
+
+        // The background purge only removes tasks older than the retention window
+        let task = create_task(&user_id, &TaskRequest {
+            title: Some("TESTtrash old".to_string()),
+            ..Default::default()
+        }).await.expect("Failed to create task: ").id.unwrap().id.to_raw();
+        delete_task_by_id(&task, SubtaskDeletePolicy::Cascade, &user_id).await.expect("Failed to delete task: ");
+        assert_eq!(purge_trash(chrono::Duration::days(30)).await.unwrap(), 0, "A new task should be kept");
+        assert!(purge_trash(chrono::Duration::zero()).await.unwrap() >= 1, "An expired task should be purged");
+        assert!(get_trash(&user_id).await.unwrap().is_empty());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod rotating {
+    use chrono::Duration;
+    use crate::database::{clear_all_test, connect, users::create_user, DBTokenError};
+    use crate::database::tokens::{create_refresh_token, rotate_refresh_token};
+
+    #[tokio::test]
+    /// Test a refresh token can be rotated once, and using it again revokes the whole family
+    async fn rotate_refresh_token_reuse() {
+        // Connect to the database and clear test data
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        // Create a user and a refresh token for them
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let token = create_refresh_token(&user_id, None, Duration::days(1)).await.expect("Failed to create refresh token: ");
+
+        // Rotate the token
+        let rotated = rotate_refresh_token(&token, Duration::days(1)).await;
+        assert!(rotated.is_ok(), "Failed to rotate refresh token: {:?}", rotated.err());
+        let (rotated_user_id, new_token) = rotated.unwrap();
+        assert_eq!(rotated_user_id, user_id, "User ID does not match");
+        assert_ne!(new_token, token, "Expected a new refresh token");
+
+        // Replay the old token
+        let replayed = rotate_refresh_token(&token, Duration::days(1)).await;
+        assert!(matches!(replayed, Err(DBTokenError::Reused(_))), "Expected reuse to be detected: {:?}", replayed);
+
+        // The new token should have been revoked along with the rest of the family
+        let revoked = rotate_refresh_token(&new_token, Duration::days(1)).await;
+        assert!(revoked.is_err(), "Expected the family to be revoked");
+    }
+
+    #[tokio::test]
+    /// Test expired and unknown refresh tokens are rejected
+    async fn rotate_refresh_token_invalid() {
+        // Connect to the database and clear test data
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        // Create a user and an already expired refresh token
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let token = create_refresh_token(&user_id, None, Duration::seconds(-1)).await.expect("Failed to create refresh token: ");
+
+        let expired = rotate_refresh_token(&token, Duration::days(1)).await;
+        assert!(matches!(expired, Err(DBTokenError::Expired(_))), "Expected expired error: {:?}", expired);
+
+        let unknown = rotate_refresh_token("TESTnotatoken", Duration::days(1)).await;
+        assert!(matches!(unknown, Err(DBTokenError::NotFound(_))), "Expected not found error: {:?}", unknown);
+    }
+}
+
+#[cfg(test)]
+mod revoking {
+    use chrono::{Duration, Utc};
+    use crate::database::{clear_all_test, connect, users::{create_user, delete_user, edit_existing_user}};
+    use crate::database::tokens::{get_token_generation, is_token_revoked, revoke_all_tokens, revoke_token};
+
+    #[tokio::test]
+    /// Test revoking a single token only revokes that token
+    async fn revoke_single_token() {
+        // Connect to the database and clear test data
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let generation = get_token_generation(&user_id).await.expect("Failed to get generation: ");
+
+        // Revoke one token
+        let revoked = revoke_token(&user_id, "TESTjti1", Utc::now() + Duration::minutes(5)).await;
+        assert!(revoked.is_ok(), "Failed to revoke token: {:?}", revoked.err());
+
+        // Check only that token is revoked
+        assert!(is_token_revoked(&user_id, "TESTjti1", generation).await.unwrap(), "Expected token to be revoked");
+        assert!(!is_token_revoked(&user_id, "TESTjti2", generation).await.unwrap(), "Expected other token to be valid");
+    }
+
+    #[tokio::test]
+    /// Test logging out everywhere, changing password and deleting the account revoke older tokens
+    async fn revoke_all_user_tokens() {
+        // Connect to the database and clear test data
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        let user = create_user("TESTuser", "TEST@example.com", "TESTpassword").await.expect("Failed to create user: ");
+        let user_id = user.id.unwrap().id.to_string();
+        let generation = get_token_generation(&user_id).await.expect("Failed to get generation: ");
+
+        // Log out everywhere
+        let revoked = revoke_all_tokens(&user_id).await;
+        assert!(revoked.is_ok(), "Failed to revoke tokens: {:?}", revoked.err());
+        assert!(is_token_revoked(&user_id, "TESTjti", generation).await.unwrap(), "Expected old generation to be revoked");
+
+        // Change password
+        let generation = get_token_generation(&user_id).await.expect("Failed to get generation: ");
+        let edited = edit_existing_user(&user_id, None, None, Some("TESTnewpassword"), None).await;
+        assert!(edited.is_ok(), "Failed to edit user: {:?}", edited.err());
+        assert!(is_token_revoked(&user_id, "TESTjti", generation).await.unwrap(), "Expected password change to revoke tokens");
+
+        // Delete the account
+        let generation = get_token_generation(&user_id).await.expect("Failed to get generation: ");
+        let deleted = delete_user(&user_id).await;
+        assert!(deleted.is_ok(), "Failed to delete user: {:?}", deleted.err());
+        assert!(is_token_revoked(&user_id, "TESTjti", generation).await.unwrap(), "Expected deleting the user to revoke tokens");
+    }
+}
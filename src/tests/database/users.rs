@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod creating {
-    use crate::database::{clear_all_test, connect, users::create_user};
+    use crate::database::{clear_all_test, connect, users::create_user, DBCreateError};
 
     #[tokio::test]
     /// Test creating a user
@@ -14,6 +14,10 @@ mod creating {
 
         // Check there were no errors
         assert!(user.is_ok(), "Failed to create user: {:?}", user.err());
+
+        // Check the password was not stored in plaintext
+        let password = user.unwrap().password.unwrap_or_default();
+        assert!(password.starts_with("$argon2id$"), "Password was not hashed: {}", password);
     }
 
     #[tokio::test]
@@ -32,8 +36,8 @@ mod creating {
         // Create another user with the same username
         let user2 = create_user("TESTuser", "TEST2@example.com", "TESTpassword").await;
         
-        // Check there was an error
-        assert!(user2.is_err(), "Expected error when creating user with duplicate username")
+        // Check the error says the username is taken
+        assert!(matches!(&user2, Err(DBCreateError::AlreadyExists(field)) if field == "username"), "Expected the username to be taken: {:?}", user2.err())
     }
 
     #[tokio::test]
@@ -52,15 +56,15 @@ mod creating {
         // Create another user with the same email
         let user2 = create_user("TESTuser2", "TEST@example.com", "TESTpassword").await;
         
-        // Check there was an error
-        assert!(user2.is_err(), "Expected error when creating user with duplicate email")
+        // Check the error says the email is taken
+        assert!(matches!(&user2, Err(DBCreateError::AlreadyExists(field)) if field == "email"), "Expected the email to be taken: {:?}", user2.err())
     }
 }
 
 #[cfg(test)]
 mod editing {
-    use crate::database::users::{create_user, edit_existing_user};
-    use crate::database::{connect, clear_all_test};
+    use crate::database::users::{check_availability, create_user, edit_existing_user};
+    use crate::database::{connect, clear_all_test, DBEditError};
 
     #[tokio::test]
     /// Test correctly updating user information
@@ -78,7 +82,7 @@ mod editing {
 
         // Edit the user
         let id = user.id.unwrap().id.to_string();
-        let edited = edit_existing_user(&id, Some("TESTuserNEW"), Some("TESTnew@example.com"), Some("TESTnewpassword")).await;
+        let edited = edit_existing_user(&id, Some("TESTuserNEW"), Some("TESTnew@example.com"), Some("TESTnewpassword"), None).await;
 
         // Ensure there are no errors
         assert!(edited.is_ok(), "Couldn't edit user: {:?}", edited.err());
@@ -100,11 +104,35 @@ mod editing {
 
         // Edit the user with nothing in the function
         let id = user.id.unwrap().id.to_string();
-        let edited = edit_existing_user(&id, None, None, None).await;
+        let edited = edit_existing_user(&id, None, None, None, None).await;
 
         // Check there is an error
         assert!(edited.is_err(), "Expected error when updating user with nothing")
     }
+
+    #[tokio::test]
+    /// Test changing the username to one another user has
+    async fn update_user_taken_username() {
+        // Connect to the database and clear all test data
+        let _ = connect().await;
+        let _ = clear_all_test().await;
+
+        // Create two users
+        let _ = create_user("TESTuser1", "TEST1@example.com", "TESTpassword").await.expect("Couldn't create user1: ");
+        let user2 = create_user("TESTuser2", "TEST2@example.com", "TESTpassword").await.expect("Couldn't create user2: ");
+
+        // Take the username of the first user
+        let id = user2.id.unwrap().id.to_string();
+        let edited = edit_existing_user(&id, Some("TESTuser1"), None, None, None).await;
+
+        // Check the error says the username is taken
+        assert!(matches!(&edited, Err(DBEditError::AlreadyExists(field)) if field == "username"), "Expected the username to be taken: {:?}", edited.err());
+
+        // Check availability agrees
+        let availability = check_availability(Some("TESTuser1"), Some("TESTfree@example.com")).await.expect("Couldn't check availability: ");
+        assert_eq!(availability.username, Some(false));
+        assert_eq!(availability.email, Some(true));
+    }
 }
 
 #[cfg(test)]